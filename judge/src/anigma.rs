@@ -1,12 +1,17 @@
 use crate::checker::Verdict;
-use crate::executer::{execute_sandboxed, ExecutionLimits, ExecutionSpec, ExecutionStatus};
+use crate::executer::{
+    execute_sandboxed, ExecutionLimits, ExecutionSpec, ExecutionStatus, SandboxSession,
+};
 use crate::judger::{compare_output, JudgeResult, TestcaseResult};
+use crate::metrics::{metrics, Timing};
+use crate::progress::{ProgressEvent, ProgressSink, ProgressSubject};
 use crate::sandbox::get_config;
 use crate::storage::StorageClient;
 use crate::utils::extract_zip;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::time::Instant;
 
 /// Task 1: 사용자가 input 파일을 제출하여 A와 B의 출력이 다른지 확인
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,6 +38,9 @@ pub struct AnigmaJudgeJob {
     pub testcases: Vec<AnigmaTestcase>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub checker_path: Option<String>,
+    /// Run every testcase instead of stopping at the first failure
+    #[serde(default)]
+    pub full_run: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -56,12 +64,21 @@ impl AnigmaJudgeResult {
             edit_distance: None,
         }
     }
+
+    pub fn cancelled(submission_id: i64) -> Self {
+        Self {
+            base: JudgeResult::cancelled(submission_id),
+            edit_distance: None,
+        }
+    }
 }
 
 pub async fn process_anigma_job(
     job: &AnigmaJudgeJob,
     storage: &StorageClient,
+    progress: &mut dyn ProgressSink,
 ) -> Result<AnigmaJudgeResult> {
+    let subject = ProgressSubject::SubmissionId(job.submission_id);
     let temp_dir = tempfile::tempdir()?;
 
     // 1. zip 파일 다운로드 및 압축 해제
@@ -83,6 +100,7 @@ pub async fn process_anigma_job(
                 execution_time: None,
                 memory_used: None,
                 testcase_results: vec![],
+                subtask_results: vec![],
                 error_message: Some("Makefile not found".into()),
             },
             edit_distance: None,
@@ -99,7 +117,16 @@ pub async fn process_anigma_job(
         })
         .with_copy_out_dir(temp_dir.path());
 
+    progress.publish(subject, ProgressEvent::CompileStart).await;
     let build_result = execute_sandboxed(&build_spec).await?;
+    progress
+        .publish(
+            subject,
+            ProgressEvent::CompileDone {
+                success: build_result.is_success(),
+            },
+        )
+        .await;
 
     if !build_result.is_success() {
         tracing::error!(
@@ -117,6 +144,7 @@ pub async fn process_anigma_job(
                 execution_time: None,
                 memory_used: None,
                 testcase_results: vec![],
+                subtask_results: vec![],
                 error_message: Some(build_result.stderr),
             },
             edit_distance: None,
@@ -129,99 +157,133 @@ pub async fn process_anigma_job(
     let mut max_time_ms: u32 = 0;
     let mut max_memory_kb: u32 = 0;
 
-    for tc in &job.testcases {
-        let input_data = storage.download(&tc.input_path).await?;
-        let input_file = temp_dir.path().join("input.txt");
-        std::fs::write(&input_file, &input_data)?;
-
-        // make run file=input.txt
-        // 주의: sandbox 내부에서는 상대 경로로 접근해야 함
-        // Python 심볼릭 링크 생성 후 make 실행 (python 명령이 없을 경우를 대비)
-        // /usr/bin에는 쓰기 권한이 없을 수 있으므로 현재 디렉토리에 링크를 만들고 PATH에 추가
-        let input_arg = "file=input.txt".to_string();
-        let make_cmd = format!("mkdir -p bin && ln -sf /usr/bin/python3 bin/python 2>/dev/null; export PATH=\"$PWD/bin:$PATH\"; make -s run {}", input_arg);
-
-        let run_spec = ExecutionSpec::new(temp_dir.path())
-            .with_command(vec!["sh".to_string(), "-c".to_string(), make_cmd])
-            .with_limits(ExecutionLimits {
-                time_ms: job.time_limit,
-                memory_mb: job.memory_limit,
-            });
-
-        let run_result = execute_sandboxed(&run_spec).await?;
-
-        max_time_ms = max_time_ms.max(run_result.time_ms);
-        max_memory_kb = max_memory_kb.max(run_result.memory_kb);
-
-        // 디버깅용 로그
-        tracing::info!(
-            "ANIGMA testcase {} result: status={:?}, stdout={}, stderr={}",
-            tc.id,
-            run_result.status,
-            run_result.stdout.chars().take(100).collect::<String>(),
-            run_result.stderr.chars().take(100).collect::<String>()
-        );
-
-        let verdict = match run_result.status {
-            ExecutionStatus::Exited(0) => {
-                // Download expected output as bytes (supports both text and binary)
-                let expected_bytes = storage.download(&tc.expected_output_path).await?;
-                
-                // Check if expected output is valid UTF-8 text
-                match String::from_utf8(expected_bytes.clone()) {
-                    Ok(expected_str) => {
-                        // Text output: use compare_output for line ending normalization
-                        if compare_output(&run_result.stdout, &expected_str) {
-                            Verdict::Accepted
-                        } else {
-                            Verdict::WrongAnswer
+    // 빌드 결과물이 담긴 박스 하나를 모든 테스트케이스에서 재사용
+    let session = SandboxSession::for_slot(temp_dir.path(), 0).await?;
+
+    // 테스트케이스 실행 중 실패해도 박스는 반드시 정리
+    let judged: Result<()> = async {
+        for (idx, tc) in job.testcases.iter().enumerate() {
+            let input_data = storage.download_testcase(&tc.input_path).await?;
+            let input_file = temp_dir.path().join("input.txt");
+            std::fs::write(&input_file, &input_data)?;
+            session.copy_in(&input_file, "input.txt").await?;
+
+            // make run file=input.txt
+            // 주의: sandbox 내부에서는 상대 경로로 접근해야 함
+            // Python 심볼릭 링크 생성 후 make 실행 (python 명령이 없을 경우를 대비)
+            // /usr/bin에는 쓰기 권한이 없을 수 있으므로 현재 디렉토리에 링크를 만들고 PATH에 추가
+            let input_arg = "file=input.txt".to_string();
+            let make_cmd = format!("mkdir -p bin && ln -sf /usr/bin/python3 bin/python 2>/dev/null; export PATH=\"$PWD/bin:$PATH\"; make -s run {}", input_arg);
+
+            let run_spec = ExecutionSpec::new(temp_dir.path())
+                .with_command(vec!["sh".to_string(), "-c".to_string(), make_cmd])
+                .with_limits(ExecutionLimits {
+                    time_ms: job.time_limit,
+                    memory_mb: job.memory_limit,
+                });
+
+            let started = Instant::now();
+            let run_result = session.run(&run_spec).await?;
+            metrics().observe(Timing::TestcaseRun, started.elapsed());
+            session.reset().await?;
+
+            max_time_ms = max_time_ms.max(run_result.time_ms);
+            max_memory_kb = max_memory_kb.max(run_result.memory_kb);
+
+            // 디버깅용 로그
+            tracing::info!(
+                "ANIGMA testcase {} result: status={:?}, stdout={}, stderr={}",
+                tc.id,
+                run_result.status,
+                run_result.stdout.chars().take(100).collect::<String>(),
+                run_result.stderr.chars().take(100).collect::<String>()
+            );
+
+            let verdict = match run_result.status {
+                ExecutionStatus::Exited(0) => {
+                    // Download expected output as bytes (supports both text and binary)
+                    let expected_bytes = storage.download_testcase(&tc.expected_output_path).await?;
+                    
+                    // Check if expected output is valid UTF-8 text
+                    match String::from_utf8(expected_bytes.clone()) {
+                        Ok(expected_str) => {
+                            // Text output: use compare_output for line ending normalization
+                            if compare_output(&run_result.stdout, &expected_str) {
+                                Verdict::Accepted
+                            } else {
+                                Verdict::WrongAnswer
+                            }
                         }
-                    }
-                    Err(_) => {
-                        // Binary output: compare bytes exactly using raw stdout_bytes
-                        if run_result.stdout_bytes == expected_bytes.as_slice() {
-                            Verdict::Accepted
-                        } else {
-                            Verdict::WrongAnswer
+                        Err(_) => {
+                            // Binary output: compare bytes exactly using raw stdout_bytes
+                            if run_result.stdout_bytes == expected_bytes.as_slice() {
+                                Verdict::Accepted
+                            } else {
+                                Verdict::WrongAnswer
+                            }
                         }
                     }
                 }
-            }
-            ExecutionStatus::Exited(_) => Verdict::WrongAnswer,
-            ExecutionStatus::TimeLimitExceeded => Verdict::TimeLimitExceeded,
-            ExecutionStatus::MemoryLimitExceeded => Verdict::MemoryLimitExceeded,
-            _ => Verdict::WrongAnswer,
-        };
-
-        // stderr가 있으면 output에 함께 포함
-        let output = if run_result.stderr.is_empty() {
-            run_result.stdout.clone()
-        } else {
-            format!(
-                "=== stdout ===\n{}\n=== stderr ===\n{}",
-                run_result.stdout, run_result.stderr
-            )
-        };
-
-        let (execution_time, memory_used) = if verdict == Verdict::Accepted {
-            (Some(run_result.time_ms), Some(run_result.memory_kb))
-        } else {
-            (None, None)
-        };
-
-        testcase_results.push(TestcaseResult {
-            testcase_id: tc.id,
-            verdict: verdict.to_string(),
-            execution_time,
-            memory_used,
-            output: Some(output.chars().take(4096).collect()),
-        });
+                ExecutionStatus::Exited(_) => Verdict::WrongAnswer,
+                ExecutionStatus::TimeLimitExceeded => Verdict::TimeLimitExceeded,
+                ExecutionStatus::MemoryLimitExceeded => Verdict::MemoryLimitExceeded,
+                ExecutionStatus::OutputLimitExceeded => Verdict::OutputLimitExceeded,
+                _ => Verdict::WrongAnswer,
+            };
+
+            // stderr가 있으면 output에 함께 포함
+            let output = if run_result.stderr.is_empty() {
+                run_result.stdout.clone()
+            } else {
+                format!(
+                    "=== stdout ===\n{}\n=== stderr ===\n{}",
+                    run_result.stdout, run_result.stderr
+                )
+            };
+
+            let (execution_time, memory_used) = if verdict == Verdict::Accepted || job.full_run {
+                (Some(run_result.time_ms), Some(run_result.memory_kb))
+            } else {
+                (None, None)
+            };
+
+            progress
+                .publish(
+                    subject,
+                    ProgressEvent::testcase(
+                        tc.id,
+                        idx + 1,
+                        job.testcases.len(),
+                        &verdict,
+                        execution_time,
+                        memory_used,
+                    ),
+                )
+                .await;
+
+            testcase_results.push(TestcaseResult {
+                testcase_id: tc.id,
+                verdict: verdict.to_string(),
+                execution_time,
+                memory_used,
+                output: Some(output.chars().take(4096).collect()),
+                score: None,
+            });
 
-        if verdict != Verdict::Accepted && overall_verdict == Verdict::Accepted {
-            overall_verdict = verdict;
-            break;
+            if verdict != Verdict::Accepted && overall_verdict == Verdict::Accepted {
+                overall_verdict = verdict;
+                if !job.full_run {
+                    break;
+                }
+            }
         }
+        Ok(())
     }
+    .await;
+
+    let cleaned = session.cleanup().await;
+    judged?;
+    cleaned?;
 
     // 중단 이후 남은 테스트케이스는 모두 스킵 처리
     for i in testcase_results.len()..job.testcases.len() {
@@ -232,6 +294,7 @@ pub async fn process_anigma_job(
             execution_time: None,
             memory_used: None,
             output: None,
+            score: None,
         });
     }
 
@@ -284,6 +347,7 @@ pub async fn process_anigma_job(
                 None
             },
             testcase_results,
+            subtask_results: vec![],
             error_message: None,
         },
         edit_distance,
@@ -366,15 +430,19 @@ async fn extract_and_build(
 pub async fn process_anigma_task1_job(
     job: &AnigmaTask1JudgeJob,
     storage: &StorageClient,
+    progress: &mut dyn ProgressSink,
 ) -> Result<JudgeResult> {
     const TASK1_SCORE: i64 = 30;
+    let subject = ProgressSubject::SubmissionId(job.submission_id);
 
     // 1. 사용자가 제출한 input 파일 다운로드
     let input_data = storage.download(&job.input_path).await?;
 
     // 2. 코드 A (문제 제공 코드) ZIP 다운로드, 압축 해제, make build
+    progress.publish(subject, ProgressEvent::CompileStart).await;
     let code_a_dir = tempfile::tempdir()?;
     if let Err(e) = extract_and_build(storage, &job.reference_code_path, code_a_dir.path()).await {
+        progress.publish(subject, ProgressEvent::CompileDone { success: false }).await;
         return Ok(JudgeResult {
             submission_id: job.submission_id,
             verdict: Verdict::SystemError.to_string(),
@@ -382,6 +450,7 @@ pub async fn process_anigma_task1_job(
             execution_time: None,
             memory_used: None,
             testcase_results: vec![],
+            subtask_results: vec![],
             error_message: Some(format!("Code A build failed: {}", e)),
         });
     }
@@ -389,6 +458,7 @@ pub async fn process_anigma_task1_job(
     // 3. 코드 B (정답 코드) ZIP 다운로드, 압축 해제, make build
     let code_b_dir = tempfile::tempdir()?;
     if let Err(e) = extract_and_build(storage, &job.solution_code_path, code_b_dir.path()).await {
+        progress.publish(subject, ProgressEvent::CompileDone { success: false }).await;
         return Ok(JudgeResult {
             submission_id: job.submission_id,
             verdict: Verdict::SystemError.to_string(),
@@ -396,10 +466,13 @@ pub async fn process_anigma_task1_job(
             execution_time: None,
             memory_used: None,
             testcase_results: vec![],
+            subtask_results: vec![],
             error_message: Some(format!("Code B build failed: {}", e)),
         });
     }
 
+    progress.publish(subject, ProgressEvent::CompileDone { success: true }).await;
+
     // 4. input 파일을 각 디렉토리에 복사
     let input_filename = "input.bin";
     std::fs::write(code_a_dir.path().join(input_filename), &input_data)?;
@@ -520,6 +593,7 @@ pub async fn process_anigma_task1_job(
             None
         },
         testcase_results: vec![],
+        subtask_results: vec![],
         error_message,
     })
 }
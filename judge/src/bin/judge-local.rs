@@ -0,0 +1,533 @@
+//! Local judging CLI for problem setters
+//!
+//! Judges one source file against a problem directory with the worker's
+//! compile, run and check pipeline, without Redis or MinIO:
+//!
+//! ```text
+//! judge-local <problem-dir> <source-file> [--language <lang>] [--worker-id <id>]
+//! ```
+//!
+//! The problem directory holds a `problem.toml` and a `tests/` directory:
+//!
+//! ```toml
+//! time_limit = 1000        # ms
+//! memory_limit = 256       # MB
+//! output_limit = 256       # MB (optional)
+//! checker = "checker.cpp"  # testlib checker, makes it a special judge problem
+//! interactor = "interactor.cpp"
+//! validator = "validator.cpp"
+//! compare_mode = { mode = "tokens" }
+//! ```
+//!
+//! Every `tests/<name>.in` needs an answer in `tests/<name>.out` or
+//! `tests/<name>.ans`. Tests run in natural order (`2` before `10`). Like the
+//! worker, this needs isolate with cgroup support; the sandbox uses the box
+//! range of `--worker-id` (default 0), so pick one no running worker uses.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use judge::checker::{status_to_verdict, Verdict, DEFAULT_CHECKER_TIMEOUT_SECS};
+use judge::compiler::{
+    compile_checker, compile_in_sandbox, compile_interactor, compile_validator, TestlibTool,
+};
+use judge::executer::{
+    execute_sandboxed, ExecutionLimits, ExecutionOutcome, ExecutionSpec, ExecutionStatus,
+    DEFAULT_OUTPUT_LIMIT_KB,
+};
+use judge::interactor::run_interactive;
+use judge::judger::{check_output, CompareMode};
+use judge::languages::{self, LanguageConfig};
+use judge::sandbox::{self, get_config, WorkerLayout};
+use judge::validator::{run_validator, DEFAULT_VALIDATOR_TIMEOUT_SECS};
+
+const USAGE: &str =
+    "Usage: judge-local <problem-dir> <source-file> [--language <lang>] [--worker-id <id>]";
+
+/// testlib.h, written next to the problem tools when they are compiled
+const TESTLIB_H: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/files/testlib.h"));
+
+/// Command line arguments
+struct Args {
+    problem_dir: PathBuf,
+    source: PathBuf,
+    language: Option<String>,
+    worker_id: u32,
+}
+
+impl Args {
+    fn parse() -> Result<Self> {
+        let mut positional = Vec::new();
+        let mut language = None;
+        let mut worker_id = 0;
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--language" | "-l" => {
+                    language = Some(args.next().context("--language needs a value")?);
+                }
+                "--worker-id" => {
+                    let value = args.next().context("--worker-id needs a value")?;
+                    worker_id = value
+                        .parse()
+                        .with_context(|| format!("Invalid worker ID: {}", value))?;
+                }
+                "--help" | "-h" => bail!(USAGE),
+                _ if arg.starts_with('-') => bail!("Unknown option {}\n{}", arg, USAGE),
+                _ => positional.push(PathBuf::from(arg)),
+            }
+        }
+
+        let [problem_dir, source] = <[PathBuf; 2]>::try_from(positional).map_err(|_| {
+            anyhow::anyhow!("Expected a problem directory and a source file\n{}", USAGE)
+        })?;
+        Ok(Self {
+            problem_dir,
+            source,
+            language,
+            worker_id,
+        })
+    }
+}
+
+/// `problem.toml` of a problem directory
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProblemConfig {
+    /// Time limit in ms
+    time_limit: u32,
+    /// Memory limit in MB
+    memory_limit: u32,
+    /// Output limit in MB
+    #[serde(default = "default_output_limit")]
+    output_limit: u32,
+    /// testlib checker source, relative to the problem directory
+    checker: Option<PathBuf>,
+    /// testlib interactor source, relative to the problem directory
+    interactor: Option<PathBuf>,
+    /// testlib validator source, relative to the problem directory
+    validator: Option<PathBuf>,
+    /// Output comparison used without a checker
+    #[serde(default)]
+    compare_mode: CompareMode,
+}
+
+fn default_output_limit() -> u32 {
+    DEFAULT_OUTPUT_LIMIT_KB / 1024
+}
+
+/// One input file with its answer
+#[derive(Debug)]
+struct LocalTestcase {
+    name: String,
+    input: PathBuf,
+    answer: PathBuf,
+}
+
+/// Problem-setter tools compiled for this run
+struct Tools {
+    checker: Option<PathBuf>,
+    interactor: Option<PathBuf>,
+    validator: Option<PathBuf>,
+}
+
+/// One row of the verdict table
+struct Row {
+    name: String,
+    verdict: Verdict,
+    score: f64,
+    time_ms: u32,
+    memory_kb: u32,
+    /// Validator message if the input was rejected
+    invalid_input: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "judge=warn".into()),
+        )
+        .init();
+    dotenvy::dotenv().ok();
+
+    match run().await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Judge the submission; returns whether every test was accepted
+async fn run() -> Result<bool> {
+    let args = Args::parse()?;
+
+    languages::init_languages()?;
+    let layout = WorkerLayout::from_env()?;
+    sandbox::init_config(args.worker_id, &layout)?;
+    sandbox::ensure_cgroups_available().await?;
+
+    let config_path = args.problem_dir.join("problem.toml");
+    let config: ProblemConfig = toml::from_str(
+        &std::fs::read_to_string(&config_path)
+            .with_context(|| format!("Failed to read {}", config_path.display()))?,
+    )
+    .with_context(|| format!("Invalid {}", config_path.display()))?;
+
+    let testcases = collect_testcases(&args.problem_dir.join("tests"))?;
+    if testcases.is_empty() {
+        bail!(
+            "No tests found in {}",
+            args.problem_dir.join("tests").display()
+        );
+    }
+
+    let language = match args.language {
+        Some(language) => language,
+        None => detect_language(&args.source)?,
+    };
+    let lang_config = languages::get_language_config(&language)
+        .with_context(|| format!("Unsupported language: {}", language))?;
+
+    let tools_dir = tempfile::tempdir()?;
+    let tools = compile_tools(&args.problem_dir, &config, tools_dir.path()).await?;
+
+    // Compile the submission
+    let work_dir = tempfile::tempdir()?;
+    std::fs::copy(&args.source, work_dir.path().join(&lang_config.source_file))
+        .with_context(|| format!("Failed to read {}", args.source.display()))?;
+    if let Some(compile_cmd) = &lang_config.compile_command {
+        let sandbox_config = get_config();
+        let compiled = compile_in_sandbox(
+            work_dir.path(),
+            compile_cmd,
+            sandbox_config.compile_time_limit_ms,
+            sandbox_config.compile_memory_limit_mb,
+        )
+        .await?;
+        if !compiled.success {
+            println!("{}", Verdict::CompileError);
+            if let Some(message) = compiled.message {
+                println!("{}", message);
+            }
+            return Ok(false);
+        }
+    }
+
+    let mut rows = Vec::with_capacity(testcases.len());
+    for tc in &testcases {
+        rows.push(judge_testcase(tc, &config, &lang_config, &tools, work_dir.path()).await?);
+    }
+
+    print_table(&rows);
+    Ok(rows
+        .iter()
+        .all(|row| row.verdict == Verdict::Accepted && row.invalid_input.is_none()))
+}
+
+/// Compile the checker, interactor and validator named in `problem.toml`
+async fn compile_tools(
+    problem_dir: &Path,
+    config: &ProblemConfig,
+    out_dir: &Path,
+) -> Result<Tools> {
+    let testlib_path = out_dir.join("testlib.h");
+    std::fs::write(&testlib_path, TESTLIB_H)?;
+
+    let mut tools = Tools {
+        checker: None,
+        interactor: None,
+        validator: None,
+    };
+    for (tool, source, binary) in [
+        (TestlibTool::Checker, &config.checker, &mut tools.checker),
+        (
+            TestlibTool::Interactor,
+            &config.interactor,
+            &mut tools.interactor,
+        ),
+        (
+            TestlibTool::Validator,
+            &config.validator,
+            &mut tools.validator,
+        ),
+    ] {
+        if let Some(source) = source {
+            *binary =
+                Some(compile_tool(tool, &problem_dir.join(source), out_dir, &testlib_path).await?);
+        }
+    }
+    Ok(tools)
+}
+
+/// Compile one tool into `out_dir/<tool>`.
+///
+/// The source is copied to `out_dir/<tool>.cpp` first: sandboxed compilation
+/// only sees the source's directory, so the binary has to be built there.
+async fn compile_tool(
+    tool: TestlibTool,
+    source: &Path,
+    out_dir: &Path,
+    testlib_path: &Path,
+) -> Result<PathBuf> {
+    let source_copy = out_dir.join(format!("{}.cpp", tool));
+    std::fs::copy(source, &source_copy)
+        .with_context(|| format!("Failed to read {} {:?}", tool, source))?;
+
+    let binary = out_dir.join(tool.to_string());
+    let result = match tool {
+        TestlibTool::Checker => compile_checker(&source_copy, &binary, testlib_path).await?,
+        TestlibTool::Interactor => compile_interactor(&source_copy, &binary, testlib_path).await?,
+        TestlibTool::Validator => compile_validator(&source_copy, &binary, testlib_path).await?,
+    };
+    if !result.success {
+        bail!("Failed to compile {}:\n{}", tool, result.stderr);
+    }
+    Ok(binary)
+}
+
+/// Validate, run and check one test
+async fn judge_testcase(
+    tc: &LocalTestcase,
+    config: &ProblemConfig,
+    lang_config: &LanguageConfig,
+    tools: &Tools,
+    work_dir: &Path,
+) -> Result<Row> {
+    let invalid_input = match &tools.validator {
+        Some(validator) => {
+            let (valid, message) =
+                run_validator(validator, &tc.input, DEFAULT_VALIDATOR_TIMEOUT_SECS).await?;
+            (!valid).then(|| message.unwrap_or_else(|| "rejected by validator".to_string()))
+        }
+        None => None,
+    };
+
+    let input = std::fs::read_to_string(&tc.input)
+        .with_context(|| format!("Failed to read {}", tc.input.display()))?;
+    let answer = std::fs::read_to_string(&tc.answer)
+        .with_context(|| format!("Failed to read {}", tc.answer.display()))?;
+
+    let spec = ExecutionSpec::new(work_dir)
+        .with_command(&lang_config.run_command)
+        .with_seccomp(lang_config.seccomp)
+        .with_limits(ExecutionLimits {
+            time_ms: lang_config.calculate_time_limit(config.time_limit),
+            memory_mb: lang_config.calculate_memory_limit(config.memory_limit),
+        })
+        .with_stdin(&input)
+        .with_output_limit_kb(config.output_limit.saturating_mul(1024));
+
+    let (run, verdict, score) = if let Some(interactor) = &tools.interactor {
+        let result = run_interactive(
+            &spec,
+            interactor,
+            &tc.input,
+            &tc.answer,
+            DEFAULT_CHECKER_TIMEOUT_SECS,
+        )
+        .await?;
+        (result.run, result.verdict, result.score)
+    } else {
+        let run = execute_sandboxed(&spec).await?;
+        let (verdict, score) = judge_run(&run, config, tools, &input, &answer).await?;
+        (run, verdict, score)
+    };
+
+    Ok(Row {
+        name: tc.name.clone(),
+        verdict,
+        score,
+        time_ms: run.time_ms,
+        memory_kb: run.memory_kb,
+        invalid_input,
+    })
+}
+
+/// Verdict of a non-interactive run
+async fn judge_run(
+    run: &ExecutionOutcome,
+    config: &ProblemConfig,
+    tools: &Tools,
+    input: &str,
+    answer: &str,
+) -> Result<(Verdict, f64)> {
+    match run.status {
+        ExecutionStatus::Exited(0) => {
+            check_output(
+                tools.checker.as_deref(),
+                config.compare_mode,
+                input,
+                run,
+                answer,
+            )
+            .await
+        }
+        ref status => Ok((status_to_verdict(status), 0.0)),
+    }
+}
+
+/// Print the verdict table and a summary line
+fn print_table(rows: &[Row]) {
+    let name_width = rows
+        .iter()
+        .map(|row| row.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("test".len());
+
+    println!(
+        "{:<name_width$}  {:<21}  {:>5}  {:>8}  {:>10}",
+        "test", "verdict", "score", "time", "memory"
+    );
+    for row in rows {
+        println!(
+            "{:<name_width$}  {:<21}  {:>5.2}  {:>5} ms  {:>7} KB",
+            row.name,
+            row.verdict.to_string(),
+            row.score,
+            row.time_ms,
+            row.memory_kb
+        );
+        if let Some(message) = &row.invalid_input {
+            println!("{:<name_width$}  invalid input: {}", "", message);
+        }
+    }
+
+    let accepted = rows
+        .iter()
+        .filter(|row| row.verdict == Verdict::Accepted)
+        .count();
+    let max_time = rows.iter().map(|row| row.time_ms).max().unwrap_or(0);
+    let max_memory = rows.iter().map(|row| row.memory_kb).max().unwrap_or(0);
+    println!(
+        "\n{}/{} accepted, max time {} ms, max memory {} KB",
+        accepted,
+        rows.len(),
+        max_time,
+        max_memory
+    );
+}
+
+/// Find the language whose source file has the same extension as `source`
+fn detect_language(source: &Path) -> Result<String> {
+    let extension = source
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .with_context(|| format!("Cannot detect the language of {}", source.display()))?;
+
+    let mut names = languages::get_supported_languages();
+    names.sort();
+    names
+        .into_iter()
+        .find(|name| {
+            languages::get_language_config(name).is_some_and(|config| {
+                Path::new(&config.source_file)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    == Some(extension)
+            })
+        })
+        .with_context(|| format!("No language for .{} files, pass --language", extension))
+}
+
+/// Pair every `<name>.in` in `tests_dir` with its `.out` or `.ans` answer
+fn collect_testcases(tests_dir: &Path) -> Result<Vec<LocalTestcase>> {
+    let mut testcases = Vec::new();
+    for entry in std::fs::read_dir(tests_dir)
+        .with_context(|| format!("Failed to read {}", tests_dir.display()))?
+    {
+        let input = entry?.path();
+        if input.extension().is_none_or(|ext| ext != "in") {
+            continue;
+        }
+        let answer = ["out", "ans"]
+            .iter()
+            .map(|ext| input.with_extension(ext))
+            .find(|path| path.exists())
+            .with_context(|| format!("No answer file for {}", input.display()))?;
+        let name = input
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        testcases.push(LocalTestcase {
+            name,
+            input,
+            answer,
+        });
+    }
+    testcases.sort_by_key(|tc| natural_key(&tc.name));
+    Ok(testcases)
+}
+
+/// Sort key that orders numeric names by value (`2` before `10`)
+fn natural_key(name: &str) -> (u64, String) {
+    let digits: String = name.chars().take_while(|c| c.is_ascii_digit()).collect();
+    (digits.parse().unwrap_or(u64::MAX), name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use judge::sandbox::mock::{MockRun, MockSandbox};
+    use judge::sandbox::with_provider;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_collect_testcases_pairs_answers_in_natural_order() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "10.in",
+            "10.ans",
+            "2.in",
+            "2.out",
+            "sample.in",
+            "sample.out",
+        ] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+
+        let testcases = collect_testcases(dir.path()).unwrap();
+        let names: Vec<_> = testcases.iter().map(|tc| tc.name.as_str()).collect();
+        assert_eq!(names, ["2", "10", "sample"]);
+        assert_eq!(testcases[1].answer, dir.path().join("10.ans"));
+    }
+
+    #[tokio::test]
+    async fn test_compile_tools_builds_checker_next_to_its_source() {
+        let problem_dir = tempfile::tempdir().unwrap();
+        std::fs::write(problem_dir.path().join("chk.cpp"), "int main() {}").unwrap();
+        let out_dir = tempfile::tempdir().unwrap();
+        let config: ProblemConfig =
+            toml::from_str("time_limit = 1000\nmemory_limit = 256\nchecker = \"chk.cpp\"").unwrap();
+
+        // The mock compiler only succeeds if the source is in the box, like g++
+        let sandbox = Arc::new(MockSandbox::new(|call| {
+            if !call.work_dir.join("checker.cpp").exists() {
+                return MockRun::exited(1).with_stderr("checker.cpp: No such file");
+            }
+            std::fs::write(call.work_dir.join("checker"), "binary").unwrap();
+            MockRun::exited(0)
+        }));
+        let tools = with_provider(
+            sandbox.clone(),
+            compile_tools(problem_dir.path(), &config, out_dir.path()),
+        )
+        .await
+        .unwrap();
+
+        let checker = tools.checker.unwrap();
+        assert_eq!(checker, out_dir.path().join("checker"));
+        assert_eq!(std::fs::read_to_string(checker).unwrap(), "binary");
+        assert!(tools.validator.is_none());
+        assert_eq!(sandbox.calls()[0].program(), "g++");
+    }
+}
@@ -0,0 +1,55 @@
+//! Seccomp wrapper for isolate boxes
+//!
+//! isolate can't filter syscalls, so isolate boxes start programs that run
+//! under a language profile through this helper:
+//!
+//! ```text
+//! judge-seccomp <profile> <program> [args...]
+//! ```
+//!
+//! It installs the filters of the profile and replaces itself with the
+//! program, which keeps the helper's environment. The helper must be visible
+//! inside the box (the Docker image puts it in /usr/local/bin).
+
+use std::convert::Infallible;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStringExt;
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use nix::sys::signal::{signal, SigHandler, Signal};
+use nix::unistd::execv;
+
+use judge::sandbox::SeccompProfile;
+
+fn main() -> ExitCode {
+    let Err(e) = run();
+    eprintln!("judge-seccomp: {:#}", e);
+    // Same exit code as a command the shell can't execute
+    ExitCode::from(127)
+}
+
+fn run() -> Result<Infallible> {
+    let mut args = std::env::args_os().skip(1);
+    let (Some(profile), Some(program)) = (args.next(), args.next()) else {
+        anyhow::bail!("usage: judge-seccomp <profile> <program> [args...]");
+    };
+    let profile: SeccompProfile = profile.to_string_lossy().parse()?;
+
+    let program = CString::new(program.into_vec())?;
+    let mut argv = vec![program.clone()];
+    for arg in args {
+        argv.push(CString::new(arg.into_vec())?);
+    }
+
+    let filters = profile.filters(&program)?;
+
+    // Rust ignores SIGPIPE, which the program would inherit
+    // SAFETY: restores the default disposition, no handler is involved
+    unsafe { signal(Signal::SIGPIPE, SigHandler::SigDfl) }?;
+    for filter in &filters {
+        seccompiler::apply_filter(filter).context("Failed to install seccomp filter")?;
+    }
+
+    execv(&program, &argv).with_context(|| format!("Failed to execute {:?}", program))
+}
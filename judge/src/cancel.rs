@@ -0,0 +1,67 @@
+//! Cancellation of running jobs
+//!
+//! A cancel request only stops a job that is still being processed. Once a
+//! job has its result, the result is kept even if a cancel request arrives
+//! at the same moment, so a finished submission is never reported as
+//! cancelled.
+
+use std::future::Future;
+
+use crate::sandbox;
+
+/// Run `processing` until it finishes or `cancelled` resolves.
+///
+/// A cancelled job's future is dropped, and the boxes it left behind are
+/// cleaned up before returning `None`.
+pub async fn unless_cancelled<T>(
+    processing: impl Future<Output = T>,
+    cancelled: impl Future<Output = ()>,
+) -> Option<T> {
+    tokio::select! {
+        // A finished job wins over a cancel request that is ready as well
+        biased;
+        processed = processing => Some(processed),
+        _ = cancelled => {
+            sandbox::cleanup_active_boxes().await;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executer::SandboxSession;
+    use crate::sandbox::mock::{MockRun, MockSandbox};
+    use crate::sandbox::with_provider;
+    use std::sync::Arc;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn test_finished_job_is_not_cancelled() {
+        let processed = unless_cancelled(async { 42 }, async {}).await;
+        assert_eq!(processed, Some(42));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_job_leaves_no_open_boxes() {
+        let dir = tempfile::tempdir().unwrap();
+        let sandbox = Arc::new(MockSandbox::new(|_| MockRun::exited(0)));
+        let (started_tx, started_rx) = oneshot::channel();
+
+        let processing = async {
+            let _session = SandboxSession::for_slot(dir.path(), 0).await.unwrap();
+            started_tx.send(()).unwrap();
+            std::future::pending::<()>().await;
+        };
+        let cancelled = async {
+            started_rx.await.unwrap();
+            assert_eq!(sandbox.open_boxes(), 1);
+        };
+        let processed =
+            with_provider(sandbox.clone(), unless_cancelled(processing, cancelled)).await;
+
+        assert_eq!(processed, None);
+        assert_eq!(sandbox.open_boxes(), 0);
+    }
+}
@@ -7,9 +7,13 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use tracing::{debug, info, warn};
 
-use crate::compiler::CheckerCompiler;
-use crate::executer::{execute_trusted, ExecutionLimits, ExecutionSpec};
+use crate::compiler::{TestlibCompiler, TestlibTool};
+use crate::executer::{
+    execute_sandboxed, execute_trusted, ExecutionLimits, ExecutionSpec, ExecutionStatus,
+};
+use crate::sandbox::get_config;
 use crate::storage::StorageClient;
+use crate::utils::file_name;
 
 /// Verdict from judging (shared with other modules)
 #[derive(Debug, Clone, PartialEq)]
@@ -18,12 +22,19 @@ pub enum Verdict {
     WrongAnswer,
     TimeLimitExceeded,
     MemoryLimitExceeded,
+    OutputLimitExceeded,
     RuntimeError,
+    /// The program made a syscall its seccomp profile forbids
+    RestrictedFunction,
     SystemError,
     CompileError,
     Skipped,
     PresentationError,
     Fail,
+    /// Partial credit from a testlib checker (`quitp` / `_pc`)
+    Partial,
+    /// Judging was stopped on request
+    Cancelled,
 }
 
 impl std::fmt::Display for Verdict {
@@ -33,12 +44,16 @@ impl std::fmt::Display for Verdict {
             Verdict::WrongAnswer => "wrong_answer",
             Verdict::TimeLimitExceeded => "time_limit_exceeded",
             Verdict::MemoryLimitExceeded => "memory_limit_exceeded",
+            Verdict::OutputLimitExceeded => "output_limit_exceeded",
             Verdict::RuntimeError => "runtime_error",
+            Verdict::RestrictedFunction => "restricted_function",
             Verdict::SystemError => "system_error",
             Verdict::CompileError => "compile_error",
             Verdict::Skipped => "skipped",
             Verdict::PresentationError => "presentation_error",
             Verdict::Fail => "fail",
+            Verdict::Partial => "partial",
+            Verdict::Cancelled => "cancelled",
         };
         write!(f, "{}", s)
     }
@@ -49,6 +64,8 @@ impl std::fmt::Display for Verdict {
 pub struct CheckerResult {
     pub verdict: Verdict,
     pub message: Option<String>,
+    /// Fraction of the testcase's points earned (0.0 ~ 1.0)
+    pub score: f64,
 }
 
 /// testlib.h exit codes
@@ -59,13 +76,16 @@ mod testlib_exit_codes {
     pub const PRESENTATION_ERROR: i32 = 2; // _pe (treated as WA in most systems)
     pub const FAIL: i32 = 3; // _fail (checker bug or internal error)
     pub const DIRT: i32 = 4; // _dirt (extra output in user file)
-    #[allow(dead_code)]
-    pub const POINTS: i32 = 5; // _points (partial scoring - not commonly used)
+    pub const POINTS: i32 = 7; // _points (quitp, score printed in the message)
     pub const UNEXPECTED_EOF: i32 = 8; // _unexpected_eof
+    /// _pc(n) exits with PARTIALLY_BASE + n; checkers are compiled with
+    /// -DPC_BASE_EXIT_CODE=50 since testlib's default base of 0 collides with _ok
+    pub const PARTIALLY_BASE: i32 = 50;
+    pub const PARTIALLY_MAX: i32 = PARTIALLY_BASE + 100;
 }
 
 /// Convert testlib exit code to verdict
-fn exit_code_to_verdict(exit_code: i32) -> Verdict {
+pub fn exit_code_to_verdict(exit_code: i32) -> Verdict {
     match exit_code {
         testlib_exit_codes::OK => Verdict::Accepted,
         testlib_exit_codes::WRONG_ANSWER => Verdict::WrongAnswer,
@@ -73,6 +93,8 @@ fn exit_code_to_verdict(exit_code: i32) -> Verdict {
         testlib_exit_codes::FAIL => Verdict::Fail,
         testlib_exit_codes::DIRT => Verdict::WrongAnswer,
         testlib_exit_codes::UNEXPECTED_EOF => Verdict::WrongAnswer,
+        testlib_exit_codes::POINTS => Verdict::Partial,
+        testlib_exit_codes::PARTIALLY_BASE..=testlib_exit_codes::PARTIALLY_MAX => Verdict::Partial,
         _ => {
             warn!("Unknown checker exit code: {}", exit_code);
             if exit_code < 0 || exit_code > 127 {
@@ -84,6 +106,67 @@ fn exit_code_to_verdict(exit_code: i32) -> Verdict {
     }
 }
 
+/// Parse the score ratio of a partial-credit testlib result.
+///
+/// `quitp(x, ...)` prints `points x ...` and `x` is taken as a fraction of the
+/// testcase's points; `_pc(n)` is taken as n percent. The result is clamped to 0.0 ~ 1.0.
+fn parse_points(exit_code: i32, message: &str) -> Option<f64> {
+    let ratio = if exit_code == testlib_exit_codes::POINTS {
+        let message = message.trim_start();
+        let message = message.strip_prefix("points").unwrap_or(message);
+        message.split_whitespace().next()?.parse::<f64>().ok()?
+    } else {
+        (exit_code - testlib_exit_codes::PARTIALLY_BASE) as f64 / 100.0
+    };
+
+    if ratio.is_finite() {
+        Some(ratio.clamp(0.0, 1.0))
+    } else {
+        None
+    }
+}
+
+/// Interpret the exit code and message of a testlib program (checker or interactor)
+pub fn interpret_testlib_result(exit_code: i32, message: Option<String>) -> CheckerResult {
+    let verdict = exit_code_to_verdict(exit_code);
+
+    let (verdict, score) = match verdict {
+        Verdict::Accepted => (Verdict::Accepted, 1.0),
+        Verdict::Partial => match parse_points(exit_code, message.as_deref().unwrap_or("")) {
+            Some(score) if score >= 1.0 => (Verdict::Accepted, 1.0),
+            Some(score) if score <= 0.0 => (Verdict::WrongAnswer, 0.0),
+            Some(score) => (Verdict::Partial, score),
+            None => {
+                warn!("Failed to parse checker points from {:?}", message);
+                (Verdict::Fail, 0.0)
+            }
+        },
+        other => (other, 0.0),
+    };
+
+    CheckerResult {
+        verdict,
+        message,
+        score,
+    }
+}
+
+/// Convert the execution status of a user program to a verdict
+/// (a clean exit is Accepted; output checking is up to the caller)
+pub fn status_to_verdict(status: &ExecutionStatus) -> Verdict {
+    match status {
+        ExecutionStatus::Exited(0) => Verdict::Accepted,
+        ExecutionStatus::Exited(_) => Verdict::RuntimeError,
+        ExecutionStatus::TimeLimitExceeded => Verdict::TimeLimitExceeded,
+        ExecutionStatus::MemoryLimitExceeded => Verdict::MemoryLimitExceeded,
+        ExecutionStatus::OutputLimitExceeded => Verdict::OutputLimitExceeded,
+        ExecutionStatus::Signaled(_) => Verdict::RuntimeError,
+        ExecutionStatus::RestrictedFunction => Verdict::RestrictedFunction,
+        ExecutionStatus::RuntimeError => Verdict::RuntimeError,
+        ExecutionStatus::SystemError => Verdict::SystemError,
+    }
+}
+
 /// Run a testlib.h-based checker
 ///
 /// Arguments to checker: <input_file> <user_output_file> <expected_answer_file>
@@ -99,22 +182,45 @@ pub async fn run_checker(
         checker_path, input_path, user_output_path, answer_path
     );
 
+    let limits = ExecutionLimits {
+        time_ms: (timeout_secs * 1000) as u32,
+        memory_mb: 512,
+    };
+
     // Build execution spec for checker: <input_file> <output_file> <answer_file>
-    let spec = ExecutionSpec::new(checker_path.parent().unwrap_or(Path::new(".")))
-        .with_command([
-            checker_path.to_str().unwrap_or(""),
-            input_path.to_str().unwrap_or(""),
-            user_output_path.to_str().unwrap_or(""),
-            answer_path.to_str().unwrap_or(""),
-        ])
-        .with_limits(ExecutionLimits {
-            time_ms: (timeout_secs * 1000) as u32,
-            memory_mb: 512,
-        });
-
-    let result = execute_trusted(&spec)
-        .await
-        .context("Failed to run checker")?;
+    let result = if get_config().trusted_tools {
+        let spec = ExecutionSpec::new(checker_path.parent().unwrap_or(Path::new(".")))
+            .with_command([
+                checker_path.to_str().unwrap_or(""),
+                input_path.to_str().unwrap_or(""),
+                user_output_path.to_str().unwrap_or(""),
+                answer_path.to_str().unwrap_or(""),
+            ])
+            .with_limits(limits);
+
+        execute_trusted(&spec).await
+    } else {
+        // The checker binary is copied into the box; the three files are
+        // mounted read-only from their host directories
+        let binary_name = file_name(checker_path)?;
+        let spec = ExecutionSpec::new(checker_path.parent().unwrap_or(Path::new(".")))
+            .with_command([
+                format!("./{}", binary_name),
+                format!("/input/{}", file_name(input_path)?),
+                format!("/output/{}", file_name(user_output_path)?),
+                format!("/answer/{}", file_name(answer_path)?),
+            ])
+            .with_bind(input_path.parent().unwrap_or(Path::new(".")), "/input")
+            .with_bind(
+                user_output_path.parent().unwrap_or(Path::new(".")),
+                "/output",
+            )
+            .with_bind(answer_path.parent().unwrap_or(Path::new(".")), "/answer")
+            .with_limits(limits);
+
+        execute_sandboxed(&spec).await
+    }
+    .context("Failed to run checker")?;
 
     debug!(
         "Checker result: exit_code={}, stdout={}, stderr={}",
@@ -123,8 +229,6 @@ pub async fn run_checker(
         result.stderr.chars().take(200).collect::<String>()
     );
 
-    let verdict = exit_code_to_verdict(result.exit_code());
-
     // Checker message is typically in stderr (testlib writes to stderr)
     let message = if result.stderr.is_empty() {
         if result.stdout.is_empty() {
@@ -136,20 +240,26 @@ pub async fn run_checker(
         Some(result.stderr.trim().to_string())
     };
 
-    Ok(CheckerResult { verdict, message })
+    Ok(interpret_testlib_result(result.exit_code(), message))
 }
 
 /// Checker manager for handling checker compilation and caching
 pub struct CheckerManager {
     /// Compiler for checkers
-    compiler: CheckerCompiler,
+    compiler: TestlibCompiler,
+}
+
+impl Default for CheckerManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CheckerManager {
     /// Create a new checker manager
     pub fn new() -> Self {
         Self {
-            compiler: CheckerCompiler::new(),
+            compiler: TestlibCompiler::new(TestlibTool::Checker),
         }
     }
 
@@ -190,6 +300,33 @@ mod tests {
         assert_eq!(exit_code_to_verdict(2), Verdict::PresentationError);
         assert_eq!(exit_code_to_verdict(3), Verdict::Fail);
         assert_eq!(exit_code_to_verdict(4), Verdict::WrongAnswer);
+        assert_eq!(exit_code_to_verdict(7), Verdict::Partial);
+        assert_eq!(exit_code_to_verdict(63), Verdict::Partial);
+    }
+
+    #[test]
+    fn test_interpret_testlib_points() {
+        let result = interpret_testlib_result(7, Some("points 0.25 two of eight".to_string()));
+        assert_eq!(result.verdict, Verdict::Partial);
+        assert_eq!(result.score, 0.25);
+
+        let result = interpret_testlib_result(7, Some("points 1".to_string()));
+        assert_eq!(result.verdict, Verdict::Accepted);
+        assert_eq!(result.score, 1.0);
+
+        let result = interpret_testlib_result(7, Some("points garbage".to_string()));
+        assert_eq!(result.verdict, Verdict::Fail);
+    }
+
+    #[test]
+    fn test_interpret_testlib_partially_correct() {
+        let result = interpret_testlib_result(80, None);
+        assert_eq!(result.verdict, Verdict::Partial);
+        assert_eq!(result.score, 0.3);
+
+        let result = interpret_testlib_result(1, None);
+        assert_eq!(result.verdict, Verdict::WrongAnswer);
+        assert_eq!(result.score, 0.0);
     }
 
     #[test]
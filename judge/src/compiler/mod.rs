@@ -2,19 +2,23 @@
 //!
 //! This module provides compilation functionality:
 //! - User code compilation (sandboxed)
-//! - Trusted code compilation (checkers, validators)
+//! - Problem-setter code compilation (checkers, validators, interactors),
+//!   sandboxed unless trusted tools are enabled
 //!
 //! The compiler module uses the sandbox module directly for sandboxed compilation.
 
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tracing::{debug, info};
 
 use crate::executer::{
     execute_sandboxed, execute_trusted, ExecutionLimits, ExecutionSpec, ExecutionStatus,
 };
 use crate::languages::LanguageConfig;
+use crate::metrics::{metrics, Timing};
 use crate::sandbox::get_config;
+use crate::utils::file_name;
 
 /// Result of compiling a trusted program (checker/validator)
 #[derive(Debug)]
@@ -25,38 +29,76 @@ pub struct TrustedCompileResult {
     pub success: bool,
 }
 
-/// Compile a C++ source file (for checkers/validators) without sandbox
+/// Compile a C++ source file (for checkers/validators/interactors)
+///
+/// Compilation runs inside isolate with the include paths mounted read-only,
+/// unless trusted tools are enabled. The output must be placed next to the source.
 pub async fn compile_trusted_cpp(
     source_path: &Path,
     output_path: &Path,
     include_paths: &[&Path],
 ) -> Result<TrustedCompileResult> {
+    let work_dir = source_path.parent().unwrap_or(Path::new("."));
+    let trusted = get_config().trusted_tools;
+
+    let mut spec = ExecutionSpec::new(work_dir).with_limits(ExecutionLimits {
+        time_ms: 60_000, // 60 seconds for compilation
+        memory_mb: 2048,
+    });
+
     let mut command = vec![
         "g++".to_string(),
         "-O2".to_string(),
         "-std=c++17".to_string(),
-        "-o".to_string(),
-        output_path.to_str().unwrap_or("").to_string(),
-        source_path.to_str().unwrap_or("").to_string(),
+        // Give testlib's _pc(n) exit codes their own range (see checker::testlib_exit_codes)
+        "-DPC_BASE_EXIT_CODE=50".to_string(),
     ];
 
-    // Add include paths
-    for include_path in include_paths {
-        command.push(format!("-I{}", include_path.to_str().unwrap_or("")));
+    if trusted {
+        command.extend([
+            "-o".to_string(),
+            output_path.to_str().unwrap_or("").to_string(),
+            source_path.to_str().unwrap_or("").to_string(),
+        ]);
+
+        // Add include paths
+        for include_path in include_paths {
+            command.push(format!("-I{}", include_path.to_str().unwrap_or("")));
+        }
+    } else {
+        if output_path.parent() != Some(work_dir) {
+            anyhow::bail!(
+                "Sandboxed compilation requires {:?} to be next to {:?}",
+                output_path,
+                source_path
+            );
+        }
+
+        command.extend([
+            "-o".to_string(),
+            file_name(output_path)?.to_string(),
+            file_name(source_path)?.to_string(),
+        ]);
+
+        // Mount include paths read-only inside the box
+        for (idx, include_path) in include_paths.iter().enumerate() {
+            let inside = format!("/include{}", idx);
+            command.push(format!("-I{}", inside));
+            spec = spec.with_bind(include_path, inside);
+        }
+
+        spec = spec.with_copy_out_dir(work_dir);
     }
 
     debug!("Compiling trusted C++ with command: {:?}", command);
 
-    let spec = ExecutionSpec::new(source_path.parent().unwrap_or(Path::new(".")))
-        .with_command(&command)
-        .with_limits(ExecutionLimits {
-            time_ms: 60_000, // 60 seconds for compilation
-            memory_mb: 2048,
-        });
-
-    let result = execute_trusted(&spec)
-        .await
-        .context("Failed to run g++ compiler")?;
+    let spec = spec.with_command(&command);
+    let result = if trusted {
+        execute_trusted(&spec).await
+    } else {
+        execute_sandboxed(&spec).await
+    }
+    .context("Failed to run g++ compiler")?;
 
     let success = result.is_success();
     Ok(TrustedCompileResult {
@@ -99,7 +141,9 @@ pub async fn compile_in_sandbox(
         })
         .with_copy_out_dir(source_dir);
 
+    let started = Instant::now();
     let result = execute_sandboxed(&spec).await?;
+    metrics().observe(Timing::Compile, started.elapsed());
 
     let success = matches!(result.status, ExecutionStatus::Exited(0));
 
@@ -162,7 +206,7 @@ pub async fn compile_user_code(
     .await
 }
 
-/// Compile a C++ checker/validator (trusted, no sandbox)
+/// Compile a C++ checker
 pub async fn compile_checker(
     source_path: &Path,
     output_path: &Path,
@@ -174,7 +218,7 @@ pub async fn compile_checker(
     compile_trusted_cpp(source_path, output_path, &[include_dir]).await
 }
 
-/// Compile a C++ validator (trusted, no sandbox)
+/// Compile a C++ validator
 pub async fn compile_validator(
     source_path: &Path,
     output_path: &Path,
@@ -189,114 +233,84 @@ pub async fn compile_validator(
     compile_trusted_cpp(source_path, output_path, &[include_dir]).await
 }
 
-/// Manager for checker compilation and caching
-pub struct CheckerCompiler {
-    /// Path to testlib.h header file
-    testlib_path: PathBuf,
-    /// Local cache directory for compiled checkers
-    cache_dir: PathBuf,
-}
-
-impl CheckerCompiler {
-    // pub fn new(testlib_path: impl AsRef<Path>, cache_dir: impl AsRef<Path>) -> Self {
-    pub fn new() -> Self {
-        let _ = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/files/testlib.h"));
-
-        let testlib_path = std::env::current_dir()
-            .map(|cwd| cwd.join("files/testlib.h"))
-            .unwrap_or_else(|_| PathBuf::from("files/testlib.h"));
-        let cache_dir = "/tmp/checker_cache";
-
-        Self {
-            testlib_path,
-            cache_dir: cache_dir.into(),
-        }
-    }
-
-    /// Get the path to a compiled checker, compiling if necessary
-    pub async fn get_or_compile(&self, source_content: &str, problem_id: i64) -> Result<PathBuf> {
-        let checker_dir = self.cache_dir.join(format!("checker_{}", problem_id));
-        tokio::fs::create_dir_all(&checker_dir).await?;
-
-        let source_path = checker_dir.join("checker.cpp");
-        let binary_path = checker_dir.join("checker");
-
-        // Check if source has changed or binary doesn't exist
-        let need_compile = if binary_path.exists() && source_path.exists() {
-            let cached_source = tokio::fs::read_to_string(&source_path)
-                .await
-                .unwrap_or_default();
-            if cached_source != source_content {
-                info!(
-                    "Checker source has changed, recompiling for problem {}",
-                    problem_id
-                );
-                true
-            } else {
-                debug!(
-                    "Checker source unchanged, using cached binary for problem {}",
-                    problem_id
-                );
-                false
-            }
-        } else {
-            true
-        };
-
-        if need_compile {
-            tokio::fs::write(&source_path, source_content).await?;
+/// Compile a C++ interactor
+pub async fn compile_interactor(
+    source_path: &Path,
+    output_path: &Path,
+    testlib_path: &Path,
+) -> Result<TrustedCompileResult> {
+    info!(
+        "Compiling interactor: {:?} -> {:?}",
+        source_path, output_path
+    );
 
-            info!("Compiling checker for problem {}", problem_id);
-            let result = compile_checker(&source_path, &binary_path, &self.testlib_path).await?;
+    let include_dir = testlib_path.parent().unwrap_or(Path::new("."));
+    compile_trusted_cpp(source_path, output_path, &[include_dir]).await
+}
 
-            if !result.success {
-                anyhow::bail!("Failed to compile checker: {}", result.stderr);
-            }
+/// Kind of testlib program a [`TestlibCompiler`] builds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestlibTool {
+    Checker,
+    Validator,
+    Interactor,
+}
 
-            info!("Checker compiled successfully: {:?}", binary_path);
+impl TestlibTool {
+    /// Name of the tool's source, binary and cache directory
+    fn name(self) -> &'static str {
+        match self {
+            TestlibTool::Checker => "checker",
+            TestlibTool::Validator => "validator",
+            TestlibTool::Interactor => "interactor",
         }
-
-        Ok(binary_path)
     }
+}
 
-    /// Clear cached checker for a problem
-    pub async fn clear_cache(&self, problem_id: i64) -> Result<()> {
-        let checker_dir = self.cache_dir.join(format!("checker_{}", problem_id));
-        if checker_dir.exists() {
-            tokio::fs::remove_dir_all(&checker_dir).await?;
-        }
-        Ok(())
+impl std::fmt::Display for TestlibTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
     }
 }
 
-/// Manager for validator compilation and caching
-pub struct ValidatorCompiler {
+/// Manager for compilation and caching of one kind of testlib program
+pub struct TestlibCompiler {
+    tool: TestlibTool,
     /// Path to testlib.h header file
     testlib_path: PathBuf,
-    /// Local cache directory for compiled validators
+    /// Local cache directory for compiled programs
     cache_dir: PathBuf,
 }
 
-impl ValidatorCompiler {
-    pub fn new() -> Self {
+impl TestlibCompiler {
+    pub fn new(tool: TestlibTool) -> Self {
+        let _ = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/files/testlib.h"));
+
         // CWD 기준 절대 경로로 변환
         let testlib_path = std::env::current_dir()
             .map(|cwd| cwd.join("files/testlib.h"))
             .unwrap_or_else(|_| PathBuf::from("files/testlib.h"));
 
         Self {
+            tool,
             testlib_path,
-            cache_dir: "/tmp/validator_cache".into(),
+            cache_dir: format!("/tmp/{}_cache", tool.name()).into(),
         }
     }
 
-    /// Get the path to a compiled validator, compiling if necessary
+    fn problem_dir(&self, problem_id: i64) -> PathBuf {
+        self.cache_dir
+            .join(format!("{}_{}", self.tool.name(), problem_id))
+    }
+
+    /// Get the path to a compiled program, compiling if necessary
     pub async fn get_or_compile(&self, source_content: &str, problem_id: i64) -> Result<PathBuf> {
-        let validator_dir = self.cache_dir.join(format!("validator_{}", problem_id));
-        tokio::fs::create_dir_all(&validator_dir).await?;
+        let tool = self.tool;
+        let tool_dir = self.problem_dir(problem_id);
+        tokio::fs::create_dir_all(&tool_dir).await?;
 
-        let source_path = validator_dir.join("validator.cpp");
-        let binary_path = validator_dir.join("validator");
+        let source_path = tool_dir.join(format!("{}.cpp", tool.name()));
+        let binary_path = tool_dir.join(tool.name());
 
         // Check if source has changed or binary doesn't exist
         let need_compile = if binary_path.exists() && source_path.exists() {
@@ -305,14 +319,14 @@ impl ValidatorCompiler {
                 .unwrap_or_default();
             if cached_source != source_content {
                 info!(
-                    "Validator source has changed, recompiling for problem {}",
-                    problem_id
+                    "The {} source has changed, recompiling for problem {}",
+                    tool, problem_id
                 );
                 true
             } else {
                 debug!(
-                    "Validator source unchanged, using cached binary for problem {}",
-                    problem_id
+                    "The {} source is unchanged, using cached binary for problem {}",
+                    tool, problem_id
                 );
                 false
             }
@@ -323,24 +337,35 @@ impl ValidatorCompiler {
         if need_compile {
             tokio::fs::write(&source_path, source_content).await?;
 
-            info!("Compiling validator for problem {}", problem_id);
-            let result = compile_validator(&source_path, &binary_path, &self.testlib_path).await?;
+            info!("Compiling {} for problem {}", tool, problem_id);
+            let testlib_path = &self.testlib_path;
+            let result = match tool {
+                TestlibTool::Checker => {
+                    compile_checker(&source_path, &binary_path, testlib_path).await?
+                }
+                TestlibTool::Validator => {
+                    compile_validator(&source_path, &binary_path, testlib_path).await?
+                }
+                TestlibTool::Interactor => {
+                    compile_interactor(&source_path, &binary_path, testlib_path).await?
+                }
+            };
 
             if !result.success {
-                anyhow::bail!("Failed to compile validator: {}", result.stderr);
+                anyhow::bail!("Failed to compile {}: {}", tool, result.stderr);
             }
 
-            info!("Validator compiled successfully: {:?}", binary_path);
+            info!("The {} compiled successfully: {:?}", tool, binary_path);
         }
 
         Ok(binary_path)
     }
 
-    /// Clear cached validator for a problem
+    /// Clear the cached program of a problem
     pub async fn clear_cache(&self, problem_id: i64) -> Result<()> {
-        let validator_dir = self.cache_dir.join(format!("validator_{}", problem_id));
-        if validator_dir.exists() {
-            tokio::fs::remove_dir_all(&validator_dir).await?;
+        let tool_dir = self.problem_dir(problem_id);
+        if tool_dir.exists() {
+            tokio::fs::remove_dir_all(&tool_dir).await?;
         }
         Ok(())
     }
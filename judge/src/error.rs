@@ -0,0 +1,54 @@
+//! Error classification for job retries
+//!
+//! A job that fails with an error is only retried when the failure is
+//! transient: Redis, storage IO or sandbox setup trouble that may be gone on
+//! the next attempt. Errors in the problem itself (a missing checker, a bad
+//! compare mode, a testcase object that doesn't exist) are stored as the
+//! job's result right away.
+
+use std::fmt;
+
+/// Error context marking a failure as transient.
+///
+/// Attach it where the failure happens:
+///
+/// ```ignore
+/// storage.read(key).await.with_context(|| Transient(format!("Failed to read {}", key)))?;
+/// ```
+#[derive(Debug)]
+pub struct Transient(pub String);
+
+impl fmt::Display for Transient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Whether retrying the job that failed with `error` may succeed
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Transient>().is_some()
+        || error.downcast_ref::<redis::RedisError>().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_transient_context_survives_more_context() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        let error = Err::<(), _>(io)
+            .context(Transient("Failed to download 1.in".into()))
+            .context("Failed to load testcase 1")
+            .unwrap_err();
+        assert!(is_transient(&error));
+        assert_eq!(
+            format!("{:#}", error),
+            "Failed to load testcase 1: Failed to download 1.in: reset"
+        );
+
+        let missing = anyhow::anyhow!("Special judge problem requires a checker");
+        assert!(!is_transient(&missing.context("Failed to judge")));
+    }
+}
@@ -1,24 +1,18 @@
-use crate::sandbox::{self, is_cgroups_available, IoSpec, IsolateBox, IsolateStatus, Limits};
+use crate::sandbox::{
+    create_box, get_config, DirBind, IoSpec, IsolateStatus, Limits, SandboxBackend, SandboxOutcome,
+    SeccompProfile,
+};
 use anyhow::Context;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::Duration;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
-/// Global counter for box ID allocation within worker's range
-static BOX_ID_COUNTER: AtomicU32 = AtomicU32::new(0);
-
-/// Get next box ID for isolate sandbox using worker-aware allocation
-/// Each worker (0-9) gets a dedicated range of 1000 box IDs to prevent collisions
-pub fn next_box_id() -> u32 {
-    let counter = BOX_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
-    // calculate_box_id(counter, 0)
-    let config = sandbox::get_config();
-    let box_id = config.worker_id * 1000 + (counter % 1000);
-
-    box_id
-}
+/// Default cap on a sandboxed program's output files (256MB)
+pub const DEFAULT_OUTPUT_LIMIT_KB: u32 = 262144;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ExecutionStatus {
@@ -28,8 +22,12 @@ pub enum ExecutionStatus {
     TimeLimitExceeded,
     /// Memory limit exceeded
     MemoryLimitExceeded,
+    /// Output size limit exceeded
+    OutputLimitExceeded,
     /// Killed by signal
     Signaled(i32),
+    /// Killed by the seccomp filter for a forbidden syscall
+    RestrictedFunction,
     /// Runtime error (crash, etc.)
     RuntimeError,
     /// System/internal error
@@ -92,6 +90,12 @@ pub struct ExecutionSpec {
     pub stdin: Option<String>,
     /// Directory to copy output files to after sandboxed execution
     pub copy_out_dir: Option<std::path::PathBuf>,
+    /// Host directories mounted read-only into the sandbox
+    pub binds: Vec<DirBind>,
+    /// Maximum size of stdout and any written file in KB (sandboxed execution only)
+    pub output_limit_kb: u32,
+    /// Syscall filter of the program (sandboxed execution only)
+    pub seccomp: SeccompProfile,
 }
 
 impl ExecutionSpec {
@@ -103,6 +107,9 @@ impl ExecutionSpec {
             limits: ExecutionLimits::default(),
             stdin: None,
             copy_out_dir: None,
+            binds: vec![],
+            output_limit_kb: DEFAULT_OUTPUT_LIMIT_KB,
+            seccomp: SeccompProfile::Base,
         }
     }
     pub fn with_command(mut self, command: impl IntoIterator<Item = impl Into<String>>) -> Self {
@@ -127,6 +134,26 @@ impl ExecutionSpec {
         self.copy_out_dir = Some(dir.into());
         self
     }
+
+    pub fn with_output_limit_kb(mut self, output_limit_kb: u32) -> Self {
+        self.output_limit_kb = output_limit_kb;
+        self
+    }
+
+    pub fn with_seccomp(mut self, seccomp: SeccompProfile) -> Self {
+        self.seccomp = seccomp;
+        self
+    }
+
+    /// Mount a host directory read-only at `inside` (sandboxed execution only)
+    pub fn with_bind(
+        mut self,
+        host: impl AsRef<std::path::Path>,
+        inside: impl Into<String>,
+    ) -> Self {
+        self.binds.push(DirBind::new(host, inside));
+        self
+    }
 }
 
 pub async fn execute_trusted(spec: &ExecutionSpec) -> anyhow::Result<ExecutionOutcome> {
@@ -185,65 +212,322 @@ pub async fn execute_sandboxed(spec: &ExecutionSpec) -> anyhow::Result<Execution
         return Err(anyhow::anyhow!("No command specified for execution"));
     }
 
-    // Check cgroups availability
-    if !is_cgroups_available().await {
-        anyhow::bail!("Cgroup support is required for sandboxed execution");
+    let session = SandboxSession::new(&spec.work_dir).await?;
+    let outcome = session.run(spec).await;
+
+    // Copy output files if copy_out_dir is specified
+    let outcome = match (outcome, &spec.copy_out_dir) {
+        (Ok(outcome), Some(copy_out_dir)) => {
+            session.copy_out_dir(copy_out_dir).await.map(|_| outcome)
+        }
+        (outcome, _) => outcome,
+    };
+
+    session.cleanup().await?;
+
+    outcome
+}
+
+/// A sandbox box that is initialised once and reused for many runs of the
+/// same program, e.g. every testcase of a submission.
+///
+/// Files copied in at creation (the compiled binary, build artifacts) stay in
+/// the box. Call [`SandboxSession::reset`] between runs to drop stdin, stdout
+/// and anything else a run left behind and to restore the setup files, so
+/// testcases don't see each other's files.
+pub struct SandboxSession {
+    sandbox_box: Box<dyn SandboxBackend>,
+    /// Host directory the setup files were copied from
+    work_dir: PathBuf,
+    /// Names of the files present in the box right after setup
+    initial_files: HashSet<OsString>,
+}
+
+impl SandboxSession {
+    /// Initialise a one-off box and copy the files of `work_dir` into it
+    pub async fn new(work_dir: &Path) -> anyhow::Result<Self> {
+        Self::with_box(work_dir, None).await
     }
 
-    // Initialize isolate box with auto-assigned box_id
-    let box_id = next_box_id();
-    let isolate_box = IsolateBox::new(box_id, true).await?;
+    /// Initialise the box of a parallel testcase slot (0-based).
+    ///
+    /// A worker judges one job at a time, so with CPU pinning enabled each
+    /// slot maps to a fixed CPU.
+    pub async fn for_slot(work_dir: &Path, slot: u32) -> anyhow::Result<Self> {
+        Self::with_box(work_dir, get_config().slot_cpu(slot)).await
+    }
 
-    // Copy all files from work_dir to box
-    isolate_box.copy_dir_in(&spec.work_dir).await?;
+    async fn with_box(work_dir: &Path, cpu: Option<usize>) -> anyhow::Result<Self> {
+        let mut sandbox_box = create_box().await?;
+        if let Some(cpu) = cpu {
+            sandbox_box.pin_to_cpu(cpu);
+        }
 
-    // Prepare stdin if provided
-    let stdin_path = if let Some(content) = &spec.stdin {
-        let temp_file = tempfile::NamedTempFile::new()?;
-        fs::write(temp_file.path(), content).await?;
-        Some(temp_file)
-    } else {
-        None
-    };
+        // Copy all files from work_dir to box
+        if let Err(e) = sandbox_box.copy_dir_in(work_dir).await {
+            let _ = sandbox_box.cleanup().await;
+            return Err(e);
+        }
+
+        let initial_files = match list_dir(&sandbox_box.work_dir()).await {
+            Ok(files) => files,
+            Err(e) => {
+                let _ = sandbox_box.cleanup().await;
+                return Err(e.into());
+            }
+        };
 
-    // Build IO spec
-    let mut io = IoSpec::new();
-    if let Some(ref temp_file) = stdin_path {
-        io = io.with_stdin(temp_file.path());
+        Ok(Self {
+            sandbox_box,
+            work_dir: work_dir.to_path_buf(),
+            initial_files,
+        })
     }
 
-    // Build sandbox limits
-    let sandbox_limits = Limits {
-        time_ms: spec.limits.time_ms,
-        memory_mb: spec.limits.memory_mb,
-        processes: 64,
-        open_files: 256,
-        fsize_kb: 262144,
-    };
+    /// Copy a file into the box for the next run only
+    pub async fn copy_in(&self, source: &Path, dest_name: &str) -> anyhow::Result<()> {
+        self.sandbox_box.copy_in(source, dest_name).await
+    }
 
-    // Run command in sandbox
-    let outcome = isolate_box.run(&spec.command, &sandbox_limits, &io).await?;
+    /// Run a command in the box.
+    ///
+    /// `spec.work_dir` and `spec.copy_out_dir` are ignored; the box already
+    /// holds the files from [`SandboxSession::new`].
+    pub async fn run(&self, spec: &ExecutionSpec) -> anyhow::Result<ExecutionOutcome> {
+        if spec.command.is_empty() {
+            return Err(anyhow::anyhow!("No command specified for execution"));
+        }
 
-    // Copy output files if copy_out_dir is specified
-    if let Some(ref copy_out_dir) = spec.copy_out_dir {
-        let box_work_dir = isolate_box.work_dir();
-        let mut entries = fs::read_dir(&box_work_dir).await?;
+        // Prepare stdin if provided
+        let stdin_path = if let Some(content) = &spec.stdin {
+            let temp_file = tempfile::NamedTempFile::new()?;
+            fs::write(temp_file.path(), content).await?;
+            Some(temp_file)
+        } else {
+            None
+        };
+
+        // Build IO spec
+        let mut io = IoSpec::new().with_binds(&spec.binds);
+        if let Some(ref temp_file) = stdin_path {
+            io = io.with_stdin(temp_file.path());
+        }
+
+        // Run command in sandbox
+        let outcome = self
+            .sandbox_box
+            .run(&spec.command, &sandbox_limits(spec), &io)
+            .await?;
+
+        Ok(into_execution_outcome(outcome, &spec.limits, spec.seccomp))
+    }
+
+    /// Copy every file in the box to `dir`
+    pub async fn copy_out_dir(&self, dir: &Path) -> anyhow::Result<()> {
+        let mut entries = fs::read_dir(self.sandbox_box.work_dir()).await?;
         while let Some(entry) = entries.next_entry().await? {
             let metadata = entry.metadata().await?;
             if metadata.is_dir() {
                 continue;
             }
-            let file_name = entry.file_name();
-            let dest = copy_out_dir.join(&file_name);
-            fs::copy(entry.path(), &dest).await?;
+            fs::copy(entry.path(), dir.join(entry.file_name())).await?;
         }
+        Ok(())
     }
 
-    // Cleanup isolate box
-    isolate_box.cleanup().await?;
+    /// Bring the box back to its state right after setup.
+    ///
+    /// The program owns the box directory and may have rewritten, truncated
+    /// or replaced the setup files (even with symlinks), so everything is
+    /// removed and the setup files are copied in again from `work_dir`.
+    pub async fn reset(&self) -> anyhow::Result<()> {
+        let box_dir = self.sandbox_box.work_dir();
+        let mut entries = fs::read_dir(&box_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                fs::remove_dir_all(entry.path()).await?;
+            } else {
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+
+        for name in &self.initial_files {
+            let source = self.work_dir.join(name);
+            fs::copy(&source, box_dir.join(name))
+                .await
+                .with_context(|| format!("Failed to restore {:?}", source))?;
+        }
+        Ok(())
+    }
+
+    /// Cleanup the box
+    pub async fn cleanup(self) -> anyhow::Result<()> {
+        self.sandbox_box.cleanup().await
+    }
+}
+
+/// Names of the entries directly inside a directory
+async fn list_dir(dir: &Path) -> std::io::Result<HashSet<OsString>> {
+    let mut names = HashSet::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        names.insert(entry.file_name());
+    }
+    Ok(names)
+}
+
+/// Run a sandboxed program and an interactor at the same time.
+///
+/// The interactor's stdout is piped into the program's stdin and the program's
+/// stdout into the interactor's stdin, so `spec.stdin` is ignored. The
+/// interactor runs in its own box unless trusted tools are enabled. Returns
+/// `(program, interactor)` outcomes; the program outcome carries no stdout
+/// since it is consumed by the interactor.
+pub async fn execute_interactive(
+    spec: &ExecutionSpec,
+    interactor: &ExecutionSpec,
+) -> anyhow::Result<(ExecutionOutcome, ExecutionOutcome)> {
+    if spec.command.is_empty() || interactor.command.is_empty() {
+        return Err(anyhow::anyhow!("No command specified for execution"));
+    }
+
+    let sandbox_box = create_box().await?;
+    let result = run_interactive(sandbox_box.as_ref(), spec, interactor).await;
+
+    // Clean up even if the runs failed, so the box is not leaked
+    sandbox_box.cleanup().await?;
+
+    result
+}
+
+/// Body of [`execute_interactive`] that runs the program in `sandbox_box`;
+/// the caller owns and cleans up the box.
+async fn run_interactive(
+    sandbox_box: &dyn SandboxBackend,
+    spec: &ExecutionSpec,
+    interactor: &ExecutionSpec,
+) -> anyhow::Result<(ExecutionOutcome, ExecutionOutcome)> {
+    sandbox_box.copy_dir_in(&spec.work_dir).await?;
+
+    let (to_program, from_interactor) = std::io::pipe()?;
+    let (to_interactor, from_program) = std::io::pipe()?;
+
+    let limits = sandbox_limits(spec);
+    let program_run = sandbox_box.run_with_stdio(
+        &spec.command,
+        &limits,
+        "stderr.txt",
+        &spec.binds,
+        to_program.into(),
+        from_program.into(),
+    );
+
+    if get_config().trusted_tools {
+        let mut command = tokio::process::Command::new(&interactor.command[0]);
+        command
+            .args(&interactor.command[1..])
+            .current_dir(&interactor.work_dir)
+            .stdin(Stdio::from(to_interactor))
+            .stdout(Stdio::from(from_interactor))
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let child = command.spawn().context("Failed to spawn interactor")?;
+        // Drop our pipe ends so each side sees EOF once the other exits
+        drop(command);
+
+        let interactor_run = tokio::time::timeout(
+            Duration::from_millis(interactor.limits.time_ms as u64),
+            child.wait_with_output(),
+        );
+        let (program_result, interactor_result) = tokio::join!(program_run, interactor_run);
+
+        let interactor_outcome = match interactor_result {
+            Ok(output) => {
+                let output = output.context("Failed to wait for interactor")?;
+                ExecutionOutcome {
+                    status: ExecutionStatus::Exited(output.status.code().unwrap_or(-1)),
+                    time_ms: 0,
+                    memory_kb: 0,
+                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                    stdout_bytes: output.stdout,
+                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                }
+            }
+            // The child is killed on drop
+            Err(_) => ExecutionOutcome {
+                status: ExecutionStatus::TimeLimitExceeded,
+                time_ms: interactor.limits.time_ms,
+                memory_kb: 0,
+                stdout: String::new(),
+                stdout_bytes: vec![],
+                stderr: String::new(),
+            },
+        };
+
+        program_result.map(|program| {
+            (
+                into_execution_outcome(program, &spec.limits, spec.seccomp),
+                interactor_outcome,
+            )
+        })
+    } else {
+        let interactor_box = create_box().await?;
+        let result = async {
+            interactor_box.copy_dir_in(&interactor.work_dir).await?;
+
+            let interactor_limits = sandbox_limits(interactor);
+            let interactor_run = interactor_box.run_with_stdio(
+                &interactor.command,
+                &interactor_limits,
+                "stderr.txt",
+                &interactor.binds,
+                to_interactor.into(),
+                from_interactor.into(),
+            );
+            let (program_result, interactor_result) = tokio::join!(program_run, interactor_run);
+
+            program_result.and_then(|program| {
+                Ok((
+                    into_execution_outcome(program, &spec.limits, spec.seccomp),
+                    into_execution_outcome(
+                        interactor_result?,
+                        &interactor.limits,
+                        interactor.seccomp,
+                    ),
+                ))
+            })
+        }
+        .await;
+
+        interactor_box.cleanup().await?;
 
+        result
+    }
+}
+
+/// Build isolate limits from an execution spec
+fn sandbox_limits(spec: &ExecutionSpec) -> Limits {
+    Limits {
+        time_ms: spec.limits.time_ms,
+        memory_mb: spec.limits.memory_mb,
+        processes: 64,
+        open_files: 256,
+        fsize_kb: spec.output_limit_kb,
+        seccomp: spec.seccomp,
+    }
+}
+
+/// Interpret a raw sandbox outcome against the requested limits
+///
+/// SIGSYS only means a forbidden syscall under a language profile: base
+/// profile runs (compilers, tools) can raise it for other reasons.
+fn into_execution_outcome(
+    outcome: SandboxOutcome,
+    limits: &ExecutionLimits,
+    seccomp: SeccompProfile,
+) -> ExecutionOutcome {
     // Convert IsolateStatus to ExecutionStatus
-    let memory_limit_kb = spec.limits.memory_mb * 1024;
+    let memory_limit_kb = limits.memory_mb * 1024;
     let status = match outcome.meta.status {
         IsolateStatus::Ok if outcome.meta.exit_code == 0 => {
             if outcome.meta.memory_kb > memory_limit_kb {
@@ -253,9 +537,19 @@ pub async fn execute_sandboxed(spec: &ExecutionSpec) -> anyhow::Result<Execution
             }
         }
         IsolateStatus::Ok => ExecutionStatus::Exited(outcome.meta.exit_code),
+        // Isolate reports a non-zero exit as RE; keep the exit code for callers
+        // that interpret it (testlib checkers, validators, compilers)
+        IsolateStatus::RuntimeError if outcome.meta.exit_code != 0 => {
+            ExecutionStatus::Exited(outcome.meta.exit_code)
+        }
         IsolateStatus::TimeOut => ExecutionStatus::TimeLimitExceeded,
+        // Seccomp filters kill with SIGSYS
+        IsolateStatus::Signal(libc::SIGSYS) if seccomp != SeccompProfile::Base => {
+            ExecutionStatus::RestrictedFunction
+        }
         IsolateStatus::Signal(sig) => ExecutionStatus::Signaled(sig),
         IsolateStatus::RuntimeError => ExecutionStatus::RuntimeError,
+        IsolateStatus::OutputLimitExceeded => ExecutionStatus::OutputLimitExceeded,
         IsolateStatus::InternalError => ExecutionStatus::SystemError,
     };
 
@@ -268,12 +562,95 @@ pub async fn execute_sandboxed(spec: &ExecutionSpec) -> anyhow::Result<Execution
         status
     };
 
-    Ok(ExecutionOutcome {
+    ExecutionOutcome {
         status,
         time_ms: outcome.meta.time_ms,
         memory_kb: outcome.meta.memory_kb,
         stdout: outcome.stdout,
         stdout_bytes: outcome.stdout_bytes,
         stderr: outcome.stderr,
-    })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::mock::{MockRun, MockSandbox};
+    use crate::sandbox::with_provider;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_session_reset_restores_tampered_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Main"), "original").unwrap();
+
+        // Every run reports the binary it found, then replaces it
+        let sandbox = Arc::new(MockSandbox::new(|call| {
+            let main = call.work_dir.join("Main");
+            let found = std::fs::read_to_string(&main).unwrap();
+            std::fs::write(&main, "tampered").unwrap();
+            std::fs::write(call.work_dir.join("leftover.txt"), "x").unwrap();
+            MockRun::stdout(found)
+        }));
+
+        let (first, second, leftover) = with_provider(sandbox, async {
+            let session = SandboxSession::new(dir.path()).await.unwrap();
+            let spec = ExecutionSpec::new(dir.path()).with_command(["./Main"]);
+            let first = session.run(&spec).await.unwrap();
+            session.reset().await.unwrap();
+            let leftover = session.sandbox_box.work_dir().join("leftover.txt").exists();
+            let second = session.run(&spec).await.unwrap();
+            session.cleanup().await.unwrap();
+            (first, second, leftover)
+        })
+        .await;
+
+        assert_eq!(first.stdout, "original");
+        assert_eq!(second.stdout, "original");
+        assert!(!leftover);
+    }
+
+    #[tokio::test]
+    async fn test_interactive_cleans_up_boxes_when_copy_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let sandbox = Arc::new(MockSandbox::new(|_| MockRun::exited(0)));
+
+        let (program_failed, interactor_failed) = with_provider(sandbox.clone(), async {
+            let present = ExecutionSpec::new(dir.path()).with_command(["./Main"]);
+            let absent = ExecutionSpec::new(&missing).with_command(["./interactor"]);
+            (
+                execute_interactive(&absent, &present).await.is_err(),
+                execute_interactive(&present, &absent).await.is_err(),
+            )
+        })
+        .await;
+
+        assert!(program_failed);
+        assert!(interactor_failed);
+        assert_eq!(sandbox.open_boxes(), 0);
+    }
+
+    #[test]
+    fn test_sigsys_is_restricted_function_only_under_language_profiles() {
+        let killed = || SandboxOutcome {
+            meta: crate::sandbox::IsolateMeta {
+                time_ms: 5,
+                memory_kb: 1024,
+                exit_code: 0,
+                status: IsolateStatus::Signal(libc::SIGSYS),
+                wall_time_ms: 5,
+            },
+            stdout: String::new(),
+            stdout_bytes: vec![],
+            stderr: String::new(),
+        };
+        let limits = ExecutionLimits::default();
+
+        let strict = into_execution_outcome(killed(), &limits, SeccompProfile::Strict);
+        let base = into_execution_outcome(killed(), &limits, SeccompProfile::Base);
+
+        assert_eq!(strict.status, ExecutionStatus::RestrictedFunction);
+        assert_eq!(base.status, ExecutionStatus::Signaled(libc::SIGSYS));
+    }
 }
@@ -0,0 +1,185 @@
+//! Interactor module for interactive problems
+//!
+//! This module handles running testlib.h-based interactors. The user program
+//! and the interactor run at the same time, connected by pipes, and the
+//! interactor's exit code decides the verdict.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::{debug, info};
+
+use crate::checker::{interpret_testlib_result, status_to_verdict, Verdict};
+use crate::compiler::{TestlibCompiler, TestlibTool};
+use crate::executer::{
+    execute_interactive, ExecutionLimits, ExecutionOutcome, ExecutionSpec, ExecutionStatus,
+};
+use crate::sandbox::get_config;
+use crate::storage::StorageClient;
+use crate::utils::file_name;
+
+/// Result of an interactive run
+#[derive(Debug)]
+pub struct InteractiveResult {
+    pub verdict: Verdict,
+    /// Fraction of the testcase's points earned (0.0 ~ 1.0)
+    pub score: f64,
+    /// Outcome of the user program (stdout is consumed by the interactor)
+    pub run: ExecutionOutcome,
+}
+
+/// Run a user program against a testlib.h-based interactor
+///
+/// Arguments to interactor: <input_file> <output_file> <answer_file>
+pub async fn run_interactive(
+    run_spec: &ExecutionSpec,
+    interactor_path: &Path,
+    input_path: &Path,
+    answer_path: &Path,
+    timeout_secs: u64,
+) -> Result<InteractiveResult> {
+    info!(
+        "Running interactor: {:?} with input={:?}, answer={:?}",
+        interactor_path, input_path, answer_path
+    );
+
+    let limits = ExecutionLimits {
+        time_ms: (timeout_secs * 1000) as u32,
+        memory_mb: 512,
+    };
+
+    let interactor_spec = if get_config().trusted_tools {
+        let work_dir = input_path.parent().unwrap_or(Path::new("."));
+        let output_path = work_dir.join("interactor_output.txt");
+
+        ExecutionSpec::new(work_dir)
+            .with_command([
+                interactor_path.to_str().unwrap_or(""),
+                input_path.to_str().unwrap_or(""),
+                output_path.to_str().unwrap_or(""),
+                answer_path.to_str().unwrap_or(""),
+            ])
+            .with_limits(limits)
+    } else {
+        // Same layout as sandboxed checkers: binary copied in, files mounted read-only
+        ExecutionSpec::new(interactor_path.parent().unwrap_or(Path::new(".")))
+            .with_command([
+                format!("./{}", file_name(interactor_path)?),
+                format!("/input/{}", file_name(input_path)?),
+                "interactor_output.txt".to_string(),
+                format!("/answer/{}", file_name(answer_path)?),
+            ])
+            .with_bind(input_path.parent().unwrap_or(Path::new(".")), "/input")
+            .with_bind(answer_path.parent().unwrap_or(Path::new(".")), "/answer")
+            .with_limits(limits)
+    };
+
+    let (run, interactor) = execute_interactive(run_spec, &interactor_spec)
+        .await
+        .context("Failed to run interactor")?;
+
+    debug!(
+        "Interactor result: status={:?}, user status={:?}, stderr={}",
+        interactor.status,
+        run.status,
+        interactor.stderr.chars().take(200).collect::<String>()
+    );
+
+    // Interactor message is in stderr (testlib writes to stderr)
+    let message = if interactor.stderr.is_empty() {
+        None
+    } else {
+        Some(interactor.stderr.trim().to_string())
+    };
+    let (verdict, score) = combine_verdict(&run.status, interactor.exit_code(), message);
+
+    Ok(InteractiveResult {
+        verdict,
+        score,
+        run,
+    })
+}
+
+/// Combine the user program's status with the interactor's result.
+///
+/// A user-side failure (TLE, MLE, RE) wins over whatever the interactor
+/// reported, since the interactor usually just sees a broken pipe then.
+fn combine_verdict(
+    user_status: &ExecutionStatus,
+    interactor_exit_code: i32,
+    interactor_message: Option<String>,
+) -> (Verdict, f64) {
+    match user_status {
+        ExecutionStatus::Exited(0) => {
+            let result = interpret_testlib_result(interactor_exit_code, interactor_message);
+            (result.verdict, result.score)
+        }
+        status => (status_to_verdict(status), 0.0),
+    }
+}
+
+/// Interactor manager for handling interactor compilation and caching
+pub struct InteractorManager {
+    /// Compiler for interactors
+    compiler: TestlibCompiler,
+}
+
+impl Default for InteractorManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InteractorManager {
+    /// Create a new interactor manager
+    pub fn new() -> Self {
+        Self {
+            compiler: TestlibCompiler::new(TestlibTool::Interactor),
+        }
+    }
+
+    /// Get the path to a compiled interactor, compiling it if necessary
+    pub async fn get_interactor(
+        &self,
+        storage: &StorageClient,
+        interactor_source_path: &str,
+        problem_id: i64,
+    ) -> Result<std::path::PathBuf> {
+        // Download source from storage
+        info!("Downloading interactor source: {}", interactor_source_path);
+        let source_content = storage.download_string(interactor_source_path).await?;
+
+        // Compile or get cached
+        self.compiler
+            .get_or_compile(&source_content, problem_id)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine_verdict_uses_interactor_on_clean_exit() {
+        assert_eq!(
+            combine_verdict(&ExecutionStatus::Exited(0), 0, None),
+            (Verdict::Accepted, 1.0)
+        );
+        assert_eq!(
+            combine_verdict(&ExecutionStatus::Exited(0), 1, None),
+            (Verdict::WrongAnswer, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_combine_verdict_user_failure_wins() {
+        assert_eq!(
+            combine_verdict(&ExecutionStatus::TimeLimitExceeded, 1, None),
+            (Verdict::TimeLimitExceeded, 0.0)
+        );
+        assert_eq!(
+            combine_verdict(&ExecutionStatus::Signaled(13), 1, None),
+            (Verdict::RuntimeError, 0.0)
+        );
+    }
+}
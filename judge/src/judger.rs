@@ -5,14 +5,26 @@
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Instant;
 use tracing::{info, warn};
 
-use crate::checker::{CheckerManager, Verdict, DEFAULT_CHECKER_TIMEOUT_SECS};
+use crate::checker::{status_to_verdict, CheckerManager, Verdict, DEFAULT_CHECKER_TIMEOUT_SECS};
 use crate::compiler::compile_in_sandbox;
-use crate::executer::{execute_sandboxed, ExecutionLimits, ExecutionSpec, ExecutionStatus};
-use crate::languages;
+use crate::error::is_transient;
+use crate::executer::{
+    ExecutionLimits, ExecutionOutcome, ExecutionSpec, ExecutionStatus, SandboxSession,
+    DEFAULT_OUTPUT_LIMIT_KB,
+};
+use crate::interactor::{run_interactive, InteractorManager};
+use crate::languages::{self, LanguageConfig};
+use crate::metrics::{metrics, Timing};
+use crate::progress::{ProgressEvent, ProgressSink, ProgressSubject};
 use crate::sandbox::get_config;
 use crate::storage::StorageClient;
+use crate::subtask::{
+    score_subtasks, ScoringPolicy, SubtaskInfo, SubtaskResult, SubtaskTracker, TestcaseOutcome,
+};
 
 /// Problem type enum for judging strategy
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
@@ -21,6 +33,35 @@ pub enum ProblemType {
     #[default]
     Icpc,
     SpecialJudge,
+    Interactive,
+}
+
+/// Built-in output comparison used when no checker is given
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum CompareMode {
+    /// Ignore trailing whitespace on each line and trailing blank lines
+    #[default]
+    Lines,
+    /// Compare whitespace-separated tokens
+    Tokens,
+    /// Same as `Lines`, ignoring ASCII case
+    CaseInsensitive,
+    /// Compare tokens, accepting numbers within an absolute or relative error
+    Float {
+        #[serde(default = "default_float_eps")]
+        abs_eps: f64,
+        #[serde(default = "default_float_eps")]
+        rel_eps: f64,
+    },
+    /// Same as `Lines`, but lines may appear in any order
+    UnorderedLines,
+    /// Byte-for-byte comparison
+    Exact,
+}
+
+fn default_float_eps() -> f64 {
+    1e-6
 }
 
 /// Job received from the Redis queue
@@ -36,12 +77,36 @@ pub struct JudgeJob {
     pub ignore_memory_limit_bonus: bool,
     pub max_score: i64,
     pub testcases: Vec<TestcaseInfo>,
-    /// Problem type (icpc or special_judge)
+    /// Problem type (icpc, special_judge or interactive)
     #[serde(default)]
     pub problem_type: ProblemType,
     /// Checker source path in MinIO (for special_judge)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub checker_path: Option<String>,
+    /// Interactor source path in MinIO (for interactive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interactor_path: Option<String>,
+    /// Subtask definitions (empty for all-or-nothing scoring)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtasks: Vec<SubtaskInfo>,
+    /// Run every testcase instead of stopping at the first failure
+    #[serde(default)]
+    pub full_run: bool,
+    /// How testcase scores combine when there are no subtasks: `min` keeps
+    /// the problem all-or-nothing, `sum` gives each testcase an equal share of
+    /// `max_score` and runs every testcase
+    #[serde(default)]
+    pub scoring: ScoringPolicy,
+    /// Output comparison for problems without a checker
+    #[serde(default)]
+    pub compare_mode: CompareMode,
+    /// Output limit in MB
+    #[serde(default = "default_output_limit")]
+    pub output_limit: u32,
+}
+
+fn default_output_limit() -> u32 {
+    DEFAULT_OUTPUT_LIMIT_KB / 1024
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,6 +114,9 @@ pub struct TestcaseInfo {
     pub id: i64,
     pub input_path: String,
     pub output_path: String,
+    /// Subtask this testcase belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtask_id: Option<i64>,
 }
 
 /// Result of judging a submission
@@ -60,6 +128,9 @@ pub struct JudgeResult {
     pub execution_time: Option<u32>,
     pub memory_used: Option<u32>,
     pub testcase_results: Vec<TestcaseResult>,
+    /// Per-subtask scores (only for problems with subtasks)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtask_results: Vec<SubtaskResult>,
     /// Compile error / Runtime error message
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_message: Option<String>,
@@ -74,9 +145,29 @@ impl JudgeResult {
             execution_time: None,
             memory_used: None,
             testcase_results: vec![],
+            subtask_results: vec![],
             error_message: Some(error),
         }
     }
+
+    pub fn cancelled(submission_id: i64) -> Self {
+        Self {
+            submission_id,
+            verdict: Verdict::Cancelled.to_string(),
+            score: 0,
+            execution_time: None,
+            memory_used: None,
+            testcase_results: vec![],
+            subtask_results: vec![],
+            error_message: None,
+        }
+    }
+
+    /// Error message if judging ended in a system error
+    pub fn system_error_message(&self) -> Option<&str> {
+        (self.verdict == Verdict::SystemError.to_string())
+            .then(|| self.error_message.as_deref().unwrap_or("System error"))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -88,6 +179,9 @@ pub struct TestcaseResult {
     /// 실제 프로그램 출력 (디버깅/보안 테스트용, 최대 4KB)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub output: Option<String>,
+    /// Fraction of the testcase's points earned (0.0 ~ 1.0), partial credit from checkers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
 }
 
 /// Process a judge job
@@ -95,7 +189,8 @@ pub async fn process_judge_job(
     job: &JudgeJob,
     storage: &StorageClient,
     checker_manager: &CheckerManager,
-    redis: &mut crate::redis_manager::RedisManager,
+    interactor_manager: &InteractorManager,
+    progress: &mut dyn ProgressSink,
 ) -> Result<JudgeResult> {
     let lang_config = languages::get_language_config(&job.language)
         .ok_or_else(|| anyhow::anyhow!("Unsupported language: {}", job.language))?;
@@ -105,10 +200,13 @@ pub async fn process_judge_job(
 
     std::fs::write(&source_path, &job.code)?;
 
+    let subject = ProgressSubject::SubmissionId(job.submission_id);
+
     // Compile if needed
     if let Some(compile_cmd) = &lang_config.compile_command {
         let config = get_config();
 
+        progress.publish(subject, ProgressEvent::CompileStart).await;
         let compile_result = compile_in_sandbox(
             temp_dir.path(),
             compile_cmd,
@@ -116,6 +214,14 @@ pub async fn process_judge_job(
             config.compile_memory_limit_mb,
         )
         .await?;
+        progress
+            .publish(
+                subject,
+                ProgressEvent::CompileDone {
+                    success: compile_result.success,
+                },
+            )
+            .await;
 
         if !compile_result.success {
             return Ok(JudgeResult {
@@ -125,6 +231,7 @@ pub async fn process_judge_job(
                 execution_time: None,
                 memory_used: None,
                 testcase_results: vec![],
+                subtask_results: vec![],
                 error_message: compile_result.message,
             });
         }
@@ -139,6 +246,8 @@ pub async fn process_judge_job(
                     .await
                 {
                     Ok(binary_path) => Some(binary_path),
+                    // Retried by the worker instead of stored
+                    Err(e) if is_transient(&e) => return Err(e),
                     Err(e) => {
                         warn!(
                             "Failed to get checker for problem {}: {:#}",
@@ -151,6 +260,7 @@ pub async fn process_judge_job(
                             execution_time: None,
                             memory_used: None,
                             testcase_results: vec![],
+                            subtask_results: vec![],
                             error_message: Some(format!("Failed to compile checker: {:#}", e)),
                         });
                     }
@@ -164,6 +274,7 @@ pub async fn process_judge_job(
                     execution_time: None,
                     memory_used: None,
                     testcase_results: vec![],
+                    subtask_results: vec![],
                     error_message: Some("Special judge problem requires a checker".to_string()),
                 });
             }
@@ -172,6 +283,52 @@ pub async fn process_judge_job(
         None
     };
 
+    // Get interactor path if this is an interactive problem
+    let interactor_binary = if job.problem_type == ProblemType::Interactive {
+        match &job.interactor_path {
+            Some(path) => {
+                match interactor_manager
+                    .get_interactor(storage, path, job.problem_id)
+                    .await
+                {
+                    Ok(binary_path) => Some(binary_path),
+                    // Retried by the worker instead of stored
+                    Err(e) if is_transient(&e) => return Err(e),
+                    Err(e) => {
+                        warn!(
+                            "Failed to get interactor for problem {}: {:#}",
+                            job.problem_id, e
+                        );
+                        return Ok(JudgeResult {
+                            submission_id: job.submission_id,
+                            verdict: Verdict::SystemError.to_string(),
+                            score: 0,
+                            execution_time: None,
+                            memory_used: None,
+                            testcase_results: vec![],
+                            subtask_results: vec![],
+                            error_message: Some(format!("Failed to compile interactor: {:#}", e)),
+                        });
+                    }
+                }
+            }
+            None => {
+                return Ok(JudgeResult {
+                    submission_id: job.submission_id,
+                    verdict: Verdict::SystemError.to_string(),
+                    score: 0,
+                    execution_time: None,
+                    memory_used: None,
+                    testcase_results: vec![],
+                    subtask_results: vec![],
+                    error_message: Some("Interactive problem requires an interactor".to_string()),
+                });
+            }
+        }
+    } else {
+        None
+    };
+
     let mut testcase_results = Vec::with_capacity(job.testcases.len());
     let mut overall_verdict = Verdict::Accepted;
     let mut max_time = 0u32;
@@ -179,121 +336,158 @@ pub async fn process_judge_job(
 
     let total_testcases = job.testcases.len();
 
-    for (idx, tc) in job.testcases.iter().enumerate() {
-        let input_content = storage
-            .download_string(&tc.input_path)
-            .await
-            .with_context(|| format!("Failed to download testcase input: {}", tc.input_path))?;
+    // Subtask problems keep running past failures so that every subtask gets scored
+    let has_subtasks = !job.subtasks.is_empty();
+    let mut subtask_tracker = SubtaskTracker::new();
+    let mut outcomes = Vec::with_capacity(job.testcases.len());
 
-        let expected_output = storage
-            .download_string(&tc.output_path)
+    // Non-interactive runs reuse one box per parallel slot for the whole submission
+    let mut sessions = Vec::new();
+    if interactor_binary.is_none() {
+        for slot in 0..get_config().testcase_concurrency {
+            match SandboxSession::for_slot(temp_dir.path(), slot).await {
+                Ok(session) => sessions.push(session),
+                Err(e) => {
+                    let _ = cleanup_sessions(sessions).await;
+                    return Err(e);
+                }
+            }
+        }
+    }
+    let concurrency = sessions.len().max(1);
+
+    let ctx = TestcaseContext {
+        job,
+        storage,
+        lang_config: &lang_config,
+        work_dir: temp_dir.path(),
+        checker_binary: checker_binary.as_deref(),
+        interactor_binary: interactor_binary.as_deref(),
+    };
+
+    let mut next_idx = 0;
+    // Collect the outcome first so the sessions are cleaned up on every path
+    let judged: anyhow::Result<()> = async {
+        'judging: while next_idx < total_testcases {
+            // Take the next batch of testcases that still need to run. Skip decisions
+            // only see earlier batches, so parallel runs may judge a few extra cases.
+            let mut batch = Vec::with_capacity(concurrency);
+            let mut to_run = Vec::with_capacity(concurrency);
+            while next_idx < total_testcases && to_run.len() < concurrency {
+                let tc = &job.testcases[next_idx];
+                let skip = !job.full_run
+                    && find_subtask(job, tc).is_some_and(|s| subtask_tracker.should_skip(s));
+                if !skip {
+                    to_run.push(tc);
+                }
+                batch.push((next_idx, skip));
+                next_idx += 1;
+            }
+
+            // Results come back in submission order regardless of which run finishes first
+            let mut runs = futures::future::join_all(
+                to_run
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, tc)| run_testcase(&ctx, tc, sessions.get(slot))),
+            )
             .await
-            .with_context(|| format!("Failed to download testcase output: {}", tc.output_path))?;
+            .into_iter();
 
-        let adjusted_time_limit = if job.ignore_time_limit_bonus {
-            job.time_limit
-        } else {
-            lang_config.calculate_time_limit(job.time_limit)
-        };
-        let adjusted_memory_limit = if job.ignore_memory_limit_bonus {
-            job.memory_limit
-        } else {
-            lang_config.calculate_memory_limit(job.memory_limit)
-        };
+            for (idx, skip) in batch {
+                let tc = &job.testcases[idx];
 
-        // Run user's program using execute_sandboxed
-        let spec = ExecutionSpec::new(temp_dir.path())
-            .with_command(&lang_config.run_command)
-            .with_limits(ExecutionLimits {
-                time_ms: adjusted_time_limit,
-                memory_mb: adjusted_memory_limit,
-            })
-            .with_stdin(&input_content);
+                if skip {
+                    testcase_results.push(TestcaseResult {
+                        testcase_id: tc.id,
+                        verdict: Verdict::Skipped.to_string(),
+                        execution_time: None,
+                        memory_used: None,
+                        output: None,
+                        score: None,
+                    });
+                    outcomes.push(TestcaseOutcome {
+                        subtask_id: tc.subtask_id,
+                        verdict: Verdict::Skipped,
+                        ratio: 0.0,
+                    });
+                    continue;
+                }
 
-        let run_result = execute_sandboxed(&spec).await?;
+                let (run_result, verdict, score) =
+                    runs.next().context("Missing result for testcase run")??;
 
-        let output_preview = if run_result.stdout.is_empty() {
-            None
-        } else {
-            let truncated: String = run_result.stdout.chars().take(4096).collect();
-            Some(truncated)
-        };
+                let output_preview = if run_result.stdout.is_empty() {
+                    None
+                } else {
+                    let truncated: String = run_result.stdout.chars().take(4096).collect();
+                    Some(truncated)
+                };
 
-        max_time = max_time.max(run_result.time_ms);
-        max_memory = max_memory.max(run_result.memory_kb);
+                max_time = max_time.max(run_result.time_ms);
+                max_memory = max_memory.max(run_result.memory_kb);
 
-        // Determine verdict based on run status and problem type
-        let verdict = match run_result.status {
-            ExecutionStatus::Exited(0) => {
-                // Program ran successfully, check output
-                if let Some(ref checker_path) = checker_binary {
-                    // Special judge: run checker
-                    let checker_temp_dir = tempfile::tempdir()?;
-                    let input_path = checker_temp_dir.path().join("input.txt");
-                    let output_path = checker_temp_dir.path().join("output.txt");
-                    let answer_path = checker_temp_dir.path().join("answer.txt");
-
-                    tokio::fs::write(&input_path, &input_content).await?;
-                    tokio::fs::write(&output_path, &run_result.stdout).await?;
-                    tokio::fs::write(&answer_path, &expected_output).await?;
-
-                    match crate::checker::run_checker(
-                        checker_path,
-                        &input_path,
-                        &output_path,
-                        &answer_path,
-                        DEFAULT_CHECKER_TIMEOUT_SECS,
-                    )
-                    .await
-                    {
-                        Ok(checker_result) => checker_result.verdict,
-                        Err(e) => {
-                            warn!("Checker failed for testcase {}: {}", tc.id, e);
-                            Verdict::SystemError
-                        }
-                    }
+                let (execution_time, memory_used) = if score > 0.0 || job.full_run {
+                    (Some(run_result.time_ms), Some(run_result.memory_kb))
                 } else {
-                    // ICPC: simple string comparison
-                    if compare_output(&run_result.stdout, &expected_output) {
-                        Verdict::Accepted
-                    } else {
-                        Verdict::WrongAnswer
-                    }
-                }
-            }
-            ExecutionStatus::Exited(_) => Verdict::RuntimeError,
-            ExecutionStatus::TimeLimitExceeded => Verdict::TimeLimitExceeded,
-            ExecutionStatus::MemoryLimitExceeded => Verdict::MemoryLimitExceeded,
-            ExecutionStatus::Signaled(_) => Verdict::RuntimeError,
-            ExecutionStatus::RuntimeError => Verdict::RuntimeError,
-            ExecutionStatus::SystemError => Verdict::SystemError,
-        };
+                    (None, None)
+                };
 
-        let (execution_time, memory_used) = if verdict == Verdict::Accepted {
-            (Some(run_result.time_ms), Some(run_result.memory_kb))
-        } else {
-            (None, None)
-        };
+                progress
+                    .publish(
+                        subject,
+                        ProgressEvent::testcase(
+                            tc.id,
+                            idx + 1,
+                            total_testcases,
+                            &verdict,
+                            execution_time,
+                            memory_used,
+                        ),
+                    )
+                    .await;
 
-        let tc_result = TestcaseResult {
-            testcase_id: tc.id,
-            verdict: verdict.to_string(),
-            execution_time,
-            memory_used,
-            output: output_preview,
-        };
+                let tc_result = TestcaseResult {
+                    testcase_id: tc.id,
+                    verdict: verdict.to_string(),
+                    execution_time,
+                    memory_used,
+                    output: output_preview,
+                    score: Some(score),
+                };
 
-        testcase_results.push(tc_result);
+                testcase_results.push(tc_result);
 
-        // Publish progress update
-        let _ = redis
-            .publish_progress(job.submission_id, idx + 1, total_testcases)
-            .await;
+                if let Some(subtask) = find_subtask(job, tc) {
+                    subtask_tracker.record(subtask, score);
+                }
+                outcomes.push(TestcaseOutcome {
+                    subtask_id: tc.subtask_id,
+                    verdict: verdict.clone(),
+                    ratio: score,
+                });
 
-        if verdict != Verdict::Accepted && overall_verdict == Verdict::Accepted {
-            overall_verdict = verdict;
-            break;
+                // Partial credit keeps judging; any other failure decides the overall verdict
+                let failed = verdict != Verdict::Accepted && verdict != Verdict::Partial;
+                if failed && overall_verdict == Verdict::Accepted {
+                    overall_verdict = verdict;
+                    if !has_subtasks && !job.full_run && job.scoring == ScoringPolicy::Min {
+                        break 'judging;
+                    }
+                }
+            }
         }
+        Ok(())
+    }
+    .await;
+
+    let cleaned = cleanup_sessions(sessions).await;
+    judged?;
+    cleaned?;
+
+    let ratios: Vec<f64> = outcomes.iter().map(|o| o.ratio).collect();
+    if overall_verdict == Verdict::Accepted && ratios.iter().any(|r| *r < 1.0) {
+        overall_verdict = Verdict::Partial;
     }
 
     // Mark remaining testcases as skipped if early termination
@@ -304,11 +498,26 @@ pub async fn process_judge_job(
             execution_time: None,
             memory_used: None,
             output: None,
+            score: None,
         };
 
         testcase_results.push(tc_result);
     }
 
+    let subtask_results = score_subtasks(&job.subtasks, &outcomes);
+    let score = if has_subtasks {
+        subtask_results.iter().map(|r| r.score).sum()
+    } else {
+        // Without subtasks the whole problem is one group scored by the job's policy
+        match (job.scoring, &overall_verdict) {
+            (ScoringPolicy::Min, Verdict::Accepted | Verdict::Partial)
+            | (ScoringPolicy::Sum, _) => {
+                (job.max_score as f64 * job.scoring.aggregate(&ratios)).floor() as i64
+            }
+            (ScoringPolicy::Min, _) => 0,
+        }
+    };
+
     info!(
         "Job summary: submission_id={}, verdict={}, max_time_ms={}, max_memory_kb={}",
         job.submission_id,
@@ -317,32 +526,191 @@ pub async fn process_judge_job(
         max_memory
     );
 
-    let execution_time = if overall_verdict == Verdict::Accepted {
-        Some(max_time)
-    } else {
-        None
-    };
-    let memory_used = if overall_verdict == Verdict::Accepted {
-        Some(max_memory)
-    } else {
-        None
-    };
+    let completed = matches!(overall_verdict, Verdict::Accepted | Verdict::Partial);
+    let execution_time = if completed { Some(max_time) } else { None };
+    let memory_used = if completed { Some(max_memory) } else { None };
 
     Ok(JudgeResult {
         submission_id: job.submission_id,
         verdict: overall_verdict.to_string(),
         execution_time,
-        score: if overall_verdict == Verdict::Accepted {
-            job.max_score
-        } else {
-            0
-        },
+        score,
         memory_used,
         testcase_results,
+        subtask_results,
         error_message: None,
     })
 }
 
+/// Everything a single testcase run needs from its submission
+struct TestcaseContext<'a> {
+    job: &'a JudgeJob,
+    storage: &'a StorageClient,
+    lang_config: &'a LanguageConfig,
+    work_dir: &'a Path,
+    checker_binary: Option<&'a Path>,
+    interactor_binary: Option<&'a Path>,
+}
+
+/// Subtask a testcase belongs to, if any
+fn find_subtask<'a>(job: &'a JudgeJob, tc: &TestcaseInfo) -> Option<&'a SubtaskInfo> {
+    tc.subtask_id
+        .and_then(|id| job.subtasks.iter().find(|s| s.id == id))
+}
+
+/// Clean up every session, even after one of them fails; returns the first error
+async fn cleanup_sessions(sessions: Vec<SandboxSession>) -> anyhow::Result<()> {
+    let mut first_error = None;
+    for session in sessions {
+        if let Err(e) = session.cleanup().await {
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Run one testcase and judge its output.
+///
+/// `session` is the box of the testcase's parallel slot; interactive problems
+/// run without one since they set up their own boxes.
+async fn run_testcase(
+    ctx: &TestcaseContext<'_>,
+    tc: &TestcaseInfo,
+    session: Option<&SandboxSession>,
+) -> Result<(ExecutionOutcome, Verdict, f64)> {
+    let job = ctx.job;
+    let lang_config = ctx.lang_config;
+
+    let input_content = ctx
+        .storage
+        .download_testcase_string(&tc.input_path)
+        .await
+        .with_context(|| format!("Failed to download testcase input: {}", tc.input_path))?;
+
+    let expected_output = ctx
+        .storage
+        .download_testcase_string(&tc.output_path)
+        .await
+        .with_context(|| format!("Failed to download testcase output: {}", tc.output_path))?;
+
+    let adjusted_time_limit = if job.ignore_time_limit_bonus {
+        job.time_limit
+    } else {
+        lang_config.calculate_time_limit(job.time_limit)
+    };
+    let adjusted_memory_limit = if job.ignore_memory_limit_bonus {
+        job.memory_limit
+    } else {
+        lang_config.calculate_memory_limit(job.memory_limit)
+    };
+
+    // Run user's program in the submission's sandbox
+    let spec = ExecutionSpec::new(ctx.work_dir)
+        .with_command(&lang_config.run_command)
+        .with_seccomp(lang_config.seccomp)
+        .with_limits(ExecutionLimits {
+            time_ms: adjusted_time_limit,
+            memory_mb: adjusted_memory_limit,
+        })
+        .with_stdin(&input_content)
+        .with_output_limit_kb(job.output_limit.saturating_mul(1024));
+
+    let result = if let Some(interactor_path) = ctx.interactor_binary {
+        // Interactive: the interactor talks to the program and decides the verdict
+        let interactor_temp_dir = tempfile::tempdir()?;
+        let input_path = interactor_temp_dir.path().join("input.txt");
+        let answer_path = interactor_temp_dir.path().join("answer.txt");
+
+        tokio::fs::write(&input_path, &input_content).await?;
+        tokio::fs::write(&answer_path, &expected_output).await?;
+
+        let interactive_result = run_interactive(
+            &spec,
+            interactor_path,
+            &input_path,
+            &answer_path,
+            DEFAULT_CHECKER_TIMEOUT_SECS,
+        )
+        .await?;
+
+        (
+            interactive_result.run,
+            interactive_result.verdict,
+            interactive_result.score,
+        )
+    } else {
+        let session = session.context("Sandbox session is not initialized")?;
+        let started = Instant::now();
+        let run_result = session.run(&spec).await?;
+        metrics().observe(Timing::TestcaseRun, started.elapsed());
+        session.reset().await?;
+
+        // Determine verdict based on run status and problem type
+        let (verdict, score) = match run_result.status {
+            ExecutionStatus::Exited(0) => {
+                match check_output(
+                    ctx.checker_binary,
+                    job.compare_mode,
+                    &input_content,
+                    &run_result,
+                    &expected_output,
+                )
+                .await
+                {
+                    Ok(checked) => checked,
+                    Err(e) => {
+                        warn!("Checker failed for testcase {}: {:#}", tc.id, e);
+                        (Verdict::SystemError, 0.0)
+                    }
+                }
+            }
+            ref status => (status_to_verdict(status), 0.0),
+        };
+
+        (run_result, verdict, score)
+    };
+
+    Ok(result)
+}
+
+/// Judge the output of a run that exited normally: with the special judge
+/// checker if there is one, otherwise with the built-in comparison
+pub async fn check_output(
+    checker_binary: Option<&Path>,
+    compare_mode: CompareMode,
+    input: &str,
+    run_result: &ExecutionOutcome,
+    expected_output: &str,
+) -> Result<(Verdict, f64)> {
+    let Some(checker_path) = checker_binary else {
+        let accepted = compare_mode.compare(&run_result.stdout_bytes, expected_output.as_bytes());
+        return Ok(if accepted {
+            (Verdict::Accepted, 1.0)
+        } else {
+            (Verdict::WrongAnswer, 0.0)
+        });
+    };
+
+    let checker_temp_dir = tempfile::tempdir()?;
+    let input_path = checker_temp_dir.path().join("input.txt");
+    let output_path = checker_temp_dir.path().join("output.txt");
+    let answer_path = checker_temp_dir.path().join("answer.txt");
+
+    tokio::fs::write(&input_path, input).await?;
+    tokio::fs::write(&output_path, &run_result.stdout).await?;
+    tokio::fs::write(&answer_path, expected_output).await?;
+
+    let checker_result = crate::checker::run_checker(
+        checker_path,
+        &input_path,
+        &output_path,
+        &answer_path,
+        DEFAULT_CHECKER_TIMEOUT_SECS,
+    )
+    .await?;
+    Ok((checker_result.verdict, checker_result.score))
+}
+
 /// Compare program output with expected output
 pub fn compare_output(actual: &str, expected: &str) -> bool {
     // Normalize outputs: trim trailing whitespace from each line and trailing newlines
@@ -370,9 +738,71 @@ pub fn compare_output(actual: &str, expected: &str) -> bool {
     actual_lines == expected_lines
 }
 
+impl CompareMode {
+    /// Compare program output against the expected answer
+    pub fn compare(&self, actual: &[u8], expected: &[u8]) -> bool {
+        if *self == CompareMode::Exact {
+            return actual == expected;
+        }
+
+        let actual = String::from_utf8_lossy(actual);
+        let expected = String::from_utf8_lossy(expected);
+
+        match *self {
+            CompareMode::Lines => compare_output(&actual, &expected),
+            CompareMode::Tokens => actual.split_whitespace().eq(expected.split_whitespace()),
+            CompareMode::CaseInsensitive => {
+                compare_output(&actual.to_ascii_lowercase(), &expected.to_ascii_lowercase())
+            }
+            CompareMode::Float { abs_eps, rel_eps } => {
+                let mut actual_tokens = actual.split_whitespace();
+                let mut expected_tokens = expected.split_whitespace();
+                loop {
+                    match (actual_tokens.next(), expected_tokens.next()) {
+                        (None, None) => return true,
+                        (Some(a), Some(e)) if compare_float_token(a, e, abs_eps, rel_eps) => {}
+                        _ => return false,
+                    }
+                }
+            }
+            CompareMode::UnorderedLines => {
+                let sorted_lines = |s: &str| -> Vec<String> {
+                    let mut lines: Vec<String> =
+                        s.lines().map(|line| line.trim_end().to_string()).collect();
+                    while lines.last().map(|s| s.is_empty()).unwrap_or(false) {
+                        lines.pop();
+                    }
+                    lines.sort();
+                    lines
+                };
+                sorted_lines(&actual) == sorted_lines(&expected)
+            }
+            CompareMode::Exact => unreachable!(),
+        }
+    }
+}
+
+/// Compare a single token, as numbers if the expected token is one
+fn compare_float_token(actual: &str, expected: &str, abs_eps: f64, rel_eps: f64) -> bool {
+    if actual == expected {
+        return true;
+    }
+    match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(a), Ok(e)) if a.is_finite() && e.is_finite() => {
+            let diff = (a - e).abs();
+            diff <= abs_eps || diff <= rel_eps * e.abs()
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sandbox::mock::{MockCall, MockRun, MockSandbox};
+    use crate::sandbox::{with_provider, SeccompProfile};
+    use crate::storage::LocalStorage;
+    use std::sync::Arc;
 
     #[test]
     fn test_compare_output_exact_match() {
@@ -394,9 +824,278 @@ mod tests {
         assert!(!compare_output("hello\nworld\n", "hello\nearth\n"));
     }
 
+    #[test]
+    fn test_compare_mode_tokens() {
+        let mode = CompareMode::Tokens;
+        assert!(mode.compare(b"1  2\n3\n\n", b"1 2 3"));
+        assert!(!mode.compare(b"1 2", b"1 2 3"));
+        assert!(!mode.compare(b"12 3", b"1 2 3"));
+    }
+
+    #[test]
+    fn test_compare_mode_case_insensitive() {
+        let mode = CompareMode::CaseInsensitive;
+        assert!(mode.compare(b"YES\nno  \n", b"yes\nNo\n"));
+        assert!(!mode.compare(b"yes no\n", b"yes\nno\n"));
+    }
+
+    #[test]
+    fn test_compare_mode_float() {
+        let mode = CompareMode::Float {
+            abs_eps: 1e-6,
+            rel_eps: 1e-6,
+        };
+        assert!(mode.compare(b"0.3333333\n", b"0.333333333"));
+        assert!(mode.compare(b"1000000.5 x", b"1000000 x"));
+        assert!(!mode.compare(b"0.334", b"0.333333333"));
+        assert!(!mode.compare(b"1.0 y", b"1.0 x"));
+        assert!(!mode.compare(b"nan", b"0"));
+        assert!(!mode.compare(b"1.0", b"1.0 2.0"));
+    }
+
+    #[test]
+    fn test_compare_mode_float_defaults() {
+        let mode: CompareMode = serde_json::from_str(r#"{"mode":"float"}"#).unwrap();
+        assert_eq!(
+            mode,
+            CompareMode::Float {
+                abs_eps: 1e-6,
+                rel_eps: 1e-6
+            }
+        );
+    }
+
+    #[test]
+    fn test_compare_mode_unordered_lines() {
+        let mode = CompareMode::UnorderedLines;
+        assert!(mode.compare(b"b\na \nc\n\n", b"a\nb\nc\n"));
+        assert!(!mode.compare(b"a\na\nb\n", b"a\nb\nb\n"));
+    }
+
+    #[test]
+    fn test_compare_mode_exact() {
+        let mode = CompareMode::Exact;
+        assert!(mode.compare(b"\x00\x01\n", b"\x00\x01\n"));
+        assert!(!mode.compare(b"hello\n", b"hello"));
+    }
+
     #[test]
     fn test_problem_type_default() {
         let pt: ProblemType = Default::default();
         assert_eq!(pt, ProblemType::Icpc);
     }
+
+    /// Sums the numbers on stdin, like a correct A+B submission would
+    fn sum_stdin(call: &MockCall) -> MockRun {
+        let sum: i64 = call
+            .stdin
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|n| n.parse::<i64>().unwrap())
+            .sum();
+        MockRun::stdout(format!("{}\n", sum)).with_time(5)
+    }
+
+    /// A+B in C++ with two testcases stored under `dir`
+    fn a_plus_b_job(dir: &Path) -> JudgeJob {
+        let cases = [("1 2\n", "3\n"), ("5 7\n", "12\n")];
+        let testcases = cases
+            .iter()
+            .enumerate()
+            .map(|(i, (input, output))| {
+                std::fs::write(dir.join(format!("{}.in", i)), input).unwrap();
+                std::fs::write(dir.join(format!("{}.out", i)), output).unwrap();
+                TestcaseInfo {
+                    id: i as i64 + 1,
+                    input_path: format!("{}.in", i),
+                    output_path: format!("{}.out", i),
+                    subtask_id: None,
+                }
+            })
+            .collect();
+
+        JudgeJob {
+            submission_id: 1,
+            problem_id: 1,
+            code: "int main() {}".to_string(),
+            language: "cpp".to_string(),
+            time_limit: 1000,
+            ignore_time_limit_bonus: true,
+            memory_limit: 256,
+            ignore_memory_limit_bonus: true,
+            max_score: 100,
+            testcases,
+            problem_type: ProblemType::Icpc,
+            checker_path: None,
+            interactor_path: None,
+            subtasks: Vec::new(),
+            full_run: false,
+            scoring: ScoringPolicy::default(),
+            compare_mode: CompareMode::default(),
+            output_limit: default_output_limit(),
+        }
+    }
+
+    async fn judge_with(
+        sandbox: Arc<MockSandbox>,
+        job: &JudgeJob,
+        dir: &Path,
+    ) -> (JudgeResult, Vec<(ProgressSubject, ProgressEvent)>) {
+        let _ = languages::init_languages();
+        let storage = StorageClient::new(LocalStorage::new(dir));
+        let mut events = Vec::new();
+        let result = with_provider(
+            sandbox,
+            process_judge_job(
+                job,
+                &storage,
+                &CheckerManager::new(),
+                &InteractorManager::new(),
+                &mut events,
+            ),
+        )
+        .await
+        .unwrap();
+        (result, events)
+    }
+
+    #[tokio::test]
+    async fn test_judge_accepted_with_mock_sandbox() {
+        let dir = tempfile::tempdir().unwrap();
+        let job = a_plus_b_job(dir.path());
+        let sandbox = Arc::new(MockSandbox::new(|call| match call.program() {
+            "./Main" => sum_stdin(call),
+            _ => MockRun::exited(0),
+        }));
+
+        let (result, events) = judge_with(sandbox, &job, dir.path()).await;
+
+        assert_eq!(result.verdict, "accepted");
+        assert_eq!(result.score, 100);
+        assert_eq!(result.testcase_results.len(), 2);
+        assert_eq!(result.execution_time, Some(5));
+        assert_eq!(
+            events.first().map(|(_, e)| e),
+            Some(&ProgressEvent::CompileStart)
+        );
+        assert!(events
+            .iter()
+            .any(|(_, e)| *e == ProgressEvent::CompileDone { success: true }));
+    }
+
+    #[tokio::test]
+    async fn test_judge_time_limit_stops_at_first_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let job = a_plus_b_job(dir.path());
+        let sandbox = Arc::new(MockSandbox::new(|call| match call.program() {
+            "./Main" if call.stdin.as_deref() == Some("1 2\n") => sum_stdin(call).with_time(3000),
+            "./Main" => sum_stdin(call),
+            _ => MockRun::exited(0),
+        }));
+
+        let (result, _) = judge_with(sandbox, &job, dir.path()).await;
+
+        assert_eq!(result.verdict, "time_limit_exceeded");
+        assert_eq!(result.score, 0);
+        assert_eq!(result.testcase_results[1].verdict, "skipped");
+    }
+
+    #[tokio::test]
+    async fn test_judge_full_run_judges_past_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut job = a_plus_b_job(dir.path());
+        std::fs::write(dir.path().join("2.in"), "10 20\n").unwrap();
+        std::fs::write(dir.path().join("2.out"), "30\n").unwrap();
+        job.testcases.push(TestcaseInfo {
+            id: 3,
+            input_path: "2.in".to_string(),
+            output_path: "2.out".to_string(),
+            subtask_id: None,
+        });
+        job.full_run = true;
+        let sandbox = Arc::new(MockSandbox::new(|call| match call.program() {
+            "./Main" if call.stdin.as_deref() == Some("5 7\n") => {
+                MockRun::stdout("13\n").with_time(7)
+            }
+            "./Main" if call.stdin.as_deref() == Some("10 20\n") => sum_stdin(call).with_time(3000),
+            "./Main" => sum_stdin(call),
+            _ => MockRun::exited(0),
+        }));
+
+        let (result, _) = judge_with(sandbox, &job, dir.path()).await;
+
+        assert_eq!(result.verdict, "wrong_answer");
+        assert_eq!(result.score, 0);
+        let verdicts: Vec<&str> = result
+            .testcase_results
+            .iter()
+            .map(|r| r.verdict.as_str())
+            .collect();
+        assert_eq!(
+            verdicts,
+            ["accepted", "wrong_answer", "time_limit_exceeded"]
+        );
+        assert!(result
+            .testcase_results
+            .iter()
+            .all(|r| r.execution_time.is_some() && r.memory_used.is_some()));
+        assert_eq!(result.testcase_results[1].execution_time, Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_judge_sum_scoring_averages_testcases() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut job = a_plus_b_job(dir.path());
+        job.scoring = ScoringPolicy::Sum;
+        let sandbox = Arc::new(MockSandbox::new(|call| match call.program() {
+            "./Main" if call.stdin.as_deref() == Some("1 2\n") => MockRun::stdout("4\n"),
+            "./Main" => sum_stdin(call),
+            _ => MockRun::exited(0),
+        }));
+
+        let (result, _) = judge_with(sandbox, &job, dir.path()).await;
+
+        assert_eq!(result.verdict, "wrong_answer");
+        assert_eq!(result.score, 50);
+        assert_eq!(result.testcase_results[1].verdict, "accepted");
+    }
+
+    #[tokio::test]
+    async fn test_judge_seccomp_kill_is_restricted_function() {
+        let dir = tempfile::tempdir().unwrap();
+        let job = a_plus_b_job(dir.path());
+        let sandbox = Arc::new(MockSandbox::new(|call| match call.program() {
+            "./Main" if call.limits.seccomp == SeccompProfile::Strict => {
+                MockRun::signaled(libc::SIGSYS)
+            }
+            "./Main" => sum_stdin(call),
+            _ => MockRun::exited(0),
+        }));
+
+        let (result, _) = judge_with(sandbox.clone(), &job, dir.path()).await;
+
+        assert_eq!(result.verdict, "restricted_function");
+        assert!(sandbox
+            .calls()
+            .iter()
+            .filter(|call| call.program() == "g++")
+            .all(|call| call.limits.seccomp == SeccompProfile::Base));
+    }
+
+    #[tokio::test]
+    async fn test_judge_compile_error_runs_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let job = a_plus_b_job(dir.path());
+        let sandbox = Arc::new(MockSandbox::new(|call| match call.program() {
+            "g++" => MockRun::exited(1).with_stderr("Main.cpp:1:1: error: expected ';'"),
+            _ => panic!("nothing should run after a compile error"),
+        }));
+
+        let (result, _) = judge_with(sandbox.clone(), &job, dir.path()).await;
+
+        assert_eq!(result.verdict, "compile_error");
+        assert!(result.error_message.unwrap().contains("expected ';'"));
+        assert_eq!(sandbox.calls().len(), 1);
+    }
 }
@@ -8,6 +8,8 @@ use std::sync::OnceLock;
 use anyhow::Context;
 use serde::Deserialize;
 
+use crate::sandbox::SeccompProfile;
+
 /// Configuration for a supported programming language
 #[derive(Debug, Clone)]
 pub struct LanguageConfig {
@@ -25,6 +27,8 @@ pub struct LanguageConfig {
     /// actual_memory = base_memory * multiplier + bonus
     /// Example: (2, 32) means base_memory * 2 + 32 MB
     pub memory_limit: Option<(u32, u32)>,
+    /// Syscall filter the program runs under
+    pub seccomp: SeccompProfile,
 }
 
 impl LanguageConfig {
@@ -67,6 +71,8 @@ struct RawLanguageConfig {
     memory_limit: Vec<String>,
     #[serde(default)]
     aliases: Vec<String>,
+    #[serde(default)]
+    seccomp: SeccompProfile,
 }
 
 /// Global language configurations
@@ -103,6 +109,7 @@ pub fn init_languages() -> anyhow::Result<()> {
             run_command: into_command(&raw.run_command),
             time_limit: parse_limit(raw.time_limit, "time")?,
             memory_limit: parse_limit(raw.memory_limit, "memory")?,
+            seccomp: raw.seccomp,
         };
 
         // Add main language name
@@ -153,6 +160,7 @@ mod tests {
 source_file = "main.c"
 compile_command = "gcc -o main main.c"
 run_command = "./main"
+seccomp = "strict"
 
 [python]
 source_file = "main.py"
@@ -175,5 +183,7 @@ aliases = ["py", "python3"]
         assert!(raw_configs.contains_key("c"));
         assert!(raw_configs.contains_key("python"));
         assert_eq!(raw_configs["python"].aliases, vec!["py", "python3"]);
+        assert_eq!(raw_configs["c"].seccomp, SeccompProfile::Strict);
+        assert_eq!(raw_configs["python"].seccomp, SeccompProfile::Base);
     }
 }
@@ -0,0 +1,86 @@
+//! ANA Online Judge worker library
+//!
+//! The `judge` binary runs these modules as a Redis-driven worker; the
+//! `judge-local` binary reuses the same compile, run and check pipeline to
+//! judge a problem directory on the local machine.
+
+pub mod anigma;
+pub mod cancel;
+pub mod checker;
+pub mod compiler;
+pub mod error;
+pub mod executer;
+pub mod interactor;
+pub mod judger;
+pub mod languages;
+pub mod metrics;
+pub mod playground;
+pub mod progress;
+pub mod redis_manager;
+pub mod sandbox;
+pub mod shutdown;
+pub mod storage;
+pub mod subtask;
+pub mod utils;
+pub mod validator;
+pub mod worker_status;
+
+use serde::{Deserialize, Serialize};
+
+use crate::anigma::{AnigmaJudgeJob, AnigmaTask1JudgeJob};
+use crate::judger::JudgeJob;
+use crate::playground::PlaygroundJob;
+use crate::validator::ValidateJob;
+
+/// Worker job enum - represents different types of jobs the worker can process
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "job_type")]
+pub enum WorkerJob {
+    /// Judge a user submission
+    #[serde(rename = "judge")]
+    Judge(JudgeJob),
+    /// Validate testcases
+    #[serde(rename = "validate")]
+    Validate(ValidateJob),
+    /// Anigma Task 2 Judge Job (ZIP 제출)
+    #[serde(rename = "anigma")]
+    Anigma(AnigmaJudgeJob),
+    /// Anigma Task 1 Judge Job (input 파일 제출)
+    #[serde(rename = "anigma_task1")]
+    AnigmaTask1(AnigmaTask1JudgeJob),
+    /// Playground execution job
+    #[serde(rename = "playground")]
+    Playground(PlaygroundJob),
+}
+
+impl WorkerJob {
+    /// Job type as it appears in the `job_type` tag
+    pub fn job_type(&self) -> &'static str {
+        match self {
+            WorkerJob::Judge(_) => "judge",
+            WorkerJob::Validate(_) => "validate",
+            WorkerJob::Anigma(_) => "anigma",
+            WorkerJob::AnigmaTask1(_) => "anigma_task1",
+            WorkerJob::Playground(_) => "playground",
+        }
+    }
+
+    /// Short description of what the job works on, e.g. `submission:42`
+    pub fn subject(&self) -> String {
+        match self {
+            WorkerJob::Validate(job) => format!("problem:{}", job.problem_id),
+            WorkerJob::Playground(job) => format!("playground:{}", job.session_id),
+            _ => format!("submission:{}", self.submission_id().unwrap_or_default()),
+        }
+    }
+
+    /// Submission judged by this job, if it can be cancelled
+    pub fn submission_id(&self) -> Option<i64> {
+        match self {
+            WorkerJob::Judge(job) => Some(job.submission_id),
+            WorkerJob::Anigma(job) => Some(job.submission_id),
+            WorkerJob::AnigmaTask1(job) => Some(job.submission_id),
+            WorkerJob::Validate(_) | WorkerJob::Playground(_) => None,
+        }
+    }
+}
@@ -1,50 +1,60 @@
-mod anigma;
-mod checker;
-mod compiler;
-mod executer;
-mod judger;
-mod languages;
-mod playground;
-mod redis_manager;
-mod sandbox;
-mod storage;
-mod utils;
-mod validator;
-
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
-use storage::StorageClient;
-use tracing::{error, info};
-
-use crate::anigma::{
-    process_anigma_job, process_anigma_task1_job, AnigmaJudgeJob, AnigmaJudgeResult,
-    AnigmaTask1JudgeJob,
-};
-use crate::checker::{CheckerManager, Verdict};
-use crate::judger::{process_judge_job, JudgeJob, JudgeResult};
-use crate::playground::{process_playground_job, PlaygroundJob, PlaygroundResult};
-use crate::redis_manager::RedisManager;
-use crate::validator::{process_validate_job, ValidateJob, ValidateResult, ValidatorManager};
-
-/// Worker job enum - represents different types of jobs the worker can process
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "job_type")]
-pub enum WorkerJob {
-    /// Judge a user submission
-    #[serde(rename = "judge")]
-    Judge(JudgeJob),
-    /// Validate testcases
-    #[serde(rename = "validate")]
-    Validate(ValidateJob),
-    /// Anigma Task 2 Judge Job (ZIP 제출)
-    #[serde(rename = "anigma")]
-    Anigma(AnigmaJudgeJob),
-    /// Anigma Task 1 Judge Job (input 파일 제출)
-    #[serde(rename = "anigma_task1")]
-    AnigmaTask1(AnigmaTask1JudgeJob),
-    /// Playground execution job
-    #[serde(rename = "playground")]
-    Playground(PlaygroundJob),
+use std::future::Future;
+use tracing::{error, info, warn};
+
+use judge::anigma::{process_anigma_job, process_anigma_task1_job, AnigmaJudgeResult};
+use judge::cancel::unless_cancelled;
+use judge::checker::{CheckerManager, Verdict};
+use judge::error::is_transient;
+use judge::interactor::InteractorManager;
+use judge::judger::{process_judge_job, JudgeResult};
+use judge::languages;
+use judge::metrics::{self, metrics};
+use judge::playground::{process_playground_job, PlaygroundResult};
+use judge::progress::{ProgressEvent, ProgressSubject};
+use judge::redis_manager::{RedisManager, WorkerIdentity};
+use judge::sandbox;
+use judge::shutdown::Shutdown;
+use judge::storage::StorageClient;
+use judge::validator::{process_validate_job, ValidateResult, ValidatorManager};
+use judge::WorkerJob;
+
+/// How the worker loop stopped waiting for a job
+enum JobEnd {
+    Finished,
+    Abandoned,
+    Cancelled,
+    /// The worker lease was lost, so another worker may take the job over
+    LeaseLost,
+}
+
+/// Acknowledge the current job once its result is stored, otherwise put it
+/// back in the queue so another attempt can store a result
+async fn settle_job(redis: &mut RedisManager, stored: bool) {
+    let settled = if stored {
+        redis.ack_job().await
+    } else {
+        redis.requeue_job().await
+    };
+    if let Err(e) = settled {
+        error!("Failed to settle job: {:#}", e);
+    }
+}
+
+/// Retry the current job after a transient failure if retries are left; the
+/// caller only asks for failures [`is_transient`] accepts. Returns `true` if
+/// the job was requeued, in which case its result must not be stored.
+async fn retry_on_system_error(redis: &mut RedisManager, system_error: Option<&str>) -> bool {
+    let Some(error) = system_error else {
+        return false;
+    };
+    match redis.retry_failed_job(error).await {
+        Ok(retried) => retried,
+        Err(e) => {
+            error!("Failed to retry job: {:#}", e);
+            false
+        }
+    }
 }
 
 #[tokio::main]
@@ -60,168 +70,382 @@ async fn main() -> Result<()> {
     languages::init_languages()?;
     info!("Loaded language configurations");
 
+    let layout = sandbox::WorkerLayout::from_env()?;
+
     // Initialize Redis manager (connects, allocates worker_id, starts heartbeat)
-    let mut redis = RedisManager::from_env().await?;
+    let mut redis = RedisManager::from_env(layout.max_workers).await?;
     let worker_id = redis.worker_id();
+    let identity = redis.identity().clone();
 
     // Initialize sandbox configuration with dynamic worker_id
-    sandbox::init_config(worker_id)?;
+    sandbox::init_config(worker_id, &layout)?;
+    redis
+        .set_cgroups_status(sandbox::is_cgroups_available().await)
+        .await;
     sandbox::ensure_cgroups_available().await?;
 
+    metrics::serve_from_env(redis.client().clone()).await?;
+
     let storage = StorageClient::from_env().await?;
-    info!("Connected to MinIO storage");
+    info!("Storage client initialized");
 
     let checker_manager = CheckerManager::new();
     info!("Checker manager initialized");
+    let interactor_manager = InteractorManager::new();
+    info!("Interactor manager initialized");
     let validator_manager = ValidatorManager::new();
     info!("Validator manager initialized");
 
+    let mut shutdown = Shutdown::listen()?;
+
     info!("Waiting for jobs...");
 
     loop {
-        let job = redis.pop_job().await?;
+        let lease_lost = redis.lease_lost();
+        let job = tokio::select! {
+            job = redis.pop_job() => job?,
+            _ = shutdown.requested() => break,
+            _ = lease_lost => return stop_after_lost_lease(&identity).await,
+        };
 
-        match job {
-            WorkerJob::Judge(job) => {
-                info!(
-                    "Received judge job: submission_id={}, language={}",
-                    job.submission_id, job.language
-                );
+        redis.begin_job_status(job.job_type(), job.subject()).await;
 
-                let result =
-                    match process_judge_job(&job, &storage, &checker_manager, &mut redis).await {
-                        Ok(result) => result,
-                        Err(e) => {
-                            error!("Failed to process judge job {}: {}", job.submission_id, e);
-                            JudgeResult::system_error(job.submission_id, format!("{:#}", e))
-                        }
-                    };
-
-                if let Err(e) = redis.store_judge_result(&result).await {
-                    error!("Failed to store judge result: {}", e);
+        let submission_id = job.submission_id();
+        let cancellation = submission_id.map(|id| redis.cancellation(id));
+        let cancelled = async {
+            match cancellation {
+                Some(cancellation) => cancellation.await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let lease_lost = redis.lease_lost();
+        let end = tokio::select! {
+            end = handle_job(
+                &job,
+                &mut redis,
+                &storage,
+                &checker_manager,
+                &interactor_manager,
+                &validator_manager,
+                cancelled,
+            ) => end,
+            _ = shutdown.abandon_job() => JobEnd::Abandoned,
+            _ = lease_lost => JobEnd::LeaseLost,
+        };
+
+        match end {
+            JobEnd::Finished => {}
+            JobEnd::Abandoned => {
+                warn!("Abandoned the current job, requeueing it");
+                if let Err(e) = redis.requeue_job().await {
+                    error!("Failed to requeue job: {:#}", e);
                 }
+            }
+            JobEnd::Cancelled => store_cancelled_result(&job, &mut redis).await,
+            JobEnd::LeaseLost => return stop_after_lost_lease(&identity).await,
+        }
+        redis.finish_job_status().await;
 
-                info!(
-                    "Judge job completed: submission_id={}, verdict={}",
-                    result.submission_id, result.verdict
-                );
+        if shutdown.is_requested() {
+            break;
+        }
+    }
+
+    info!("Shutting down worker {}", identity);
+    sandbox::cleanup_active_boxes().await;
+    redis.shutdown().await?;
+    info!("Worker {} stopped", identity);
+
+    Ok(())
+}
+
+/// Stop a worker whose lease was lost without touching its Redis state,
+/// which may belong to the worker holding the lease now
+async fn stop_after_lost_lease(identity: &WorkerIdentity) -> Result<()> {
+    sandbox::cleanup_active_boxes().await;
+    anyhow::bail!(
+        "Worker {} lost its lease; stopping so its jobs are judged only once",
+        identity
+    )
+}
+
+/// Publish the final progress event of a submission
+async fn publish_done(redis: &mut RedisManager, submission_id: i64, verdict: &str, score: i64) {
+    let event = ProgressEvent::JudgeDone {
+        verdict: verdict.to_string(),
+        score: Some(score),
+    };
+    let subject = ProgressSubject::SubmissionId(submission_id);
+    let _ = redis.publish_progress(subject, event).await;
+}
+
+/// Store a `Cancelled` result for a cancelled job and acknowledge it
+async fn store_cancelled_result(job: &WorkerJob, redis: &mut RedisManager) {
+    let Some(submission_id) = job.submission_id() else {
+        return;
+    };
+    info!("Judging of submission {} was cancelled", submission_id);
+    metrics().record_job(job.job_type(), &Verdict::Cancelled.to_string());
+    publish_done(redis, submission_id, &Verdict::Cancelled.to_string(), 0).await;
+
+    let stored = match job {
+        WorkerJob::Anigma(_) => {
+            redis
+                .store_anigma_result(&AnigmaJudgeResult::cancelled(submission_id))
+                .await
+        }
+        _ => {
+            redis
+                .store_judge_result(&JudgeResult::cancelled(submission_id))
+                .await
+        }
+    };
+    if let Err(ref e) = stored {
+        error!("Failed to store cancelled result: {}", e);
+    }
+    settle_job(redis, stored.is_ok()).await;
+
+    if let Err(e) = redis.clear_cancellation(submission_id).await {
+        warn!("Failed to clear cancel request: {:#}", e);
+    }
+}
+
+/// Process one job, store its result and acknowledge it.
+///
+/// A cancel request (`cancelled` resolving) only stops the job while it is
+/// processed; once a result exists it is stored and acknowledged as is.
+async fn handle_job(
+    job: &WorkerJob,
+    redis: &mut RedisManager,
+    storage: &StorageClient,
+    checker_manager: &CheckerManager,
+    interactor_manager: &InteractorManager,
+    validator_manager: &ValidatorManager,
+    cancelled: impl Future<Output = ()>,
+) -> JobEnd {
+    let job_type = job.job_type();
+    match job {
+        WorkerJob::Judge(job) => {
+            info!(
+                "Received judge job: submission_id={}, language={}",
+                job.submission_id, job.language
+            );
+
+            let processing =
+                process_judge_job(job, storage, checker_manager, interactor_manager, redis);
+            let Some(processed) = unless_cancelled(processing, cancelled).await else {
+                return JobEnd::Cancelled;
+            };
+            let transient = processed.as_ref().is_err_and(is_transient);
+            let result = match processed {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Failed to process judge job {}: {}", job.submission_id, e);
+                    JudgeResult::system_error(job.submission_id, format!("{:#}", e))
+                }
+            };
+
+            if transient && retry_on_system_error(redis, result.system_error_message()).await {
+                return JobEnd::Finished;
             }
-            WorkerJob::Validate(job) => {
-                info!(
-                    "Received validate job: problem_id={}, testcases={}",
-                    job.problem_id,
-                    job.testcase_inputs.len()
-                );
 
-                let result = match process_validate_job(&job, &storage, &validator_manager).await {
-                    Ok(result) => result,
-                    Err(e) => {
-                        error!(
-                            "Failed to process validate job for problem {}: {}",
-                            job.problem_id, e
-                        );
-                        ValidateResult::failed(job.problem_id, format!("{:#}", e))
-                    }
-                };
+            publish_done(redis, result.submission_id, &result.verdict, result.score).await;
+
+            let stored = redis.store_judge_result(&result).await;
+            if let Err(ref e) = stored {
+                error!("Failed to store judge result: {}", e);
+            }
+            settle_job(redis, stored.is_ok()).await;
+            metrics().record_job(job_type, &result.verdict);
+
+            info!(
+                "Judge job completed: submission_id={}, verdict={}",
+                result.submission_id, result.verdict
+            );
+        }
+        WorkerJob::Validate(job) => {
+            info!(
+                "Received validate job: problem_id={}, testcases={}",
+                job.problem_id,
+                job.testcase_inputs.len()
+            );
 
-                if let Err(e) = redis.store_validate_result(&result).await {
+            let mut system_error = None;
+            let processing = process_validate_job(job, storage, validator_manager, redis);
+            let Some(processed) = unless_cancelled(processing, cancelled).await else {
+                return JobEnd::Cancelled;
+            };
+            let transient = processed.as_ref().is_err_and(is_transient);
+            let result = match processed {
+                Ok(result) => result,
+                Err(e) => {
                     error!(
-                        "Failed to store validation result for problem {}: {}",
-                        result.problem_id, e
+                        "Failed to process validate job for problem {}: {}",
+                        job.problem_id, e
                     );
+                    system_error = Some(format!("{:#}", e));
+                    ValidateResult::failed(job.problem_id, format!("{:#}", e))
                 }
+            };
 
-                info!(
-                    "Validate job completed: problem_id={}, success={}",
-                    result.problem_id, result.success
-                );
+            if transient && retry_on_system_error(redis, system_error.as_deref()).await {
+                return JobEnd::Finished;
             }
-            WorkerJob::Anigma(job) => {
-                info!(
-                    "Received anigma task2 job: submission_id={}, problem_id={}",
-                    job.submission_id, job.problem_id
+
+            let verdict = if result.success { "valid" } else { "invalid" };
+            let event = ProgressEvent::JudgeDone {
+                verdict: verdict.into(),
+                score: None,
+            };
+            let subject = ProgressSubject::ProblemId(result.problem_id);
+            let _ = redis.publish_progress(subject, event).await;
+
+            let stored = redis.store_validate_result(&result).await;
+            if let Err(ref e) = stored {
+                error!(
+                    "Failed to store validation result for problem {}: {}",
+                    result.problem_id, e
                 );
+            }
+            settle_job(redis, stored.is_ok()).await;
+            metrics().record_job(job_type, verdict);
 
-                let result = match process_anigma_job(&job, &storage).await {
-                    Ok(result) => result,
-                    Err(e) => {
-                        error!("Failed to process anigma job {}: {}", job.submission_id, e);
-                        AnigmaJudgeResult::system_error(job.submission_id, format!("{:#}", e))
-                    }
-                };
+            info!(
+                "Validate job completed: problem_id={}, success={}",
+                result.problem_id, result.success
+            );
+        }
+        WorkerJob::Anigma(job) => {
+            info!(
+                "Received anigma task2 job: submission_id={}, problem_id={}",
+                job.submission_id, job.problem_id
+            );
 
-                if let Err(e) = redis.store_anigma_result(&result).await {
-                    error!("Failed to store anigma result: {}", e);
+            let processing = process_anigma_job(job, storage, redis);
+            let Some(processed) = unless_cancelled(processing, cancelled).await else {
+                return JobEnd::Cancelled;
+            };
+            let transient = processed.as_ref().is_err_and(is_transient);
+            let result = match processed {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Failed to process anigma job {}: {}", job.submission_id, e);
+                    AnigmaJudgeResult::system_error(job.submission_id, format!("{:#}", e))
                 }
+            };
 
-                info!(
-                    "Anigma task2 job completed: submission_id={}, verdict={}",
-                    result.base.submission_id, result.base.verdict
-                );
+            if transient && retry_on_system_error(redis, result.base.system_error_message()).await {
+                return JobEnd::Finished;
             }
-            WorkerJob::AnigmaTask1(job) => {
-                info!(
-                    "Received anigma task1 job: submission_id={}, problem_id={}",
-                    job.submission_id, job.problem_id
-                );
 
-                let result = match process_anigma_task1_job(&job, &storage).await {
-                    Ok(result) => result,
-                    Err(e) => {
-                        error!(
-                            "Failed to process anigma task1 job {}: {}",
-                            job.submission_id, e
-                        );
-                        JudgeResult::system_error(job.submission_id, format!("{:#}", e))
-                    }
-                };
+            let base = &result.base;
+            publish_done(redis, base.submission_id, &base.verdict, base.score).await;
 
-                if let Err(e) = redis.store_judge_result(&result).await {
-                    error!("Failed to store anigma task1 result: {}", e);
+            let stored = redis.store_anigma_result(&result).await;
+            if let Err(ref e) = stored {
+                error!("Failed to store anigma result: {}", e);
+            }
+            settle_job(redis, stored.is_ok()).await;
+            metrics().record_job(job_type, &result.base.verdict);
+
+            info!(
+                "Anigma task2 job completed: submission_id={}, verdict={}",
+                result.base.submission_id, result.base.verdict
+            );
+        }
+        WorkerJob::AnigmaTask1(job) => {
+            info!(
+                "Received anigma task1 job: submission_id={}, problem_id={}",
+                job.submission_id, job.problem_id
+            );
+
+            let processing = process_anigma_task1_job(job, storage, redis);
+            let Some(processed) = unless_cancelled(processing, cancelled).await else {
+                return JobEnd::Cancelled;
+            };
+            let transient = processed.as_ref().is_err_and(is_transient);
+            let result = match processed {
+                Ok(result) => result,
+                Err(e) => {
+                    error!(
+                        "Failed to process anigma task1 job {}: {}",
+                        job.submission_id, e
+                    );
+                    JudgeResult::system_error(job.submission_id, format!("{:#}", e))
                 }
+            };
 
-                info!(
-                    "Anigma task1 job completed: submission_id={}, verdict={}",
-                    result.submission_id, result.verdict
-                );
+            if transient && retry_on_system_error(redis, result.system_error_message()).await {
+                return JobEnd::Finished;
             }
-            WorkerJob::Playground(job) => {
-                info!(
-                    "Received playground job: session_id={}, target={}",
-                    job.session_id, job.target_path
-                );
 
-                let result = match process_playground_job(&job).await {
-                    Ok(result) => result,
-                    Err(e) => {
-                        error!("Failed to process playground job {}: {}", job.session_id, e);
-                        PlaygroundResult {
-                            session_id: job.session_id.clone(),
-                            success: false,
-                            stdout: String::new(),
-                            stderr: format!("Internal server error: {:#}", e),
-                            exit_code: 1,
-                            time_ms: 0,
-                            memory_kb: 0,
-                            compile_output: None,
-                            created_files: vec![],
-                        }
+            publish_done(redis, result.submission_id, &result.verdict, result.score).await;
+
+            let stored = redis.store_judge_result(&result).await;
+            if let Err(ref e) = stored {
+                error!("Failed to store anigma task1 result: {}", e);
+            }
+            settle_job(redis, stored.is_ok()).await;
+            metrics().record_job(job_type, &result.verdict);
+
+            info!(
+                "Anigma task1 job completed: submission_id={}, verdict={}",
+                result.submission_id, result.verdict
+            );
+        }
+        WorkerJob::Playground(job) => {
+            info!(
+                "Received playground job: session_id={}, target={}",
+                job.session_id, job.target_path
+            );
+
+            let mut system_error = None;
+            let processing = process_playground_job(job);
+            let Some(processed) = unless_cancelled(processing, cancelled).await else {
+                return JobEnd::Cancelled;
+            };
+            let transient = processed.as_ref().is_err_and(is_transient);
+            let result = match processed {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Failed to process playground job {}: {}", job.session_id, e);
+                    system_error = Some(format!("{:#}", e));
+                    PlaygroundResult {
+                        session_id: job.session_id.clone(),
+                        success: false,
+                        stdout: String::new(),
+                        stderr: format!("Internal server error: {:#}", e),
+                        exit_code: 1,
+                        time_ms: 0,
+                        memory_kb: 0,
+                        compile_output: None,
+                        created_files: vec![],
                     }
-                };
-
-                // Store result using the key provided in the job
-                if let Err(e) = redis
-                    .store_playground_result(&job.result_key, &result)
-                    .await
-                {
-                    error!("Failed to store playground result: {}", e);
                 }
+            };
 
-                info!(
-                    "Playground job completed: session_id={}, success={}",
-                    result.session_id, result.success
-                );
+            if transient && retry_on_system_error(redis, system_error.as_deref()).await {
+                return JobEnd::Finished;
             }
+
+            // Store result using the key provided in the job
+            let stored = redis
+                .store_playground_result(&job.result_key, &result)
+                .await;
+            if let Err(ref e) = stored {
+                error!("Failed to store playground result: {}", e);
+            }
+            settle_job(redis, stored.is_ok()).await;
+            let verdict = if result.success { "success" } else { "failure" };
+            metrics().record_job(job_type, verdict);
+
+            info!(
+                "Playground job completed: session_id={}, success={}",
+                result.session_id, result.success
+            );
         }
     }
+
+    JobEnd::Finished
 }
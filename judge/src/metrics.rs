@@ -0,0 +1,291 @@
+//! Prometheus metrics
+//!
+//! Counters and histograms are recorded in a process-wide registry. When
+//! `JUDGE_METRICS_ADDR` is set, a small HTTP listener serves them on
+//! `/metrics`, together with `/healthz` (the process is up) and `/readyz`
+//! (Redis answers and isolate's cgroup support works).
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+use crate::sandbox;
+
+/// Bucket bounds (seconds) for sandbox and storage latencies
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// How long `/readyz` waits for Redis
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Cumulative histogram in the Prometheus sense
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Observations per bucket (not cumulative); the last entry is `+Inf`
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        cumulative += self.counts[self.bounds.len()];
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, cumulative);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, cumulative);
+    }
+}
+
+/// Latencies tracked as histograms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Timing {
+    Compile,
+    TestcaseRun,
+    StorageDownload,
+    IsolateInit,
+    IsolateCleanup,
+}
+
+impl Timing {
+    const ALL: [Timing; 5] = [
+        Timing::Compile,
+        Timing::TestcaseRun,
+        Timing::StorageDownload,
+        Timing::IsolateInit,
+        Timing::IsolateCleanup,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Timing::Compile => "judge_compile_seconds",
+            Timing::TestcaseRun => "judge_testcase_run_seconds",
+            Timing::StorageDownload => "judge_storage_download_seconds",
+            Timing::IsolateInit => "judge_isolate_init_seconds",
+            Timing::IsolateCleanup => "judge_isolate_cleanup_seconds",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Timing::Compile => "Time spent compiling submissions",
+            Timing::TestcaseRun => "Wall time of one testcase run",
+            Timing::StorageDownload => "Latency of storage downloads",
+            Timing::IsolateInit => "Latency of isolate box initialization",
+            Timing::IsolateCleanup => "Latency of isolate box cleanup",
+        }
+    }
+}
+
+/// Process-wide metrics registry
+#[derive(Debug)]
+pub struct Metrics {
+    /// Finished jobs by (job type, verdict)
+    jobs: Mutex<BTreeMap<(String, String), u64>>,
+    timings: Mutex<BTreeMap<Timing, Histogram>>,
+    /// Seconds the last job waited in the queue
+    queue_wait_secs: Mutex<f64>,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| Metrics {
+    jobs: Mutex::new(BTreeMap::new()),
+    timings: Mutex::new(
+        Timing::ALL
+            .into_iter()
+            .map(|timing| (timing, Histogram::new(LATENCY_BUCKETS)))
+            .collect(),
+    ),
+    queue_wait_secs: Mutex::new(0.0),
+});
+
+/// Get the process-wide metrics registry
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    /// Count a finished job
+    pub fn record_job(&self, job_type: &str, verdict: &str) {
+        *self
+            .jobs
+            .lock()
+            .unwrap()
+            .entry((job_type.to_string(), verdict.to_string()))
+            .or_default() += 1;
+    }
+
+    /// Record a latency
+    pub fn observe(&self, timing: Timing, elapsed: Duration) {
+        if let Some(histogram) = self.timings.lock().unwrap().get_mut(&timing) {
+            histogram.observe(elapsed.as_secs_f64());
+        }
+    }
+
+    /// Set how long the current job waited in the queue
+    pub fn set_queue_wait(&self, wait: Duration) {
+        *self.queue_wait_secs.lock().unwrap() = wait.as_secs_f64();
+    }
+
+    /// Render every metric in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP judge_jobs_total Jobs processed by type and verdict\n");
+        out.push_str("# TYPE judge_jobs_total counter\n");
+        for ((job_type, verdict), count) in self.jobs.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "judge_jobs_total{{job_type=\"{}\",verdict=\"{}\"}} {}",
+                job_type, verdict, count
+            );
+        }
+
+        for (timing, histogram) in self.timings.lock().unwrap().iter() {
+            histogram.render(&mut out, timing.name(), timing.help());
+        }
+
+        out.push_str("# HELP judge_queue_wait_seconds Time the latest job waited in the queue\n");
+        out.push_str("# TYPE judge_queue_wait_seconds gauge\n");
+        let _ = writeln!(
+            out,
+            "judge_queue_wait_seconds {}",
+            self.queue_wait_secs.lock().unwrap()
+        );
+
+        out
+    }
+}
+
+/// Start the metrics listener if `JUDGE_METRICS_ADDR` is set
+pub async fn serve_from_env(redis: redis::Client) -> Result<()> {
+    let Ok(addr) = std::env::var("JUDGE_METRICS_ADDR") else {
+        return Ok(());
+    };
+    let addr: SocketAddr = addr
+        .parse()
+        .with_context(|| format!("Invalid JUDGE_METRICS_ADDR: {}", addr))?;
+
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind metrics listener on {}", addr))?;
+    info!("Serving metrics on http://{}/metrics", addr);
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let redis = redis.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, &redis).await {
+                            warn!("Metrics request failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept metrics connection: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Answer one HTTP request
+async fn handle_connection(mut stream: TcpStream, redis: &redis::Client) -> Result<()> {
+    // Only the request line matters; headers and bodies are ignored
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, content_type, body) = match path {
+        "/metrics" => ("200 OK", "text/plain; version=0.0.4", metrics().render()),
+        "/healthz" => ("200 OK", "text/plain", "ok\n".to_string()),
+        "/readyz" => match readiness(redis).await {
+            Ok(()) => ("200 OK", "text/plain", "ready\n".to_string()),
+            Err(reason) => ("503 Service Unavailable", "text/plain", reason + "\n"),
+        },
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Check that the worker can take jobs
+async fn readiness(redis: &redis::Client) -> std::result::Result<(), String> {
+    let ping = async {
+        let mut conn = redis.get_multiplexed_async_connection().await?;
+        redis::cmd("PING").query_async::<String>(&mut conn).await
+    };
+    match tokio::time::timeout(READY_TIMEOUT, ping).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => return Err(format!("redis unavailable: {}", e)),
+        Err(_) => return Err("redis ping timed out".to_string()),
+    }
+
+    if !sandbox::is_cgroups_available().await {
+        return Err("sandbox cgroups unavailable".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_render_is_cumulative() {
+        let mut histogram = Histogram::new(&[0.1, 1.0]);
+        histogram.observe(0.05);
+        histogram.observe(0.5);
+        histogram.observe(3.0);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "judge_test_seconds", "Test");
+        assert!(out.contains("judge_test_seconds_bucket{le=\"0.1\"} 1\n"));
+        assert!(out.contains("judge_test_seconds_bucket{le=\"1\"} 2\n"));
+        assert!(out.contains("judge_test_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("judge_test_seconds_sum 3.55\n"));
+        assert!(out.contains("judge_test_seconds_count 3\n"));
+    }
+}
@@ -268,6 +268,7 @@ async fn process_single_file(
     // 실행
     let mut spec = ExecutionSpec::new(&work_dir)
         .with_command(&run_cmd)
+        .with_seccomp(lang_config.seccomp)
         .with_limits(ExecutionLimits {
             time_ms: job.time_limit,
             memory_mb: job.memory_limit,
@@ -0,0 +1,134 @@
+//! Live progress events
+//!
+//! Jobs publish these on `judge:progress` while they run, so pages can show
+//! each testcase result as it arrives. Every message carries the subject
+//! (`submission_id` or, for validation jobs, `problem_id`) and an `event` tag.
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+/// What a progress event is about
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressSubject {
+    /// A judged submission (judge and anigma jobs)
+    SubmissionId(i64),
+    /// Testcase validation of a problem
+    ProblemId(i64),
+}
+
+/// A step of a running job
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    CompileStart,
+    CompileDone {
+        success: bool,
+    },
+    /// A testcase finished (`index` is 1-based)
+    Testcase {
+        testcase_id: i64,
+        index: usize,
+        total: usize,
+        percentage: u32,
+        verdict: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        time_ms: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        memory_kb: Option<u32>,
+    },
+    /// The job finished; the full result follows on the result channel
+    JudgeDone {
+        verdict: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        score: Option<i64>,
+    },
+}
+
+impl ProgressEvent {
+    /// Event for the `index`-th (1-based) of `total` testcases
+    pub fn testcase(
+        testcase_id: i64,
+        index: usize,
+        total: usize,
+        verdict: impl ToString,
+        time_ms: Option<u32>,
+        memory_kb: Option<u32>,
+    ) -> Self {
+        let percentage = if total > 0 {
+            ((index as f32 / total as f32) * 100.0) as u32
+        } else {
+            0
+        };
+        ProgressEvent::Testcase {
+            testcase_id,
+            index,
+            total,
+            percentage,
+            verdict: verdict.to_string(),
+            time_ms,
+            memory_kb,
+        }
+    }
+}
+
+/// Message published on the progress channel
+#[derive(Debug, Serialize)]
+pub struct ProgressMessage<'a> {
+    #[serde(flatten)]
+    pub subject: ProgressSubject,
+    #[serde(flatten)]
+    pub event: &'a ProgressEvent,
+}
+
+/// Where a job reports its progress
+#[async_trait]
+pub trait ProgressSink: Send {
+    /// Report a step; progress is best effort, so failures are swallowed
+    async fn publish(&mut self, subject: ProgressSubject, event: ProgressEvent);
+}
+
+/// Records every event, e.g. to check them in tests
+#[async_trait]
+impl ProgressSink for Vec<(ProgressSubject, ProgressEvent)> {
+    async fn publish(&mut self, subject: ProgressSubject, event: ProgressEvent) {
+        self.push((subject, event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_message_shape() {
+        let event = ProgressEvent::testcase(42, 7, 10, "accepted", Some(120), Some(14336));
+        let message = ProgressMessage {
+            subject: ProgressSubject::SubmissionId(5),
+            event: &event,
+        };
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({
+                "submission_id": 5,
+                "event": "testcase",
+                "testcase_id": 42,
+                "index": 7,
+                "total": 10,
+                "percentage": 70,
+                "verdict": "accepted",
+                "time_ms": 120,
+                "memory_kb": 14336,
+            })
+        );
+
+        let message = ProgressMessage {
+            subject: ProgressSubject::ProblemId(3),
+            event: &ProgressEvent::CompileStart,
+        };
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({ "problem_id": 3, "event": "compile_start" })
+        );
+    }
+}
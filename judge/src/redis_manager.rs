@@ -2,22 +2,37 @@
 //!
 //! This module handles all Redis-related operations including:
 //! - Worker ID allocation and lease management
-//! - Job queue operations (BLPOP)
+//! - Job queue operations (priority-ordered pop into a per-worker processing list)
 //! - Result storage and publishing
+//!
+//! Jobs are consumed at-least-once: a popped job stays in the worker's
+//! processing list until [`RedisManager::ack_job`] is called after its result
+//! is stored. Processing lists of workers whose lease expired are moved back
+//! to the front of the queues they came from, counting as a retry of each
+//! job; a worker that finds its own lease lost stops (see
+//! [`RedisManager::lease_lost`]) instead of judging jobs that may already be
+//! requeued.
 
-use std::time::Duration;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use redis::aio::MultiplexedConnection;
 use redis::AsyncCommands;
 use serde::Serialize;
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 
 use crate::anigma::AnigmaJudgeResult;
 use crate::judger::JudgeResult;
+use crate::metrics::metrics;
 use crate::playground::PlaygroundResult;
+use crate::progress::{ProgressEvent, ProgressMessage, ProgressSink, ProgressSubject};
 use crate::validator::ValidateResult;
+use crate::worker_status::WorkerStatus;
 use crate::WorkerJob;
 
 /// Redis key constants
@@ -25,9 +40,35 @@ pub mod keys {
     /// Worker lease key prefix for distributed worker ID allocation
     pub const WORKER_LEASE_PREFIX: &str = "judge:worker:lease:";
 
-    /// Judge job queue key
+    /// Worker status hash key prefix (see [`crate::worker_status`])
+    pub const WORKER_STATUS_PREFIX: &str = "judge:worker:status:";
+
+    /// Contest submission queue key (highest priority)
+    pub const CONTEST_QUEUE: &str = "judge:queue:contest";
+
+    /// Practice judge queue key
     pub const JUDGE_QUEUE: &str = "judge:queue";
 
+    /// Testcase validation queue key
+    pub const VALIDATION_QUEUE: &str = "judge:queue:validation";
+
+    /// Playground queue key (lowest priority)
+    pub const PLAYGROUND_QUEUE: &str = "judge:queue:playground";
+
+    /// Per-worker list of jobs taken from the queue but not yet acknowledged
+    pub const PROCESSING_PREFIX: &str = "judge:processing:";
+
+    /// Per-worker list of jobs taken over from dead workers, until they are
+    /// requeued
+    pub const RECLAIMING_PREFIX: &str = "judge:reclaiming:";
+
+    /// Cancel request key prefix; set `judge:cancel:{submission_id}` to stop
+    /// judging that submission
+    pub const CANCEL_PREFIX: &str = "judge:cancel:";
+
+    /// Jobs that could not be parsed or kept failing with a system error
+    pub const DEAD_LETTER_QUEUE: &str = "judge:dead_letter";
+
     /// Judge result key prefix (for polling)
     pub const JUDGE_RESULT_PREFIX: &str = "judge:result:";
 
@@ -51,16 +92,214 @@ pub mod keys {
 }
 
 /// Configuration constants
-const MAX_WORKERS: u32 = 10;
 const WORKER_LEASE_TTL_SECS: u64 = 120;
 const RESULT_EXPIRY_SECS: u64 = 3600; // 1 hour
+/// Default number of automatic retries for jobs ending in a system error
+const DEFAULT_MAX_JOB_RETRIES: u32 = 2;
+/// Maximum number of entries kept in the dead-letter list
+const DEAD_LETTER_MAX_LEN: isize = 1000;
+/// How often an idle worker checks for jobs abandoned by dead workers
+const RECLAIM_INTERVAL: Duration = Duration::from_secs(30);
+/// How long an idle worker waits before polling the queues again
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// How often a running job checks whether it was cancelled
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Pop the first job from the highest-priority non-empty queue into the
+/// processing list. KEYS[1] is the processing list, the rest are queues in
+/// priority order. The processing entry records the source queue.
+const POP_SCRIPT: &str = r#"
+for i = 2, #KEYS do
+    local job = redis.call('LPOP', KEYS[i])
+    if job then
+        local entry = KEYS[i] .. '\t' .. job
+        redis.call('RPUSH', KEYS[1], entry)
+        return entry
+    end
+end
+return false
+"#;
+
+/// Take over a dead worker's processing list (KEYS[2]) by moving it to the
+/// end of this worker's reclaiming list (KEYS[3]), unless the dead worker's
+/// lease (KEYS[1]) exists. Returns the number of jobs moved.
+///
+/// Checking the lease in the same script keeps a worker whose lease is
+/// renewed in the meantime from losing its jobs.
+const RECLAIM_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return 0
+end
+local moved = 0
+while redis.call('LMOVE', KEYS[2], KEYS[3], 'LEFT', 'RIGHT') do
+    moved = moved + 1
+end
+return moved
+"#;
+
+/// Extend a worker lease (KEYS[1]) if it still holds this worker's token
+/// (ARGV[1]); ARGV[2] is the TTL in seconds. Returns 0 if the lease expired
+/// or another worker took it over.
+const LEASE_REFRESH_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[1], 'XX', 'EX', ARGV[2])
+return 1
+"#;
+
+/// Job queues, in priority order (highest first)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum JobQueue {
+    Contest,
+    Practice,
+    Validation,
+    Playground,
+}
+
+impl JobQueue {
+    pub const ALL: [JobQueue; 4] = [
+        JobQueue::Contest,
+        JobQueue::Practice,
+        JobQueue::Validation,
+        JobQueue::Playground,
+    ];
+
+    /// Redis list key of the queue
+    pub fn key(self) -> &'static str {
+        match self {
+            JobQueue::Contest => keys::CONTEST_QUEUE,
+            JobQueue::Practice => keys::JUDGE_QUEUE,
+            JobQueue::Validation => keys::VALIDATION_QUEUE,
+            JobQueue::Playground => keys::PLAYGROUND_QUEUE,
+        }
+    }
+
+    /// Name used in the `JUDGE_QUEUES` configuration
+    pub fn name(self) -> &'static str {
+        match self {
+            JobQueue::Contest => "contest",
+            JobQueue::Practice => "practice",
+            JobQueue::Validation => "validation",
+            JobQueue::Playground => "playground",
+        }
+    }
+}
+
+/// Parse a comma-separated queue subscription (e.g. "contest,practice")
+/// into queues sorted by priority
+pub fn parse_queue_subscription(value: &str) -> Result<Vec<JobQueue>> {
+    let mut queues = Vec::new();
+    for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let queue = JobQueue::ALL
+            .into_iter()
+            .find(|q| q.name() == name)
+            .with_context(|| format!("Unknown job queue: {}", name))?;
+        queues.push(queue);
+    }
+    queues.sort();
+    queues.dedup();
+
+    if queues.is_empty() {
+        anyhow::bail!("At least one job queue must be subscribed");
+    }
+    Ok(queues)
+}
+
+/// Split a processing list entry into its source queue and job payload
+fn split_processing_entry(entry: &str) -> (&str, &str) {
+    entry.split_once('\t').unwrap_or((keys::JUDGE_QUEUE, entry))
+}
+
+/// Number of times a job payload was already retried (`retry_count` field of
+/// the job envelope)
+fn job_retry_count(job_data: &str) -> u32 {
+    serde_json::from_str::<serde_json::Value>(job_data)
+        .ok()
+        .and_then(|job| job.get("retry_count")?.as_u64())
+        .map_or(0, |count| count.try_into().unwrap_or(u32::MAX))
+}
+
+/// How long a job payload waited in its queue, from the `queued_at` field
+/// (milliseconds since the Unix epoch) set by the producer
+fn job_queue_wait(job_data: &str) -> Option<Duration> {
+    let queued_at = serde_json::from_str::<serde_json::Value>(job_data)
+        .ok()?
+        .get("queued_at")?
+        .as_u64()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    Some(now.saturating_sub(Duration::from_millis(queued_at)))
+}
+
+/// Job payload with its retry counter incremented
+fn with_next_retry(job_data: &str) -> Result<String> {
+    let mut job: serde_json::Value =
+        serde_json::from_str(job_data).context("Job payload is not JSON")?;
+    let object = job
+        .as_object_mut()
+        .context("Job payload is not an object")?;
+    object.insert("retry_count".into(), (job_retry_count(job_data) + 1).into());
+    Ok(job.to_string())
+}
+
+/// Entry stored in the dead-letter list
+#[derive(Debug, Serialize)]
+struct DeadLetter<'a> {
+    /// Raw job payload
+    payload: &'a str,
+    /// Queue the job was taken from
+    queue: &'a str,
+    error: &'a str,
+    retry_count: u32,
+    /// Unix timestamp (seconds)
+    failed_at: u64,
+}
+
+/// Identity of a worker: the host it runs on and its allocated worker ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerIdentity {
+    pub host: String,
+    /// Worker ID, unique among the workers sharing a Redis instance
+    pub index: u32,
+}
+
+impl WorkerIdentity {
+    /// Host name from JUDGE_WORKER_HOST, HOSTNAME or /etc/hostname
+    fn local_host() -> String {
+        std::env::var("JUDGE_WORKER_HOST")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|host| host.trim().to_string())
+            .filter(|host| !host.is_empty())
+            .unwrap_or_else(|| "unknown".into())
+    }
+}
+
+impl std::fmt::Display for WorkerIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.host, self.index)
+    }
+}
 
 /// Centralized Redis manager for all Redis operations
 pub struct RedisManager {
-    worker_id: u32,
+    identity: WorkerIdentity,
     client: redis::Client,
     conn: MultiplexedConnection,
     lease_handle: JoinHandle<()>,
+    /// Becomes `true` once the heartbeat finds the lease expired or taken
+    lease_lost: watch::Receiver<bool>,
+    /// Status reported in the worker status hash
+    status: Arc<Mutex<WorkerStatus>>,
+    /// Queues this worker takes jobs from, in priority order
+    queues: Vec<JobQueue>,
+    /// Automatic retries for jobs ending in a system error
+    max_job_retries: u32,
+    /// Processing entry of the job currently being processed (not yet acknowledged)
+    in_flight: Option<String>,
+    /// When abandoned jobs were last reclaimed
+    last_reclaim: Option<Instant>,
 }
 
 impl RedisManager {
@@ -68,70 +307,444 @@ impl RedisManager {
     ///
     /// This will:
     /// 1. Connect to Redis
-    /// 2. Allocate a unique worker ID (0 to MAX_WORKERS-1)
+    /// 2. Allocate a unique worker ID (0 to max_workers-1)
     /// 3. Start a background task to keep the worker lease alive
-    async fn with_url(redis_url: &str) -> Result<Self> {
+    async fn with_url(
+        redis_url: &str,
+        queues: Vec<JobQueue>,
+        max_job_retries: u32,
+        max_workers: u32,
+    ) -> Result<Self> {
         let client = redis::Client::open(redis_url).context("Failed to create Redis client")?;
 
         let conn = get_connection_with_retry(&client).await?;
         info!("Connected to Redis at {}", redis_url);
 
-        let worker_id = allocate_worker_id(&client).await?;
+        let host = WorkerIdentity::local_host();
+        let lease_token = lease_token(&host);
+        let worker_id = allocate_worker_id(&client, &lease_token, max_workers).await?;
+        let identity = WorkerIdentity {
+            host,
+            index: worker_id,
+        };
         info!(
-            "Allocated worker_id={} (lease {}s)",
-            worker_id, WORKER_LEASE_TTL_SECS
+            "Allocated worker {} (lease {}s)",
+            identity, WORKER_LEASE_TTL_SECS
         );
 
-        let lease_handle = spawn_lease_heartbeat(client.clone(), worker_id);
-
-        Ok(Self {
+        let status = Arc::new(Mutex::new(WorkerStatus::new(
+            identity.host.clone(),
+            worker_id,
+        )));
+        let (lost_tx, lease_lost) = watch::channel(false);
+        let lease_handle = spawn_lease_heartbeat(
+            client.clone(),
             worker_id,
+            lease_token,
+            status.clone(),
+            lost_tx,
+        );
+
+        let mut manager = Self {
+            identity,
             client,
             conn,
             lease_handle,
-        })
+            lease_lost,
+            status,
+            queues,
+            max_job_retries,
+            in_flight: None,
+            last_reclaim: None,
+        };
+
+        manager.write_status().await;
+
+        // A previous worker with this ID may have died mid-job or while
+        // requeueing jobs it took over
+        for key in [manager.processing_key(), manager.reclaiming_key()] {
+            manager
+                .requeue_list(&key, true)
+                .await
+                .context("Failed to requeue jobs left by a previous worker")?;
+        }
+
+        Ok(manager)
     }
 
     /// Create a new RedisManager using the REDIS_URL environment variable.
     /// Defaults to "redis://localhost:6379" if not set.
-    pub async fn from_env() -> Result<Self> {
+    ///
+    /// The subscribed queues are read from JUDGE_QUEUES (comma-separated
+    /// queue names), defaulting to all queues. Jobs failing with a transient
+    /// error or left behind by a stopped worker are retried up to
+    /// JUDGE_MAX_JOB_RETRIES times (default 2).
+    pub async fn from_env(max_workers: u32) -> Result<Self> {
         let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".into());
-        Self::with_url(&url).await
+        let queues = match std::env::var("JUDGE_QUEUES") {
+            Ok(value) => parse_queue_subscription(&value).context("Invalid JUDGE_QUEUES")?,
+            Err(_) => JobQueue::ALL.to_vec(),
+        };
+        info!(
+            "Subscribed to queues: {}",
+            queues
+                .iter()
+                .map(|q| q.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let max_job_retries = std::env::var("JUDGE_MAX_JOB_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_JOB_RETRIES);
+        Self::with_url(&url, queues, max_job_retries, max_workers).await
     }
 
     /// Get the allocated worker ID
     pub fn worker_id(&self) -> u32 {
-        self.worker_id
+        self.identity.index
     }
 
-    /// Block and wait for the next job from the queue.
+    /// Get the host and worker ID of this worker
+    pub fn identity(&self) -> &WorkerIdentity {
+        &self.identity
+    }
+
+    /// Get the Redis client, e.g. to open connections for health checks
+    pub fn client(&self) -> &redis::Client {
+        &self.client
+    }
+
+    /// Record in the status hash that a job started
+    pub async fn begin_job_status(&mut self, job_type: &'static str, current_job: String) {
+        self.status.lock().unwrap().begin_job(job_type, current_job);
+        self.write_status().await;
+    }
+
+    /// Record in the status hash that the current job ended
+    pub async fn finish_job_status(&mut self) {
+        self.status.lock().unwrap().finish_job();
+        self.write_status().await;
+    }
+
+    /// Record in the status hash whether isolate's cgroup support works
+    pub async fn set_cgroups_status(&mut self, available: bool) {
+        self.status.lock().unwrap().cgroups_available = Some(available);
+        self.write_status().await;
+    }
+
+    /// Write the status hash now instead of waiting for the next heartbeat
+    async fn write_status(&mut self) {
+        let fields = self.status.lock().unwrap().fields();
+        if let Err(e) = write_status_hash(&mut self.conn, self.identity.index, &fields).await {
+            warn!("Failed to write worker status: {}", e);
+        }
+    }
+
+    /// Wait for the next job, taking it from the highest-priority subscribed
+    /// queue that has one.
     ///
-    /// This uses BLPOP to efficiently wait for jobs without polling.
+    /// The job is atomically moved into this worker's processing list and
+    /// stays there until [`RedisManager::ack_job`] is called. Redis cannot
+    /// block on several lists while moving, so an idle worker polls the
+    /// queues, and periodically moves jobs abandoned by dead workers back.
     /// Automatically reconnects on connection failure.
     pub async fn pop_job(&mut self) -> Result<WorkerJob> {
+        if self.in_flight.is_some() {
+            warn!("Popping a new job while the previous one is not acknowledged");
+        }
+
+        let script = redis::Script::new(POP_SCRIPT);
         loop {
-            let result: Option<(String, String)> =
-                match redis::AsyncCommands::blpop(&mut self.conn, keys::JUDGE_QUEUE, 0.0).await {
-                    Ok(res) => res,
-                    Err(e) => {
-                        warn!("Redis BLPOP failed: {}. Reconnecting...", e);
-                        self.reconnect().await?;
-                        continue;
-                    }
-                };
-
-            if let Some((_, job_data)) = result {
-                match serde_json::from_str::<WorkerJob>(&job_data) {
-                    Ok(job) => return Ok(job),
-                    Err(e) => {
-                        warn!("Failed to parse job data: {}. Data: {}", e, job_data);
-                        continue;
+            if self
+                .last_reclaim
+                .is_none_or(|t| t.elapsed() >= RECLAIM_INTERVAL)
+            {
+                if let Err(e) = self.reclaim_abandoned_jobs().await {
+                    warn!("Failed to reclaim abandoned jobs: {:#}", e);
+                }
+                self.last_reclaim = Some(Instant::now());
+            }
+
+            let mut invocation = script.key(self.processing_key());
+            for queue in &self.queues {
+                invocation.key(queue.key());
+            }
+            let result: Option<String> = match invocation.invoke_async(&mut self.conn).await {
+                Ok(res) => res,
+                Err(e) => {
+                    warn!("Redis job pop failed: {}. Reconnecting...", e);
+                    self.reconnect().await?;
+                    continue;
+                }
+            };
+
+            let Some(entry) = result else {
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            };
+
+            let (_, job_data) = split_processing_entry(&entry);
+            if let Some(wait) = job_queue_wait(job_data) {
+                metrics().set_queue_wait(wait);
+            }
+            let parsed = serde_json::from_str::<WorkerJob>(job_data);
+            self.in_flight = Some(entry);
+            match parsed {
+                Ok(job) => return Ok(job),
+                // Unparseable jobs would be retried forever, so set them aside
+                Err(e) => {
+                    let error = format!("Failed to parse job data: {}", e);
+                    warn!("{}", error);
+                    self.dead_letter_job(&error).await?;
+                }
+            }
+        }
+    }
+
+    /// Acknowledge the job returned by the last [`RedisManager::pop_job`],
+    /// removing it from the processing list. Call only after its result is stored.
+    pub async fn ack_job(&mut self) -> Result<()> {
+        let Some(entry) = self.in_flight.take() else {
+            return Ok(());
+        };
+        let key = self.processing_key();
+
+        if let Err(e) = self.conn.lrem::<_, _, ()>(&key, 1, &entry).await {
+            warn!("Failed to acknowledge job: {}. Reconnecting...", e);
+            self.reconnect().await?;
+            self.conn.lrem::<_, _, ()>(&key, 1, &entry).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Put the job returned by the last [`RedisManager::pop_job`] back at the
+    /// front of its queue without processing it
+    pub async fn requeue_job(&mut self) -> Result<()> {
+        let Some(entry) = self.in_flight.take() else {
+            return Ok(());
+        };
+        let (queue, job_data) = split_processing_entry(&entry);
+
+        redis::pipe()
+            .atomic()
+            .lrem(self.processing_key(), 1, &entry)
+            .ignore()
+            .lpush(queue, job_data)
+            .ignore()
+            .query_async::<()>(&mut self.conn)
+            .await
+            .context("Failed to requeue job")?;
+
+        Ok(())
+    }
+
+    /// Handle the job returned by the last [`RedisManager::pop_job`] failing
+    /// with a transient error (see [`crate::error`]).
+    ///
+    /// While retries are left, the job goes to the back of its queue with its
+    /// retry counter incremented and `true` is returned; its result should
+    /// not be stored. Otherwise the job is recorded in the dead-letter list,
+    /// stays in flight so its result can be stored and acknowledged, and
+    /// `false` is returned.
+    pub async fn retry_failed_job(&mut self, error: &str) -> Result<bool> {
+        let Some(entry) = self.in_flight.clone() else {
+            return Ok(false);
+        };
+        let (queue, job_data) = split_processing_entry(&entry);
+        let retry_count = job_retry_count(job_data);
+
+        if retry_count >= self.max_job_retries {
+            warn!(
+                "Job failed after {} retries, moving it to the dead-letter list",
+                retry_count
+            );
+            let dead_letter = dead_letter_entry(&entry, error)?;
+            redis::pipe()
+                .atomic()
+                .lpush(keys::DEAD_LETTER_QUEUE, dead_letter)
+                .ignore()
+                .ltrim(keys::DEAD_LETTER_QUEUE, 0, DEAD_LETTER_MAX_LEN - 1)
+                .ignore()
+                .query_async::<()>(&mut self.conn)
+                .await
+                .context("Failed to store dead-letter job")?;
+            return Ok(false);
+        }
+
+        info!(
+            "Retrying job after system error (attempt {}/{})",
+            retry_count + 1,
+            self.max_job_retries
+        );
+        redis::pipe()
+            .atomic()
+            .lrem(self.processing_key(), 1, &entry)
+            .ignore()
+            .rpush(queue, with_next_retry(job_data)?)
+            .ignore()
+            .query_async::<()>(&mut self.conn)
+            .await
+            .context("Failed to retry job")?;
+        self.in_flight = None;
+
+        Ok(true)
+    }
+
+    /// Move the job returned by the last [`RedisManager::pop_job`] from the
+    /// processing list to the dead-letter list
+    async fn dead_letter_job(&mut self, error: &str) -> Result<()> {
+        let Some(entry) = self.in_flight.take() else {
+            return Ok(());
+        };
+        let dead_letter = dead_letter_entry(&entry, error)?;
+
+        redis::pipe()
+            .atomic()
+            .lrem(self.processing_key(), 1, &entry)
+            .ignore()
+            .lpush(keys::DEAD_LETTER_QUEUE, dead_letter)
+            .ignore()
+            .ltrim(keys::DEAD_LETTER_QUEUE, 0, DEAD_LETTER_MAX_LEN - 1)
+            .ignore()
+            .query_async::<()>(&mut self.conn)
+            .await
+            .context("Failed to store dead-letter job")?;
+
+        Ok(())
+    }
+
+    /// Resolve once a cancel request for the submission exists.
+    ///
+    /// Polls on its own connection, so it can run alongside the job.
+    pub fn cancellation(&self, submission_id: i64) -> impl Future<Output = ()> + 'static {
+        let client = self.client.clone();
+        let key = format!("{}{}", keys::CANCEL_PREFIX, submission_id);
+        async move {
+            let mut conn = None;
+            loop {
+                if conn.is_none() {
+                    conn = get_connection_with_retry(&client).await.ok();
+                }
+                if let Some(c) = conn.as_mut() {
+                    match c.exists::<_, bool>(&key).await {
+                        Ok(true) => return,
+                        Ok(false) => {}
+                        Err(e) => {
+                            warn!("Failed to check cancel request {}: {}", key, e);
+                            conn = None;
+                        }
                     }
                 }
+                tokio::time::sleep(CANCEL_POLL_INTERVAL).await;
             }
         }
     }
 
+    /// Remove the cancel request of a submission once it was handled
+    pub async fn clear_cancellation(&mut self, submission_id: i64) -> Result<()> {
+        let key = format!("{}{}", keys::CANCEL_PREFIX, submission_id);
+        self.conn.del::<_, ()>(&key).await?;
+        Ok(())
+    }
+
+    /// Move the processing lists of workers without a live lease back to the queue
+    async fn reclaim_abandoned_jobs(&mut self) -> Result<()> {
+        // Scan instead of counting up to the worker limit, which may differ
+        // between hosts
+        let pattern = format!("{}*", keys::PROCESSING_PREFIX);
+        let mut worker_ids = Vec::new();
+        {
+            let mut iter: redis::AsyncIter<String> = self.conn.scan_match(&pattern).await?;
+            while let Some(key) = iter.next_item().await {
+                if let Some(Ok(id)) = key
+                    .strip_prefix(keys::PROCESSING_PREFIX)
+                    .map(str::parse::<u32>)
+                {
+                    worker_ids.push(id);
+                }
+            }
+        }
+
+        let own_id = self.identity.index;
+        let reclaiming_key = self.reclaiming_key();
+        let script = redis::Script::new(RECLAIM_SCRIPT);
+        for worker_id in worker_ids.into_iter().filter(|id| *id != own_id) {
+            let moved: u64 = script
+                .key(format!("{}{}", keys::WORKER_LEASE_PREFIX, worker_id))
+                .key(format!("{}{}", keys::PROCESSING_PREFIX, worker_id))
+                .key(&reclaiming_key)
+                .invoke_async(&mut self.conn)
+                .await?;
+            if moved > 0 {
+                info!(
+                    "Took over {} unfinished jobs of worker {} without a lease",
+                    moved, worker_id
+                );
+            }
+        }
+        self.requeue_list(&reclaiming_key, true).await
+    }
+
+    /// Move every job in a list of this worker to the front of its queue.
+    ///
+    /// With `crashed`, the jobs were left by a worker that stopped while
+    /// judging them, which may be the job's fault (e.g. the worker was killed
+    /// for running out of memory). Each such requeue counts as a retry, and a
+    /// job out of retries goes to the dead-letter list instead, so it can't
+    /// take down every worker and block its queue forever.
+    async fn requeue_list(&mut self, key: &str, crashed: bool) -> Result<()> {
+        let entries: Vec<String> = self.conn.lrange(key, 0, -1).await?;
+        // From the tail so the jobs keep their original order at the head
+        for entry in entries.iter().rev() {
+            let (queue, job_data) = split_processing_entry(entry);
+            let retry_count = job_retry_count(job_data);
+
+            let mut pipe = redis::pipe();
+            pipe.atomic().lrem(key, 1, entry).ignore();
+            if !crashed {
+                pipe.lpush(queue, job_data).ignore();
+                info!("Requeued unfinished job to {}", queue);
+            } else if retry_count >= self.max_job_retries {
+                warn!(
+                    "Job stopped a worker after {} retries, moving it to the dead-letter list",
+                    retry_count
+                );
+                let error = "Worker stopped while processing the job";
+                pipe.lpush(keys::DEAD_LETTER_QUEUE, dead_letter_entry(entry, error)?)
+                    .ignore()
+                    .ltrim(keys::DEAD_LETTER_QUEUE, 0, DEAD_LETTER_MAX_LEN - 1)
+                    .ignore();
+            } else {
+                // Payloads that aren't JSON can't be counted; they are
+                // dead-lettered once a worker fails to parse them
+                let retried = with_next_retry(job_data).unwrap_or_else(|_| job_data.to_string());
+                pipe.lpush(queue, retried).ignore();
+                info!(
+                    "Requeued job left by a stopped worker to {} (attempt {}/{})",
+                    queue,
+                    retry_count + 1,
+                    self.max_job_retries
+                );
+            }
+            pipe.query_async::<()>(&mut self.conn)
+                .await
+                .context("Failed to requeue job")?;
+        }
+        Ok(())
+    }
+
+    /// Processing list key of this worker
+    fn processing_key(&self) -> String {
+        format!("{}{}", keys::PROCESSING_PREFIX, self.identity.index)
+    }
+
+    /// Reclaiming list key of this worker
+    fn reclaiming_key(&self) -> String {
+        format!("{}{}", keys::RECLAIMING_PREFIX, self.identity.index)
+    }
+
     /// Store a judge result in Redis.
     ///
     /// The result is stored with a 1-hour expiration and also published
@@ -197,25 +810,16 @@ impl RedisManager {
         Ok(())
     }
 
-    /// Publish judge progress update
+    /// Publish a progress event of a running job
     pub async fn publish_progress(
         &mut self,
-        submission_id: i64,
-        current: usize,
-        total: usize,
+        subject: ProgressSubject,
+        event: ProgressEvent,
     ) -> Result<()> {
-        let percentage = if total > 0 {
-            ((current as f32 / total as f32) * 100.0) as u32
-        } else {
-            0
-        };
-
-        let progress = serde_json::json!({
-            "submission_id": submission_id,
-            "percentage": percentage,
-        });
-
-        let json = serde_json::to_string(&progress)?;
+        let json = serde_json::to_string(&ProgressMessage {
+            subject,
+            event: &event,
+        })?;
 
         // Ignore errors - progress updates are non-critical
         let _ = self
@@ -256,6 +860,40 @@ impl RedisManager {
         Ok(())
     }
 
+    /// Resolve once this worker's lease was lost: it expired or was taken
+    /// over, so another worker may already be judging its jobs.
+    pub fn lease_lost(&self) -> impl Future<Output = ()> + 'static {
+        let mut lease_lost = self.lease_lost.clone();
+        async move {
+            if lease_lost.wait_for(|lost| *lost).await.is_err() {
+                // The heartbeat was stopped on shutdown
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Release this worker on shutdown: put unfinished jobs back in the queue,
+    /// stop the lease heartbeat and delete the lease key so the worker ID is
+    /// free right away
+    pub async fn shutdown(mut self) -> Result<()> {
+        self.in_flight = None;
+        let key = self.processing_key();
+        self.requeue_list(&key, false)
+            .await
+            .context("Failed to requeue unfinished jobs")?;
+
+        self.lease_handle.abort();
+        let lease_key = format!("{}{}", keys::WORKER_LEASE_PREFIX, self.identity.index);
+        let status_key = format!("{}{}", keys::WORKER_STATUS_PREFIX, self.identity.index);
+        self.conn
+            .del::<_, ()>(&[&lease_key, &status_key])
+            .await
+            .context("Failed to delete worker lease")?;
+
+        info!("Released worker {}", self.identity);
+        Ok(())
+    }
+
     /// Reconnect to Redis
     async fn reconnect(&mut self) -> Result<()> {
         self.conn = get_connection_with_retry(&self.client).await?;
@@ -263,6 +901,24 @@ impl RedisManager {
     }
 }
 
+/// Serialize a processing list entry as a dead-letter list entry
+fn dead_letter_entry(entry: &str, error: &str) -> Result<String> {
+    let (queue, payload) = split_processing_entry(entry);
+    let failed_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    serde_json::to_string(&DeadLetter {
+        payload,
+        queue,
+        error,
+        retry_count: job_retry_count(payload),
+        failed_at,
+    })
+    .context("Failed to serialize dead-letter entry")
+}
+
 impl Drop for RedisManager {
     fn drop(&mut self) {
         self.lease_handle.abort();
@@ -285,16 +941,37 @@ async fn get_connection_with_retry(client: &redis::Client) -> Result<Multiplexed
     }
 }
 
+#[async_trait]
+impl ProgressSink for RedisManager {
+    async fn publish(&mut self, subject: ProgressSubject, event: ProgressEvent) {
+        let _ = self.publish_progress(subject, event).await;
+    }
+}
+
+/// Lease value of this process: the host holding the worker ID and a token
+/// telling this process apart from later holders on the same host
+fn lease_token(host: &str) -> String {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("{}:{}:{}", host, std::process::id(), started)
+}
+
 /// Allocate a unique worker ID using Redis SET NX with expiration
-async fn allocate_worker_id(client: &redis::Client) -> Result<u32> {
+async fn allocate_worker_id(
+    client: &redis::Client,
+    lease_token: &str,
+    max_workers: u32,
+) -> Result<u32> {
     loop {
         let mut conn = get_connection_with_retry(client).await?;
 
-        for worker_id in 0..MAX_WORKERS {
+        for worker_id in 0..max_workers {
             let key = format!("{}{}", keys::WORKER_LEASE_PREFIX, worker_id);
             let claimed: Option<String> = redis::cmd("SET")
                 .arg(&key)
-                .arg("claimed")
+                .arg(lease_token)
                 .arg("NX")
                 .arg("EX")
                 .arg(WORKER_LEASE_TTL_SECS as usize)
@@ -307,17 +984,44 @@ async fn allocate_worker_id(client: &redis::Client) -> Result<u32> {
         }
 
         warn!(
-            "No free worker_id (0-{}). Retrying in 1 second...",
-            MAX_WORKERS - 1
+            "No free worker_id (0-{}). Raise JUDGE_MAX_WORKERS to run more workers. Retrying in 1 second...",
+            max_workers - 1
         );
         tokio::time::sleep(Duration::from_secs(1)).await;
     }
 }
 
-/// Spawn a background task to keep the worker lease alive
-fn spawn_lease_heartbeat(client: redis::Client, worker_id: u32) -> JoinHandle<()> {
+/// Replace the worker status hash and let it expire with the lease
+async fn write_status_hash(
+    conn: &mut MultiplexedConnection,
+    worker_id: u32,
+    fields: &[(&'static str, String)],
+) -> redis::RedisResult<()> {
+    let key = format!("{}{}", keys::WORKER_STATUS_PREFIX, worker_id);
+    redis::pipe()
+        .atomic()
+        .hset_multiple(&key, fields)
+        .ignore()
+        .expire(&key, WORKER_LEASE_TTL_SECS as i64)
+        .ignore()
+        .query_async(conn)
+        .await
+}
+
+/// Spawn a background task to keep the worker lease and status hash alive.
+///
+/// The task stops and sets `lost` once the lease no longer holds
+/// `lease_token`.
+fn spawn_lease_heartbeat(
+    client: redis::Client,
+    worker_id: u32,
+    lease_token: String,
+    status: Arc<Mutex<WorkerStatus>>,
+    lost: watch::Sender<bool>,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
         let interval = Duration::from_secs(WORKER_LEASE_TTL_SECS / 2);
+        let script = redis::Script::new(LEASE_REFRESH_SCRIPT);
 
         loop {
             tokio::time::sleep(interval).await;
@@ -325,13 +1029,25 @@ fn spawn_lease_heartbeat(client: redis::Client, worker_id: u32) -> JoinHandle<()
             match get_connection_with_retry(&client).await {
                 Ok(mut conn) => {
                     let key = format!("{}{}", keys::WORKER_LEASE_PREFIX, worker_id);
-                    if let Err(e) = redis::cmd("EXPIRE")
-                        .arg(&key)
-                        .arg(WORKER_LEASE_TTL_SECS as usize)
-                        .query_async::<()>(&mut conn)
-                        .await
-                    {
-                        warn!("Failed to refresh worker lease {}: {}", worker_id, e);
+                    let refreshed: redis::RedisResult<bool> = script
+                        .key(&key)
+                        .arg(&lease_token)
+                        .arg(WORKER_LEASE_TTL_SECS)
+                        .invoke_async(&mut conn)
+                        .await;
+                    match refreshed {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            error!("Lost the lease of worker {}", worker_id);
+                            lost.send_replace(true);
+                            return;
+                        }
+                        Err(e) => warn!("Failed to refresh worker lease {}: {}", worker_id, e),
+                    }
+
+                    let fields = status.lock().unwrap().fields();
+                    if let Err(e) = write_status_hash(&mut conn, worker_id, &fields).await {
+                        warn!("Failed to refresh worker status {}: {}", worker_id, e);
                     }
                 }
                 Err(e) => {
@@ -344,3 +1060,43 @@ fn spawn_lease_heartbeat(client: redis::Client, worker_id: u32) -> JoinHandle<()
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_queue_subscription_sorts_by_priority() {
+        let queues = parse_queue_subscription("playground, contest,contest").unwrap();
+        assert_eq!(queues, vec![JobQueue::Contest, JobQueue::Playground]);
+
+        assert!(parse_queue_subscription("contest,unknown").is_err());
+        assert!(parse_queue_subscription(" , ").is_err());
+    }
+
+    #[test]
+    fn test_retry_count_in_job_envelope() {
+        let job = r#"{"job_type":"judge","submission_id":1}"#;
+        assert_eq!(job_retry_count(job), 0);
+
+        let retried = with_next_retry(&with_next_retry(job).unwrap()).unwrap();
+        assert_eq!(job_retry_count(&retried), 2);
+        assert!(retried.contains(r#""submission_id":1"#));
+
+        assert_eq!(job_retry_count("not json"), 0);
+        assert!(with_next_retry("not json").is_err());
+    }
+
+    #[test]
+    fn test_split_processing_entry() {
+        assert_eq!(
+            split_processing_entry("judge:queue:contest\t{\"job_type\":\"judge\"}"),
+            (keys::CONTEST_QUEUE, r#"{"job_type":"judge"}"#)
+        );
+        // Entries written before the queue was recorded go back to the default queue
+        assert_eq!(
+            split_processing_entry(r#"{"job_type":"judge"}"#),
+            (keys::JUDGE_QUEUE, r#"{"job_type":"judge"}"#)
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Sandbox backends
+//!
+//! A [`SandboxBackend`] is one box that programs run in: isolate
+//! ([`IsolateBox`](super::IsolateBox)) or the built-in namespace sandbox
+//! ([`NativeBox`](super::NativeBox)) in production, picked with
+//! `JUDGE_SANDBOX_BACKEND`, or the scripted
+//! [`MockSandbox`](super::mock::MockSandbox) in tests. Boxes come from the
+//! [`BoxProvider`] installed with [`with_provider`] for the current task, and
+//! from the configured backend otherwise.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::future::Future;
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+use super::config::{get_config, SandboxKind};
+use super::isolate_box::{self, IsolateBox};
+use super::meta::{IsolateMeta, IsolateStatus};
+use super::native::{self, NativeBox};
+use super::seccomp::SeccompProfile;
+use crate::error::Transient;
+
+/// Environment of every sandboxed program
+pub const SANDBOX_ENV: &[(&str, &str)] = &[
+    ("PATH", "/usr/local/bin:/usr/bin:/bin"),
+    ("HOME", "/box"),
+    ("JAVA_HOME", "/usr/lib/jvm/java-17-openjdk-amd64"),
+    ("LANG", "en_US.UTF-8"),
+    ("LC_ALL", "en_US.UTF-8"),
+    ("LANGUAGE", "en_US:en"),
+    ("JAVA_TOOL_OPTIONS", "-Dfile.encoding=UTF-8"),
+];
+
+/// Host directory mounted read-only into the box
+#[derive(Debug, Clone)]
+pub struct DirBind {
+    /// Path on the host
+    pub host: PathBuf,
+    /// Mount point inside the box (absolute, e.g. "/data")
+    pub inside: String,
+}
+
+impl DirBind {
+    pub fn new(host: impl AsRef<Path>, inside: impl Into<String>) -> Self {
+        Self {
+            host: host.as_ref().to_path_buf(),
+            inside: inside.into(),
+        }
+    }
+}
+
+/// I/O specification for sandbox execution
+#[derive(Debug, Default, Clone)]
+pub struct IoSpec {
+    /// Path to stdin file (will be copied into box)
+    pub stdin_path: Option<PathBuf>,
+    /// File name for stdout inside the box
+    pub stdout_file: String,
+    /// File name for stderr inside the box
+    pub stderr_file: String,
+    /// Extra read-only directory mounts
+    pub binds: Vec<DirBind>,
+}
+
+impl IoSpec {
+    pub fn new() -> Self {
+        Self {
+            stdin_path: None,
+            stdout_file: "stdout.txt".to_string(),
+            stderr_file: "stderr.txt".to_string(),
+            binds: vec![],
+        }
+    }
+
+    pub fn with_stdin(mut self, path: impl AsRef<Path>) -> Self {
+        self.stdin_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn with_binds(mut self, binds: &[DirBind]) -> Self {
+        self.binds.extend_from_slice(binds);
+        self
+    }
+}
+
+/// Resource limits for sandbox execution
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Time limit in milliseconds
+    pub time_ms: u32,
+    /// Memory limit in MB
+    pub memory_mb: u32,
+    /// Maximum number of processes
+    pub processes: u32,
+    /// Maximum open files
+    pub open_files: u32,
+    /// Maximum file size in KB
+    pub fsize_kb: u32,
+    /// Syscall filter of the program
+    pub seccomp: SeccompProfile,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            time_ms: 1000,
+            memory_mb: 256,
+            processes: 64,
+            open_files: 256,
+            fsize_kb: 262144, // 256MB
+            seccomp: SeccompProfile::Base,
+        }
+    }
+}
+
+/// Raw outcome from sandbox execution (no verdict interpretation)
+#[derive(Debug)]
+pub struct SandboxOutcome {
+    /// Parsed meta file contents
+    pub meta: IsolateMeta,
+    /// Stdout content (as string, may have UTF-8 conversion losses)
+    pub stdout: String,
+    /// Stdout content (as raw bytes, preserves binary data)
+    pub stdout_bytes: Vec<u8>,
+    /// Stderr content (if not redirected to stdout)
+    pub stderr: String,
+}
+
+impl SandboxOutcome {
+    /// A program that ignores SIGXFSZ only sees failing writes, so a stdout
+    /// that reached the limit counts as exceeding it as well
+    pub fn check_output_limit(&mut self, limits: &Limits) {
+        if self.stdout_bytes.len() as u64 >= output_limit_bytes(limits)
+            && matches!(
+                self.meta.status,
+                IsolateStatus::Ok | IsolateStatus::RuntimeError
+            )
+        {
+            self.meta.status = IsolateStatus::OutputLimitExceeded;
+        }
+    }
+}
+
+fn output_limit_bytes(limits: &Limits) -> u64 {
+    limits.fsize_kb as u64 * 1024
+}
+
+/// Read a run's stdout file, never more than the output limit
+pub async fn read_stdout(path: &Path, limits: &Limits) -> Vec<u8> {
+    let read = async {
+        let file = fs::File::open(path).await?;
+        let mut bytes = Vec::new();
+        file.take(output_limit_bytes(limits))
+            .read_to_end(&mut bytes)
+            .await?;
+        Ok::<_, std::io::Error>(bytes)
+    };
+    read.await.unwrap_or_default()
+}
+
+/// Absolute path of a command's program; bare names are looked up in /usr/bin
+pub fn program_path(program: &str) -> String {
+    if program.starts_with('/') || program.starts_with("./") {
+        program.to_string()
+    } else {
+        format!("/usr/bin/{}", program)
+    }
+}
+
+/// A box that runs programs under resource limits
+#[async_trait]
+pub trait SandboxBackend: Send + Sync {
+    /// Working directory of programs in the box
+    fn work_dir(&self) -> PathBuf;
+
+    /// Pin every following run of this box to a single CPU
+    fn pin_to_cpu(&mut self, cpu: usize);
+
+    /// Run a command with stdin and stdout redirected to files in the box
+    async fn run(&self, command: &[String], limits: &Limits, io: &IoSpec)
+        -> Result<SandboxOutcome>;
+
+    /// Run a command with its stdin and stdout attached to the given handles
+    /// instead of files (e.g. pipes to an interactor).
+    ///
+    /// The returned outcome has empty stdout since it never touches disk.
+    async fn run_with_stdio(
+        &self,
+        command: &[String],
+        limits: &Limits,
+        stderr_file: &str,
+        binds: &[DirBind],
+        stdin: OwnedFd,
+        stdout: OwnedFd,
+    ) -> Result<SandboxOutcome>;
+
+    /// Release the box
+    async fn cleanup(self: Box<Self>) -> Result<()>;
+
+    /// Copy a file into the box's working directory
+    async fn copy_in(&self, source: &Path, dest_name: &str) -> Result<()> {
+        let dest = self.work_dir().join(dest_name);
+        fs::copy(source, &dest)
+            .await
+            .with_context(|| format!("Failed to copy {:?} to {:?}", source, dest))?;
+        Ok(())
+    }
+
+    /// Copy a directory's contents into the box's working directory
+    async fn copy_dir_in(&self, source_dir: &Path) -> Result<()> {
+        let mut entries = fs::read_dir(source_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            // Skip directories, only copy regular files
+            if metadata.is_dir() {
+                continue;
+            }
+            fs::copy(entry.path(), self.work_dir().join(entry.file_name())).await?;
+        }
+        Ok(())
+    }
+
+    /// Copy a file out of the box's working directory
+    async fn copy_out(&self, source_name: &str, dest: &Path) -> Result<()> {
+        let source = self.work_dir().join(source_name);
+        fs::copy(&source, dest)
+            .await
+            .with_context(|| format!("Failed to copy {:?} to {:?}", source, dest))?;
+        Ok(())
+    }
+
+    /// Read a file from the box's working directory
+    async fn read_file(&self, name: &str) -> Result<String> {
+        let path = self.work_dir().join(name);
+        fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {:?}", path))
+    }
+}
+
+/// Creates boxes in place of isolate, see [`with_provider`]
+#[async_trait]
+pub trait BoxProvider: Send + Sync {
+    async fn create_box(&self) -> Result<Box<dyn SandboxBackend>>;
+
+    /// Clean up every box handed out and not cleaned up yet
+    async fn cleanup_active_boxes(&self);
+}
+
+tokio::task_local! {
+    static PROVIDER: Arc<dyn BoxProvider>;
+}
+
+/// Run `future` with every box it creates coming from `provider`
+pub async fn with_provider<F: Future>(provider: Arc<dyn BoxProvider>, future: F) -> F::Output {
+    PROVIDER.scope(provider, future).await
+}
+
+/// Create a box from the current task's provider, or from the configured
+/// backend if there is none
+pub async fn create_box() -> Result<Box<dyn SandboxBackend>> {
+    if let Ok(provider) = PROVIDER.try_with(Arc::clone) {
+        return provider.create_box().await;
+    }
+
+    if !is_cgroups_available().await {
+        anyhow::bail!("Cgroup support is required for sandboxed execution");
+    }
+    let created = match get_config().backend {
+        SandboxKind::Isolate => IsolateBox::new(true)
+            .await
+            .map(|b| Box::new(b) as Box<dyn SandboxBackend>),
+        SandboxKind::Native => NativeBox::new()
+            .await
+            .map(|b| Box::new(b) as Box<dyn SandboxBackend>),
+    };
+    // Boxes are reclaimed as other runs end, so a later attempt may get one
+    created.with_context(|| Transient("Failed to create sandbox box".into()))
+}
+
+/// Clean up every box created and not cleaned up yet, e.g. boxes of a job
+/// whose future was dropped
+pub async fn cleanup_active_boxes() {
+    if let Ok(provider) = PROVIDER.try_with(Arc::clone) {
+        return provider.cleanup_active_boxes().await;
+    }
+    isolate_box::cleanup_active_boxes().await
+}
+
+/// Check if the configured backend can enforce limits with cgroups
+pub async fn is_cgroups_available() -> bool {
+    match get_config().backend {
+        SandboxKind::Isolate => isolate_box::is_cgroups_available().await,
+        SandboxKind::Native => native::is_cgroups_available().await,
+    }
+}
+
+/// Ensure the configured backend has cgroups; return an error otherwise
+pub async fn ensure_cgroups_available() -> Result<()> {
+    match get_config().backend {
+        SandboxKind::Isolate => isolate_box::ensure_cgroups_available().await,
+        SandboxKind::Native => native::ensure_cgroups_available().await,
+    }
+}
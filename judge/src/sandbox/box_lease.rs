@@ -0,0 +1,92 @@
+//! Box ID allocation
+//!
+//! Every isolate box is created from a [`BoxLease`], which holds one box ID of
+//! this worker's range until it is dropped. No two live leases share an ID,
+//! so concurrent runs never end up in the same box.
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use super::config::get_config;
+
+/// Box IDs currently leased, and where the next search starts
+struct Leases {
+    leased: BTreeSet<u32>,
+    cursor: u32,
+}
+
+static LEASES: Mutex<Leases> = Mutex::new(Leases {
+    leased: BTreeSet::new(),
+    cursor: 0,
+});
+
+/// Exclusive claim on a box ID, released on drop
+#[derive(Debug)]
+pub struct BoxLease {
+    box_id: u32,
+}
+
+impl BoxLease {
+    /// Lease a free box ID from this worker's range
+    pub fn acquire() -> Result<Self> {
+        let range = get_config().box_range();
+        let mut leases = LEASES.lock().unwrap();
+
+        let Some(box_id) = find_free(&leases.leased, &range, leases.cursor) else {
+            anyhow::bail!(
+                "All {} box IDs of this worker are in use",
+                range.end - range.start
+            );
+        };
+        leases.leased.insert(box_id);
+        // Rotate through the range so a just-released box is not reused right away
+        leases.cursor = box_id + 1;
+
+        Ok(Self { box_id })
+    }
+
+    /// The leased box ID
+    pub fn box_id(&self) -> u32 {
+        self.box_id
+    }
+}
+
+impl Drop for BoxLease {
+    fn drop(&mut self) {
+        LEASES.lock().unwrap().leased.remove(&self.box_id);
+    }
+}
+
+/// First box ID of `range` not in `leased`, searching from `cursor` and
+/// wrapping around
+fn find_free(leased: &BTreeSet<u32>, range: &Range<u32>, cursor: u32) -> Option<u32> {
+    let start = if range.contains(&cursor) {
+        cursor
+    } else {
+        range.start
+    };
+    (start..range.end)
+        .chain(range.start..start)
+        .find(|id| !leased.contains(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_free_wraps_and_skips_leased() {
+        let leased = BTreeSet::from([100, 101, 103]);
+        let range = 100..104;
+
+        assert_eq!(find_free(&leased, &range, 100), Some(102));
+        assert_eq!(find_free(&leased, &range, 103), Some(102));
+        assert_eq!(find_free(&leased, &range, 0), Some(102));
+        assert_eq!(
+            find_free(&BTreeSet::from([100, 101, 102, 103]), &range, 100),
+            None
+        );
+    }
+}
@@ -1,10 +1,173 @@
 //! Sandbox configuration
 //!
-//! Configuration for the isolate sandbox, loaded from environment or set dynamically.
+//! Configuration for the sandbox, loaded from environment or set dynamically.
 
+use anyhow::{bail, Context, Result};
+use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::OnceLock;
 use tracing::warn;
 
+/// Maximum number of testcases of one submission run at the same time
+pub const MAX_TESTCASE_CONCURRENCY: u32 = 9;
+
+/// Smallest box range that fits every box a worker may use at once
+/// (a testcase box and a checker box per parallel testcase, plus spares)
+const MIN_BOX_RANGE_SIZE: u32 = 2 * MAX_TESTCASE_CONCURRENCY + 2;
+
+/// Default `num_boxes` of our isolate build (see the judge Dockerfile)
+const DEFAULT_ISOLATE_NUM_BOXES: u32 = 10_000;
+
+/// Where isolate reads its config unless ISOLATE_CONFIG_FILE is set
+const DEFAULT_ISOLATE_CONFIG: &str = "/usr/local/etc/isolate";
+
+/// Default cgroup v2 directory the native backend creates its cgroups in
+const DEFAULT_CGROUP_ROOT: &str = "/sys/fs/cgroup/judge";
+
+/// Default path of the seccomp helper isolate boxes start language runs with
+const DEFAULT_SECCOMP_HELPER: &str = "/usr/local/bin/judge-seccomp";
+
+/// Sandbox implementation that runs programs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SandboxKind {
+    /// The external isolate binary
+    #[default]
+    Isolate,
+    /// Namespaces and cgroup v2 set up by the judge itself
+    Native,
+}
+
+impl SandboxKind {
+    /// Read JUDGE_SANDBOX_BACKEND (isolate or native, default isolate)
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("JUDGE_SANDBOX_BACKEND").as_deref() {
+            Err(_) | Ok("isolate") => Ok(Self::Isolate),
+            Ok("native") => Ok(Self::Native),
+            Ok(other) => bail!(
+                "JUDGE_SANDBOX_BACKEND must be isolate or native, got {:?}",
+                other
+            ),
+        }
+    }
+}
+
+/// How workers sharing a Redis instance split isolate's box IDs.
+///
+/// Every worker gets a contiguous range of `box_range_size` box IDs starting
+/// at 0. The `max_workers` box IDs right after the last range are cgroup probe
+/// boxes, one per worker, so `max_workers * (box_range_size + 1)` must fit in
+/// isolate's `num_boxes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerLayout {
+    /// Number of worker IDs that can be allocated
+    pub max_workers: u32,
+    /// Box IDs reserved per worker
+    pub box_range_size: u32,
+}
+
+impl Default for WorkerLayout {
+    fn default() -> Self {
+        Self {
+            max_workers: 10,
+            box_range_size: 999,
+        }
+    }
+}
+
+impl WorkerLayout {
+    /// Create a layout, checking it against isolate's number of boxes
+    pub fn new(max_workers: u32, box_range_size: u32, isolate_num_boxes: u32) -> Result<Self> {
+        if max_workers == 0 {
+            bail!("JUDGE_MAX_WORKERS must be at least 1");
+        }
+        if box_range_size < MIN_BOX_RANGE_SIZE {
+            bail!(
+                "JUDGE_BOX_RANGE_SIZE={} must be at least {}",
+                box_range_size,
+                MIN_BOX_RANGE_SIZE
+            );
+        }
+        match max_workers.checked_mul(box_range_size + 1) {
+            Some(total) if total <= isolate_num_boxes => {}
+            _ => bail!(
+                "{} workers x ({} boxes + 1 probe box) exceed isolate's num_boxes={}",
+                max_workers,
+                box_range_size,
+                isolate_num_boxes
+            ),
+        }
+
+        Ok(Self {
+            max_workers,
+            box_range_size,
+        })
+    }
+
+    /// Read the layout from JUDGE_MAX_WORKERS and JUDGE_BOX_RANGE_SIZE.
+    ///
+    /// isolate's number of boxes is read from its config file, the same one
+    /// isolate uses; ISOLATE_NUM_BOXES only applies where there is none.
+    pub fn from_env() -> Result<Self> {
+        let default = Self::default();
+        let env_num_boxes = env_u32("ISOLATE_NUM_BOXES")?;
+        let num_boxes = match isolate_num_boxes()? {
+            Some(num_boxes) => {
+                if env_num_boxes.is_some_and(|n| n != num_boxes) {
+                    warn!(
+                        "Ignoring ISOLATE_NUM_BOXES, isolate's config sets num_boxes={}",
+                        num_boxes
+                    );
+                }
+                num_boxes
+            }
+            None => env_num_boxes.unwrap_or(DEFAULT_ISOLATE_NUM_BOXES),
+        };
+        Self::new(
+            env_u32("JUDGE_MAX_WORKERS")?.unwrap_or(default.max_workers),
+            env_u32("JUDGE_BOX_RANGE_SIZE")?.unwrap_or(default.box_range_size),
+            num_boxes,
+        )
+    }
+}
+
+/// `num_boxes` from isolate's config file (ISOLATE_CONFIG_FILE, like isolate
+/// itself, or the default path), or `None` if there is no config file
+fn isolate_num_boxes() -> Result<Option<u32>> {
+    let path =
+        std::env::var("ISOLATE_CONFIG_FILE").unwrap_or_else(|_| DEFAULT_ISOLATE_CONFIG.into());
+    let config = match std::fs::read_to_string(&path) {
+        Ok(config) => config,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read isolate config {}", path)),
+    };
+    parse_num_boxes(&config)
+        .with_context(|| format!("No valid num_boxes in isolate config {}", path))
+        .map(Some)
+}
+
+/// Find `num_boxes = <n>` in the text of an isolate config file
+fn parse_num_boxes(config: &str) -> Option<u32> {
+    config.lines().find_map(|line| {
+        let line = line.split('#').next()?;
+        let (key, value) = line.split_once('=')?;
+        if key.trim() != "num_boxes" {
+            return None;
+        }
+        value.trim().parse().ok()
+    })
+}
+
+/// Read an optional numeric environment variable
+fn env_u32(name: &str) -> Result<Option<u32>> {
+    match std::env::var(name) {
+        Ok(value) => match value.parse() {
+            Ok(n) => Ok(Some(n)),
+            Err(_) => bail!("{} must be a non-negative integer, got {:?}", name, value),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
 /// Sandbox configuration
 #[derive(Debug, Clone)]
 pub struct SandboxConfig {
@@ -12,8 +175,24 @@ pub struct SandboxConfig {
     pub compile_time_limit_ms: u32,
     /// Compile memory limit in MB (default: 2048MB)
     pub compile_memory_limit_mb: u32,
-    /// Worker ID for box ID allocation (0-9)
+    /// Worker ID for box ID allocation (0 to max_workers-1)
     pub worker_id: u32,
+    /// How box IDs are split between workers
+    pub layout: WorkerLayout,
+    /// Run checkers, validators and interactors (and their compilation)
+    /// directly on the host instead of inside isolate (default: false)
+    pub trusted_tools: bool,
+    /// Number of testcases of one submission run at the same time (default: 1)
+    pub testcase_concurrency: u32,
+    /// Pin each parallel testcase slot to its own CPU (default: false)
+    pub cpu_pinning: bool,
+    /// Sandbox implementation (default: isolate)
+    pub backend: SandboxKind,
+    /// cgroup v2 directory of the native backend (default: /sys/fs/cgroup/judge)
+    pub cgroup_root: PathBuf,
+    /// `judge-seccomp` binary, which must be visible inside isolate boxes
+    /// (default: /usr/local/bin/judge-seccomp)
+    pub seccomp_helper: PathBuf,
 }
 
 impl Default for SandboxConfig {
@@ -22,16 +201,50 @@ impl Default for SandboxConfig {
             compile_time_limit_ms: 30_000,
             compile_memory_limit_mb: 2048,
             worker_id: 0,
+            layout: WorkerLayout::default(),
+            trusted_tools: false,
+            testcase_concurrency: 1,
+            cpu_pinning: false,
+            backend: SandboxKind::default(),
+            cgroup_root: PathBuf::from(DEFAULT_CGROUP_ROOT),
+            seccomp_helper: PathBuf::from(DEFAULT_SECCOMP_HELPER),
         }
     }
 }
 
 impl SandboxConfig {
-    /// Create config with a specific worker_id
-    pub fn with_worker_id(worker_id: u32) -> Self {
-        let mut config = Self::default();
-        config.worker_id = worker_id;
-        config
+    /// Create config with a specific worker_id and box layout
+    pub fn with_worker_id(worker_id: u32, layout: &WorkerLayout) -> Self {
+        Self {
+            worker_id,
+            layout: *layout,
+            ..Self::default()
+        }
+    }
+
+    /// Box IDs this worker may hand out
+    pub fn box_range(&self) -> Range<u32> {
+        let start = self.worker_id * self.layout.box_range_size;
+        start..start + self.layout.box_range_size
+    }
+
+    /// Box ID used to probe cgroup support, outside every worker's range
+    pub fn probe_box_id(&self) -> u32 {
+        self.layout.max_workers * self.layout.box_range_size + self.worker_id
+    }
+
+    /// CPU a parallel testcase slot is pinned to, if pinning is enabled.
+    ///
+    /// Workers on the same host get consecutive CPU ranges so their slots
+    /// don't share cores as long as there are enough of them.
+    pub fn slot_cpu(&self, slot: u32) -> Option<usize> {
+        if !self.cpu_pinning {
+            return None;
+        }
+        let cpus = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        Some((self.worker_id * self.testcase_concurrency + slot) as usize % cpus)
     }
 }
 
@@ -39,8 +252,39 @@ impl SandboxConfig {
 static SANDBOX_CONFIG: OnceLock<SandboxConfig> = OnceLock::new();
 
 /// Initialize sandbox configuration with dynamically assigned worker_id
-pub fn init_config(worker_id: u32) -> anyhow::Result<()> {
-    let sandbox_config = SandboxConfig::with_worker_id(worker_id);
+pub fn init_config(worker_id: u32, layout: &WorkerLayout) -> anyhow::Result<()> {
+    let mut sandbox_config = SandboxConfig::with_worker_id(worker_id, layout);
+    sandbox_config.trusted_tools = std::env::var("JUDGE_TRUSTED_TOOLS")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false);
+
+    sandbox_config.cpu_pinning = std::env::var("JUDGE_CPU_PINNING")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false);
+
+    sandbox_config.backend = SandboxKind::from_env()?;
+    if let Ok(root) = std::env::var("JUDGE_CGROUP_ROOT") {
+        sandbox_config.cgroup_root = PathBuf::from(root);
+    }
+    if let Ok(helper) = std::env::var("JUDGE_SECCOMP_HELPER") {
+        sandbox_config.seccomp_helper = PathBuf::from(helper);
+    }
+
+    let concurrency = std::env::var("JUDGE_TESTCASE_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(1);
+    if !(1..=MAX_TESTCASE_CONCURRENCY).contains(&concurrency) {
+        warn!(
+            "JUDGE_TESTCASE_CONCURRENCY={} is out of range, clamping to 1..={}",
+            concurrency, MAX_TESTCASE_CONCURRENCY
+        );
+    }
+    sandbox_config.testcase_concurrency = concurrency.clamp(1, MAX_TESTCASE_CONCURRENCY);
+
+    if sandbox_config.trusted_tools {
+        warn!("JUDGE_TRUSTED_TOOLS is enabled: checkers and validators run unsandboxed");
+    }
 
     SANDBOX_CONFIG
         .set(sandbox_config)
@@ -59,14 +303,41 @@ pub fn get_config() -> &'static SandboxConfig {
     })
 }
 
-/// Calculate unique box ID for a worker to prevent collisions.
-/// Isolate only supports box IDs 0-9999, so we use modulo to stay in range.
-/// Each worker (0-9) gets a range of 1000 box IDs.
-pub fn calculate_box_id(base_counter: u32, testcase_idx: u32) -> u32 {
-    let config = get_config();
-    // Limit worker_id to 0-9 range (10 workers max for box ID allocation)
-    let effective_worker_id = config.worker_id % 10;
-    let worker_offset = effective_worker_id * 1000;
-    // Use modulo to cycle within worker's range (0-999)
-    worker_offset + ((base_counter * 10 + testcase_idx) % 1000)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_layout_fits_isolate_boxes() {
+        assert!(WorkerLayout::new(10, 999, 10_000).is_ok());
+        assert!(WorkerLayout::new(50, 199, 10_000).is_ok());
+        assert!(WorkerLayout::new(10, 1000, 10_000).is_err());
+        assert!(WorkerLayout::new(0, 999, 10_000).is_err());
+        assert!(WorkerLayout::new(10, 10, 10_000).is_err());
+        assert!(WorkerLayout::new(u32::MAX, 999, 10_000).is_err());
+    }
+
+    #[test]
+    fn test_probe_boxes_are_outside_worker_ranges() {
+        let layout = WorkerLayout::new(10, 999, 10_000).unwrap();
+        let first = SandboxConfig::with_worker_id(0, &layout);
+        let last = SandboxConfig::with_worker_id(9, &layout);
+
+        assert_eq!(first.box_range(), 0..999);
+        assert_eq!(last.box_range(), 8991..9990);
+        assert_eq!(first.probe_box_id(), 9990);
+        assert_eq!(last.probe_box_id(), 9999);
+    }
+
+    #[test]
+    fn test_parse_num_boxes_from_isolate_config() {
+        let config = "\
+# num_boxes = 5
+box_root = /var/local/lib/isolate
+num_boxes = 10000 # raised for multiple workers
+first_uid = 60000
+";
+        assert_eq!(parse_num_boxes(config), Some(10_000));
+        assert_eq!(parse_num_boxes("box_root = /tmp\n"), None);
+    }
 }
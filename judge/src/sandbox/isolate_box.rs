@@ -3,33 +3,50 @@
 //! Low-level wrapper around the isolate sandbox for secure code execution.
 //! Manages box initialization, cleanup, and command execution.
 
-use anyhow::{Context, Result};
-use std::path::Path;
-use std::sync::OnceLock;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
 use tokio::fs;
 use tokio::process::Command;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
+use super::backend::{
+    program_path, read_stdout, DirBind, IoSpec, Limits, SandboxBackend, SandboxOutcome, SANDBOX_ENV,
+};
+use super::box_lease::BoxLease;
+use super::config::get_config;
 use super::meta::{parse_meta, IsolateMeta, IsolateStatus};
+use super::seccomp::SeccompProfile;
+use crate::metrics::{metrics, Timing};
 
 /// Cached cgroup availability
 static USE_CGROUPS: OnceLock<bool> = OnceLock::new();
 
+/// Boxes initialized by this process and not cleaned up yet
+static ACTIVE_BOXES: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
 /// Check if isolate cgroups are available
 pub async fn is_cgroups_available() -> bool {
     if let Some(value) = USE_CGROUPS.get() {
         return *value;
     }
 
-    // Try to initialize a test box with cgroups
+    // Try to initialize a test box with cgroups. The probe box lies outside
+    // every worker's range, so it never clashes with a running submission.
+    let probe_box_id = get_config().probe_box_id().to_string();
     let test_result = Command::new("isolate")
-        .args(["--box-id", "99", "--cg", "--init"])
+        .args(["--box-id", &probe_box_id, "--cg", "--init"])
         .output()
         .await;
 
     // Cleanup
     let _ = Command::new("isolate")
-        .args(["--box-id", "99", "--cleanup"])
+        .args(["--box-id", &probe_box_id, "--cleanup"])
         .output()
         .await;
 
@@ -51,82 +68,24 @@ pub async fn ensure_cgroups_available() -> Result<()> {
     }
 }
 
-/// I/O specification for sandbox execution
-#[derive(Debug, Default, Clone)]
-pub struct IoSpec {
-    /// Path to stdin file (will be copied into box)
-    pub stdin_path: Option<std::path::PathBuf>,
-    /// File name for stdout inside the box
-    pub stdout_file: String,
-    /// File name for stderr inside the box
-    pub stderr_file: String,
-}
-
-impl IoSpec {
-    pub fn new() -> Self {
-        Self {
-            stdin_path: None,
-            stdout_file: "stdout.txt".to_string(),
-            stderr_file: "stderr.txt".to_string(),
-        }
-    }
-
-    pub fn with_stdin(mut self, path: impl AsRef<Path>) -> Self {
-        self.stdin_path = Some(path.as_ref().to_path_buf());
-        self
-    }
-}
-
-/// Resource limits for sandbox execution
-#[derive(Debug, Clone)]
-pub struct Limits {
-    /// Time limit in milliseconds
-    pub time_ms: u32,
-    /// Memory limit in MB
-    pub memory_mb: u32,
-    /// Maximum number of processes
-    pub processes: u32,
-    /// Maximum open files
-    pub open_files: u32,
-    /// Maximum file size in KB
-    pub fsize_kb: u32,
-}
-
-impl Default for Limits {
-    fn default() -> Self {
-        Self {
-            time_ms: 1000,
-            memory_mb: 256,
-            processes: 64,
-            open_files: 256,
-            fsize_kb: 262144, // 256MB
-        }
-    }
-}
-
-/// Raw outcome from sandbox execution (no verdict interpretation)
-#[derive(Debug)]
-pub struct SandboxOutcome {
-    /// Parsed meta file contents
-    pub meta: IsolateMeta,
-    /// Stdout content (as string, may have UTF-8 conversion losses)
-    pub stdout: String,
-    /// Stdout content (as raw bytes, preserves binary data)
-    pub stdout_bytes: Vec<u8>,
-    /// Stderr content (if not redirected to stdout)
-    pub stderr: String,
-}
-
 /// Isolate box manager
 pub struct IsolateBox {
     box_id: u32,
+    /// Keeps the box ID reserved until the box is cleaned up or dropped
+    _lease: BoxLease,
     box_path: String,
     use_cgroups: bool,
+    /// CPU that runs are pinned to (via taskset)
+    cpu: Option<usize>,
 }
 
 impl IsolateBox {
-    /// Create and initialize a new isolate box
-    pub async fn new(box_id: u32, use_cgroups: bool) -> Result<Self> {
+    /// Lease a box ID and initialize a new isolate box on it
+    pub async fn new(use_cgroups: bool) -> Result<Self> {
+        let lease = BoxLease::acquire()?;
+        let box_id = lease.box_id();
+        let started = Instant::now();
+
         // Clean up any existing box
         let _ = Command::new("isolate")
             .args(["--box-id", &box_id.to_string(), "--cleanup"])
@@ -151,6 +110,9 @@ impl IsolateBox {
             anyhow::bail!("Failed to initialize isolate box: {}", stderr);
         }
 
+        ACTIVE_BOXES.lock().unwrap().insert(box_id);
+        metrics().observe(Timing::IsolateInit, started.elapsed());
+
         let box_path = String::from_utf8_lossy(&output.stdout).trim().to_string();
         info!(
             "Initialized isolate box {} at {} (cgroups: {})",
@@ -159,8 +121,10 @@ impl IsolateBox {
 
         Ok(Self {
             box_id,
+            _lease: lease,
             box_path,
             use_cgroups,
+            cpu: None,
         })
     }
 
@@ -174,66 +138,30 @@ impl IsolateBox {
         &self.box_path
     }
 
-    /// Get the path to the box/box subdirectory (working directory for programs)
-    pub fn work_dir(&self) -> String {
-        format!("{}/box", self.box_path)
-    }
-
-    /// Copy a file into the box's working directory
-    pub async fn copy_in(&self, source: &Path, dest_name: &str) -> Result<()> {
-        let dest = format!("{}/{}", self.work_dir(), dest_name);
-        fs::copy(source, &dest)
-            .await
-            .with_context(|| format!("Failed to copy {:?} to {}", source, dest))?;
-        Ok(())
+    /// Path of a file in the box's working directory
+    fn box_file(&self, name: &str) -> String {
+        format!("{}/box/{}", self.box_path, name)
     }
 
-    /// Copy a directory's contents into the box's working directory
-    pub async fn copy_dir_in(&self, source_dir: &Path) -> Result<()> {
-        let mut entries = fs::read_dir(source_dir).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let metadata = entry.metadata().await?;
-            // Skip directories, only copy regular files
-            if metadata.is_dir() {
-                continue;
+    /// `isolate` command for `--run`, wrapped in taskset when the box is pinned
+    fn isolate_command(&self) -> Command {
+        match self.cpu {
+            Some(cpu) => {
+                let mut command = Command::new("taskset");
+                command.args(["--cpu-list", &cpu.to_string(), "isolate"]);
+                command
             }
-            let dest = format!(
-                "{}/{}",
-                self.work_dir(),
-                entry.file_name().to_string_lossy()
-            );
-            fs::copy(entry.path(), &dest).await?;
+            None => Command::new("isolate"),
         }
-        Ok(())
     }
 
-    /// Copy a file out of the box's working directory
-    pub async fn copy_out(&self, source_name: &str, dest: &Path) -> Result<()> {
-        let source = format!("{}/{}", self.work_dir(), source_name);
-        fs::copy(&source, dest)
-            .await
-            .with_context(|| format!("Failed to copy {} to {:?}", source, dest))?;
-        Ok(())
+    /// Path of the meta file isolate writes for this box
+    fn meta_file(&self) -> String {
+        format!("/tmp/isolate_meta_{}.txt", self.box_id)
     }
 
-    /// Read a file from the box's working directory
-    pub async fn read_file(&self, name: &str) -> Result<String> {
-        let path = format!("{}/{}", self.work_dir(), name);
-        fs::read_to_string(&path)
-            .await
-            .with_context(|| format!("Failed to read {}", path))
-    }
-
-    /// Run a command in the isolate box
-    pub async fn run(
-        &self,
-        command: &[String],
-        limits: &Limits,
-        io: &IoSpec,
-    ) -> Result<SandboxOutcome> {
-        let meta_file = format!("/tmp/isolate_meta_{}.txt", self.box_id);
-        let stdout_path = format!("{}/{}", self.work_dir(), io.stdout_file);
-
+    /// Build the isolate arguments shared by every run: limits, mounts and environment
+    fn limit_args(&self, limits: &Limits, meta_file: &str) -> Vec<String> {
         let time_limit_secs = (limits.time_ms as f64) / 1000.0;
         let wall_time_secs = time_limit_secs * 2.0 + 1.0;
         let memory_limit_kb = limits.memory_mb * 1024;
@@ -250,7 +178,6 @@ impl IsolateBox {
             format!("--time={}", time_limit_secs),
             format!("--wall-time={}", wall_time_secs),
             format!("--meta={}", meta_file),
-            format!("--stdout={}", io.stdout_file),
             format!("--processes={}", limits.processes),
             format!("--open-files={}", limits.open_files),
             format!("--fsize={}", limits.fsize_kb),
@@ -260,62 +187,36 @@ impl IsolateBox {
             "--dir=/lib64".to_string(),
             "--dir=/etc:noexec".to_string(),
             "--dir=/tmp:tmp".to_string(),
-            // Environment variables
-            "--env=PATH=/usr/local/bin:/usr/bin:/bin".to_string(),
-            "--env=HOME=/box".to_string(),
-            "--env=JAVA_HOME=/usr/lib/jvm/java-17-openjdk-amd64".to_string(),
-            "--env=LANG=en_US.UTF-8".to_string(),
-            "--env=LC_ALL=en_US.UTF-8".to_string(),
-            "--env=LANGUAGE=en_US:en".to_string(),
-            "--env=JAVA_TOOL_OPTIONS=-Dfile.encoding=UTF-8".to_string(),
         ]);
+        args.extend(
+            SANDBOX_ENV
+                .iter()
+                .map(|(name, value)| format!("--env={}={}", name, value)),
+        );
 
-        args.push(format!("--stderr={}", io.stderr_file));
-
-        // Handle stdin
-        if let Some(stdin_path) = &io.stdin_path {
-            let dest = format!("{}/stdin.txt", self.work_dir());
-            fs::copy(stdin_path, &dest).await?;
-            args.push("--stdin=stdin.txt".to_string());
-        }
-
-        args.push("--run".to_string());
-        args.push("--".to_string());
-
-        // Prepend /usr/bin/ to the command if it's not an absolute path
-        let mut cmd_iter = command.iter();
-        if let Some(cmd) = cmd_iter.next() {
-            if cmd.starts_with('/') || cmd.starts_with("./") {
-                args.push(cmd.clone());
-            } else {
-                args.push(format!("/usr/bin/{}", cmd));
-            }
-            args.extend(cmd_iter.cloned());
-        }
-
-        debug!("Running isolate with args: {:?}", args);
-
-        let _output = Command::new("isolate")
-            .args(&args)
-            .output()
-            .await
-            .context("Failed to run isolate")?;
+        args
+    }
 
+    /// Parse the meta file and stderr of a finished run
+    async fn collect_outcome(
+        &self,
+        meta_file: &str,
+        stdout_bytes: Vec<u8>,
+        stderr_file: &str,
+    ) -> Result<SandboxOutcome> {
         // Parse meta file
-        let meta_content = fs::read_to_string(&meta_file).await.unwrap_or_default();
+        let meta_content = fs::read_to_string(meta_file).await.unwrap_or_default();
         let meta = parse_meta(&meta_content);
 
-        // Read stdout as bytes (preserves binary data)
-        let stdout_bytes = fs::read(&stdout_path).await.unwrap_or_default();
         // Convert to string for text output (may have UTF-8 conversion losses for binary)
         let stdout = String::from_utf8_lossy(&stdout_bytes).to_string();
 
         // Read stderr
-        let stderr_path = format!("{}/{}", self.work_dir(), io.stderr_file);
+        let stderr_path = self.box_file(stderr_file);
         let stderr = fs::read_to_string(&stderr_path).await.unwrap_or_default();
 
         // Cleanup meta file
-        let _ = fs::remove_file(&meta_file).await;
+        let _ = fs::remove_file(meta_file).await;
 
         Ok(SandboxOutcome {
             meta,
@@ -324,16 +225,165 @@ impl IsolateBox {
             stderr,
         })
     }
+}
+
+#[async_trait]
+impl SandboxBackend for IsolateBox {
+    fn work_dir(&self) -> PathBuf {
+        PathBuf::from(format!("{}/box", self.box_path))
+    }
+
+    /// Pin every following run of this box to a single CPU
+    fn pin_to_cpu(&mut self, cpu: usize) {
+        self.cpu = Some(cpu);
+    }
+
+    /// Run a command in the isolate box
+    async fn run(
+        &self,
+        command: &[String],
+        limits: &Limits,
+        io: &IoSpec,
+    ) -> Result<SandboxOutcome> {
+        let meta_file = self.meta_file();
+        let stdout_path = self.box_file(&io.stdout_file);
+
+        let mut args = self.limit_args(limits, &meta_file);
+        args.push(format!("--stdout={}", io.stdout_file));
+        args.push(format!("--stderr={}", io.stderr_file));
+        args.extend(io.binds.iter().map(bind_arg));
+
+        // Handle stdin
+        if let Some(stdin_path) = &io.stdin_path {
+            let dest = self.box_file("stdin.txt");
+            fs::copy(stdin_path, &dest).await?;
+            args.push("--stdin=stdin.txt".to_string());
+        }
 
-    /// Cleanup the isolate box
-    pub async fn cleanup(self) -> Result<()> {
-        Command::new("isolate")
-            .args(["--box-id", &self.box_id.to_string(), "--cleanup"])
+        args.extend(command_args(command, limits.seccomp)?);
+
+        debug!("Running isolate with args: {:?}", args);
+
+        // Dropping the run (e.g. on cancellation) kills isolate and the program
+        let _output = self
+            .isolate_command()
+            .args(&args)
+            .kill_on_drop(true)
             .output()
+            .await
+            .context("Failed to run isolate")?;
+
+        // Read stdout as bytes (preserves binary data)
+        let stdout_bytes = read_stdout(Path::new(&stdout_path), limits).await;
+
+        let mut outcome = self
+            .collect_outcome(&meta_file, stdout_bytes, &io.stderr_file)
             .await?;
-        info!("Cleaned up isolate box {}", self.box_id);
-        Ok(())
+        outcome.check_output_limit(limits);
+
+        Ok(outcome)
     }
+
+    /// Run a command in the isolate box with its stdin and stdout attached to
+    /// the given handles instead of files (e.g. pipes to an interactor).
+    ///
+    /// The returned outcome has empty stdout since it never touches disk.
+    async fn run_with_stdio(
+        &self,
+        command: &[String],
+        limits: &Limits,
+        stderr_file: &str,
+        binds: &[DirBind],
+        stdin: OwnedFd,
+        stdout: OwnedFd,
+    ) -> Result<SandboxOutcome> {
+        let meta_file = self.meta_file();
+
+        let mut args = self.limit_args(limits, &meta_file);
+        args.push(format!("--stderr={}", stderr_file));
+        args.extend(binds.iter().map(bind_arg));
+        args.extend(command_args(command, limits.seccomp)?);
+
+        debug!("Running isolate with piped stdio, args: {:?}", args);
+
+        let mut isolate = self.isolate_command();
+        isolate
+            .args(&args)
+            .stdin(Stdio::from(stdin))
+            .stdout(Stdio::from(stdout))
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        let child = isolate.spawn().context("Failed to run isolate")?;
+        // Release our copies of the pipe ends so the peer sees EOF when the program exits
+        drop(isolate);
+
+        child
+            .wait_with_output()
+            .await
+            .context("Failed to wait for isolate")?;
+
+        self.collect_outcome(&meta_file, Vec::new(), stderr_file)
+            .await
+    }
+
+    /// Cleanup the isolate box; its box ID is released afterwards
+    async fn cleanup(self: Box<Self>) -> Result<()> {
+        cleanup_box(self.box_id).await
+    }
+}
+
+/// Cleanup every box this process initialized and has not cleaned up yet,
+/// e.g. boxes of a job that was abandoned during shutdown
+pub async fn cleanup_active_boxes() {
+    let box_ids = std::mem::take(&mut *ACTIVE_BOXES.lock().unwrap());
+    for box_id in box_ids {
+        if let Err(e) = cleanup_box(box_id).await {
+            warn!("Failed to clean up isolate box {}: {:#}", box_id, e);
+        }
+    }
+}
+
+async fn cleanup_box(box_id: u32) -> Result<()> {
+    let started = Instant::now();
+    Command::new("isolate")
+        .args(["--box-id", &box_id.to_string(), "--cleanup"])
+        .output()
+        .await?;
+    ACTIVE_BOXES.lock().unwrap().remove(&box_id);
+    metrics().observe(Timing::IsolateCleanup, started.elapsed());
+    info!("Cleaned up isolate box {}", box_id);
+    Ok(())
+}
+
+/// isolate `--dir` argument mounting a bind read-only
+fn bind_arg(bind: &DirBind) -> String {
+    format!("--dir={}={}", bind.inside, bind.host.display())
+}
+
+/// Build the `--run -- <command>` tail of the isolate arguments. Runs under
+/// a language profile start through the seccomp helper, which installs the
+/// filters and then executes the command.
+fn command_args(command: &[String], seccomp: SeccompProfile) -> Result<Vec<String>> {
+    let mut args = vec!["--run".to_string(), "--".to_string()];
+
+    let mut cmd_iter = command.iter();
+    if let Some(cmd) = cmd_iter.next() {
+        if seccomp != SeccompProfile::Base {
+            let helper = &get_config().seccomp_helper;
+            if !helper.is_file() {
+                bail!(
+                    "Seccomp helper {} not found; install judge-seccomp there or set JUDGE_SECCOMP_HELPER",
+                    helper.display()
+                );
+            }
+            args.push(helper.display().to_string());
+            args.push(seccomp.to_string());
+        }
+        args.push(program_path(cmd));
+        args.extend(cmd_iter.cloned());
+    }
+
+    Ok(args)
 }
 
 /// Check if program exited successfully (for use after run)
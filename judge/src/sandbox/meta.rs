@@ -2,6 +2,9 @@
 //!
 //! Parses the meta file output from isolate to extract execution results.
 
+/// Signal delivered when a process exceeds `--fsize`
+const SIGXFSZ: i32 = 25;
+
 /// Raw execution status from isolate
 #[derive(Debug, Clone, PartialEq)]
 pub enum IsolateStatus {
@@ -13,6 +16,8 @@ pub enum IsolateStatus {
     Signal(i32),
     /// Runtime error (non-zero exit)
     RuntimeError,
+    /// Output file size limit exceeded (killed by SIGXFSZ)
+    OutputLimitExceeded,
     /// Internal error in isolate
     InternalError,
 }
@@ -84,9 +89,11 @@ pub fn parse_meta(content: &str) -> IsolateMeta {
                 meta.exit_code = value.parse().unwrap_or(0);
             }
             "exitsig" => {
-                if let Ok(sig) = value.parse::<i32>() {
-                    meta.status = IsolateStatus::Signal(sig);
-                }
+                meta.status = match value.parse::<i32>() {
+                    Ok(SIGXFSZ) => IsolateStatus::OutputLimitExceeded,
+                    Ok(sig) => IsolateStatus::Signal(sig),
+                    Err(_) => meta.status,
+                };
             }
             _ => {}
         }
@@ -140,4 +147,12 @@ mod tests {
 
         assert_eq!(meta.status, IsolateStatus::Signal(11));
     }
+
+    #[test]
+    fn test_parse_meta_output_limit() {
+        let content = "status:SG\nexitsig:25\n";
+        let meta = parse_meta(content);
+
+        assert_eq!(meta.status, IsolateStatus::OutputLimitExceeded);
+    }
 }
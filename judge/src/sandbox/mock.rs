@@ -0,0 +1,249 @@
+//! Scripted in-process sandbox for tests
+//!
+//! [`MockSandbox`] hands out boxes that never execute anything: every run
+//! asks a script for its outcome, so judging logic can be tested without
+//! root or isolate. Limits are applied to the scripted numbers the same way
+//! isolate would report them, e.g. a run scripted to take longer than the
+//! time limit comes back as a timeout.
+//!
+//! ```ignore
+//! let sandbox = Arc::new(MockSandbox::new(|run: &MockCall| match run.program() {
+//!     "./Main" => MockRun::stdout("3\n").with_time(12),
+//!     _ => MockRun::exited(0),
+//! }));
+//! let result = sandbox::with_provider(sandbox.clone(), process_judge_job(...)).await;
+//! ```
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::os::fd::OwnedFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::backend::{BoxProvider, DirBind, IoSpec, Limits, SandboxBackend, SandboxOutcome};
+use super::meta::{IsolateMeta, IsolateStatus};
+
+/// A run as the script sees it
+#[derive(Debug, Clone)]
+pub struct MockCall {
+    pub command: Vec<String>,
+    /// Contents of the stdin file, if the run had one
+    pub stdin: Option<String>,
+    pub limits: Limits,
+    /// The box's working directory, for scripts that act on its files
+    pub work_dir: PathBuf,
+}
+
+impl MockCall {
+    /// The program being run (first word of the command)
+    pub fn program(&self) -> &str {
+        self.command.first().map(String::as_str).unwrap_or_default()
+    }
+}
+
+/// Scripted outcome of a run
+#[derive(Debug, Clone, Default)]
+pub struct MockRun {
+    pub exit_code: i32,
+    /// Signal that killed the program, if any
+    pub signal: Option<i32>,
+    pub time_ms: u32,
+    pub memory_kb: u32,
+    pub stdout: Vec<u8>,
+    pub stderr: String,
+}
+
+impl MockRun {
+    /// A run that exits with `exit_code` and prints nothing
+    pub fn exited(exit_code: i32) -> Self {
+        Self {
+            exit_code,
+            ..Self::default()
+        }
+    }
+
+    /// A successful run printing `stdout`
+    pub fn stdout(stdout: impl Into<Vec<u8>>) -> Self {
+        Self {
+            stdout: stdout.into(),
+            ..Self::default()
+        }
+    }
+
+    /// A run killed by `signal`
+    pub fn signaled(signal: i32) -> Self {
+        Self {
+            signal: Some(signal),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_time(mut self, time_ms: u32) -> Self {
+        self.time_ms = time_ms;
+        self
+    }
+
+    pub fn with_memory(mut self, memory_kb: u32) -> Self {
+        self.memory_kb = memory_kb;
+        self
+    }
+
+    pub fn with_stderr(mut self, stderr: impl Into<String>) -> Self {
+        self.stderr = stderr.into();
+        self
+    }
+
+    /// What isolate would report for this run under `limits`
+    fn into_outcome(self, limits: &Limits) -> SandboxOutcome {
+        let output_limit = limits.fsize_kb as usize * 1024;
+        let status = if self.time_ms > limits.time_ms {
+            IsolateStatus::TimeOut
+        } else if self.stdout.len() >= output_limit {
+            IsolateStatus::OutputLimitExceeded
+        } else if let Some(signal) = self.signal {
+            IsolateStatus::Signal(signal)
+        } else if self.exit_code != 0 {
+            IsolateStatus::RuntimeError
+        } else {
+            IsolateStatus::Ok
+        };
+
+        let mut stdout_bytes = self.stdout;
+        stdout_bytes.truncate(output_limit);
+        SandboxOutcome {
+            meta: IsolateMeta {
+                time_ms: self.time_ms,
+                memory_kb: self.memory_kb,
+                exit_code: self.exit_code,
+                status,
+                wall_time_ms: self.time_ms,
+            },
+            stdout: String::from_utf8_lossy(&stdout_bytes).to_string(),
+            stdout_bytes,
+            stderr: self.stderr,
+        }
+    }
+}
+
+type Script = dyn Fn(&MockCall) -> MockRun + Send + Sync;
+
+/// Provider of scripted boxes; install it with
+/// [`with_provider`](super::with_provider)
+pub struct MockSandbox {
+    script: Arc<Script>,
+    calls: Arc<Mutex<Vec<MockCall>>>,
+    /// IDs of the boxes created and not cleaned up yet
+    open: Arc<Mutex<BTreeSet<usize>>>,
+    next_id: AtomicUsize,
+}
+
+impl MockSandbox {
+    /// Answer every run with `script`
+    pub fn new(script: impl Fn(&MockCall) -> MockRun + Send + Sync + 'static) -> Self {
+        Self {
+            script: Arc::new(script),
+            calls: Arc::new(Mutex::new(Vec::new())),
+            open: Arc::new(Mutex::new(BTreeSet::new())),
+            next_id: AtomicUsize::new(0),
+        }
+    }
+
+    /// Every run so far, in order
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Boxes created and not cleaned up yet
+    pub fn open_boxes(&self) -> usize {
+        self.open.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl BoxProvider for MockSandbox {
+    async fn create_box(&self) -> Result<Box<dyn SandboxBackend>> {
+        let dir = tempfile::tempdir()?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.open.lock().unwrap().insert(id);
+        Ok(Box::new(MockBox {
+            id,
+            dir,
+            script: self.script.clone(),
+            calls: self.calls.clone(),
+            open: self.open.clone(),
+        }))
+    }
+
+    async fn cleanup_active_boxes(&self) {
+        self.open.lock().unwrap().clear();
+    }
+}
+
+/// A box of [`MockSandbox`], backed by a temporary directory
+pub struct MockBox {
+    id: usize,
+    dir: tempfile::TempDir,
+    script: Arc<Script>,
+    calls: Arc<Mutex<Vec<MockCall>>>,
+    open: Arc<Mutex<BTreeSet<usize>>>,
+}
+
+impl MockBox {
+    fn call(&self, command: &[String], limits: &Limits, stdin: Option<String>) -> MockRun {
+        let call = MockCall {
+            command: command.to_vec(),
+            stdin,
+            limits: limits.clone(),
+            work_dir: self.dir.path().to_path_buf(),
+        };
+        let run = (self.script)(&call);
+        self.calls.lock().unwrap().push(call);
+        run
+    }
+}
+
+#[async_trait]
+impl SandboxBackend for MockBox {
+    fn work_dir(&self) -> PathBuf {
+        self.dir.path().to_path_buf()
+    }
+
+    fn pin_to_cpu(&mut self, _cpu: usize) {}
+
+    async fn run(
+        &self,
+        command: &[String],
+        limits: &Limits,
+        io: &IoSpec,
+    ) -> Result<SandboxOutcome> {
+        let stdin = match &io.stdin_path {
+            Some(path) => Some(tokio::fs::read_to_string(path).await?),
+            None => None,
+        };
+        let outcome = self.call(command, limits, stdin).into_outcome(limits);
+        tokio::fs::write(self.dir.path().join(&io.stdout_file), &outcome.stdout_bytes).await?;
+        Ok(outcome)
+    }
+
+    async fn run_with_stdio(
+        &self,
+        command: &[String],
+        limits: &Limits,
+        _stderr_file: &str,
+        _binds: &[DirBind],
+        _stdin: OwnedFd,
+        _stdout: OwnedFd,
+    ) -> Result<SandboxOutcome> {
+        let mut outcome = self.call(command, limits, None).into_outcome(limits);
+        outcome.stdout.clear();
+        outcome.stdout_bytes.clear();
+        Ok(outcome)
+    }
+
+    async fn cleanup(self: Box<Self>) -> Result<()> {
+        self.open.lock().unwrap().remove(&self.id);
+        Ok(())
+    }
+}
@@ -2,8 +2,11 @@
 //!
 //! This module provides a minimal abstraction over the isolate sandbox.
 //! It handles:
-//! - Isolate box initialization and cleanup
+//! - The box backend trait, with isolate, a native namespace sandbox and a
+//!   scripted mock for tests
+//! - Box ID leasing, isolate box initialization and cleanup
 //! - Cgroup detection and configuration
+//! - Seccomp profiles restricting the syscalls of a run
 //! - File copy in/out helpers
 //! - Raw command execution returning `SandboxOutcome`
 //!
@@ -12,13 +15,22 @@
 //! - Know about languages or compilation
 //! - Compare outputs
 
+pub mod backend;
+pub mod box_lease;
 pub mod config;
 pub mod isolate_box;
 pub mod meta;
+pub mod mock;
+pub mod native;
+pub mod seccomp;
 
 // Re-exports for convenience
-pub use config::{calculate_box_id, get_config, init_config, SandboxConfig};
-pub use isolate_box::{
-    ensure_cgroups_available, is_cgroups_available, IoSpec, IsolateBox, Limits, SandboxOutcome,
+pub use backend::{
+    cleanup_active_boxes, create_box, ensure_cgroups_available, is_cgroups_available,
+    with_provider, BoxProvider, DirBind, IoSpec, Limits, SandboxBackend, SandboxOutcome,
 };
+pub use config::{get_config, init_config, SandboxConfig, SandboxKind, WorkerLayout};
+pub use isolate_box::IsolateBox;
 pub use meta::{IsolateMeta, IsolateStatus};
+pub use native::NativeBox;
+pub use seccomp::SeccompProfile;
@@ -0,0 +1,1157 @@
+//! Native sandbox backend
+//!
+//! Runs programs without the isolate binary, using namespaces and cgroup v2
+//! directly. Every run forks twice:
+//!
+//! 1. The first child joins a fresh cgroup v2 leaf (memory and pids limits)
+//!    and unshares user, mount, pid, net, IPC and UTS namespaces. The judge
+//!    then maps the box's uid and gid into the new user namespace.
+//! 2. The second child is pid 1 of the new pid namespace. It mounts the box
+//!    filesystem (read-only system directories, the box directory at /box, a
+//!    fresh /proc and /tmp), pivots into it and forks the program.
+//! 3. The program applies rlimits, drops to the box's uid, installs the
+//!    filters of its seccomp profile and calls `execve`.
+//!
+//! Each child reports how its own child ended, or which setup step failed.
+//! CPU time and peak memory come from the cgroup, and everything is reported
+//! as an [`IsolateMeta`] so verdicts don't depend on the backend.
+//!
+//! Between `fork` and `execve` the children only make async-signal-safe calls;
+//! every path, argument and limit is prepared by the judge beforehand.
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::mount::{mount, umount2, MntFlags, MsFlags};
+use nix::sched::{sched_setaffinity, unshare, CloneFlags, CpuSet};
+use nix::sys::prctl;
+use nix::sys::resource::{setrlimit, Resource};
+use nix::sys::signal::{kill, signal, SigHandler, Signal};
+use nix::sys::statvfs::{statvfs, FsFlags};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{
+    chdir, chown, dup2, fork, pipe2, pivot_root, setgroups, setresgid, setresuid, ForkResult, Gid,
+    Pid, Uid,
+};
+use seccompiler::BpfProgram;
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tracing::{debug, info, warn};
+
+use super::backend::{
+    program_path, read_stdout, DirBind, IoSpec, Limits, SandboxBackend, SandboxOutcome, SANDBOX_ENV,
+};
+use super::box_lease::BoxLease;
+use super::config::get_config;
+use super::meta::{IsolateMeta, IsolateStatus};
+
+/// uid and gid of box 0; box N runs as `FIRST_UID + N`, like isolate
+const FIRST_UID: u32 = 60_000;
+
+/// Host directories mounted read-only into every box (skipped if missing)
+const SYSTEM_DIRS: &[&str] = &["/bin", "/lib", "/lib64", "/usr"];
+
+/// Host devices available in the box's /dev
+const DEVICES: &[&str] = &["/dev/null", "/dev/zero", "/dev/random", "/dev/urandom"];
+
+/// How often a running program's CPU time is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Runs started by this process, numbering their cgroups
+static RUN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Cached cgroup availability
+static CGROUPS_AVAILABLE: OnceLock<bool> = OnceLock::new();
+
+/// Check if the native backend can create cgroups with memory and pids limits
+pub async fn is_cgroups_available() -> bool {
+    if let Some(value) = CGROUPS_AVAILABLE.get() {
+        return *value;
+    }
+
+    let available = match tokio::task::spawn_blocking(probe_cgroups).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            warn!("Native sandbox cannot use cgroups: {:#}", e);
+            false
+        }
+        Err(_) => false,
+    };
+
+    let _ = CGROUPS_AVAILABLE.set(available);
+    available
+}
+
+/// Ensure cgroups are available; return an error otherwise
+pub async fn ensure_cgroups_available() -> Result<()> {
+    if is_cgroups_available().await {
+        Ok(())
+    } else {
+        bail!(
+            "The native sandbox needs to run as root with a cgroup v2 hierarchy at {} that has the memory and pids controllers (Linux 5.19 or newer). Set JUDGE_CGROUP_ROOT to a delegated cgroup.",
+            get_config().cgroup_root.display()
+        )
+    }
+}
+
+/// Create the cgroup root and check that a leaf in it gets every file we use
+fn probe_cgroups() -> Result<()> {
+    if !Uid::effective().is_root() {
+        bail!("not running as root");
+    }
+
+    let root = &get_config().cgroup_root;
+    std::fs::create_dir_all(root)
+        .with_context(|| format!("Failed to create {}", root.display()))?;
+    // The parent has to delegate the controllers as well; this fails harmlessly
+    // when it already does or when we may not change it
+    if let Some(parent) = root.parent() {
+        let _ = std::fs::write(parent.join("cgroup.subtree_control"), "+memory +pids");
+    }
+    std::fs::write(root.join("cgroup.subtree_control"), "+memory +pids").with_context(|| {
+        format!(
+            "Failed to enable the memory and pids controllers in {}",
+            root.display()
+        )
+    })?;
+
+    let probe = Cgroup::create(&format!("probe{}", get_config().worker_id))?;
+    for file in [
+        "memory.max",
+        "memory.peak",
+        "memory.events",
+        "pids.max",
+        "cpu.stat",
+    ] {
+        if !probe.path.join(file).exists() {
+            bail!("{} has no {}", probe.path.display(), file);
+        }
+    }
+    Ok(())
+}
+
+/// A cgroup v2 leaf holding one run, removed on drop
+struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    fn create(name: &str) -> Result<Self> {
+        let cgroup = Self {
+            path: get_config().cgroup_root.join(name),
+        };
+        // Left over by a run that never finished
+        if cgroup.path.exists() {
+            cgroup.remove();
+        }
+        std::fs::create_dir(&cgroup.path)
+            .with_context(|| format!("Failed to create cgroup {}", cgroup.path.display()))?;
+        Ok(cgroup)
+    }
+
+    fn write(&self, file: &str, value: impl AsRef<[u8]>) -> Result<()> {
+        let path = self.path.join(file);
+        std::fs::write(&path, value).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Single-number file such as `memory.peak`
+    fn value(&self, file: &str) -> Option<u64> {
+        std::fs::read_to_string(self.path.join(file))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// Entry of a flat keyed file such as `cpu.stat`
+    fn stat(&self, file: &str, key: &str) -> Option<u64> {
+        std::fs::read_to_string(self.path.join(file))
+            .ok()?
+            .lines()
+            .find_map(|line| match line.split_once(' ') {
+                Some((k, value)) if k == key => value.trim().parse().ok(),
+                _ => None,
+            })
+    }
+
+    fn cpu_usage_ms(&self) -> u32 {
+        (self.stat("cpu.stat", "usage_usec").unwrap_or(0) / 1000) as u32
+    }
+
+    fn procs(&self) -> Vec<i32> {
+        std::fs::read_to_string(self.path.join("cgroup.procs"))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|pid| pid.parse().ok())
+            .collect()
+    }
+
+    /// Kill every process in the cgroup and wait (up to a second) until it is empty
+    fn kill_all(&self) {
+        for _ in 0..200 {
+            let procs = self.procs();
+            if procs.is_empty() {
+                return;
+            }
+            // cgroup.kill needs Linux 5.14; signal each process on older kernels
+            if self.write("cgroup.kill", "1").is_err() {
+                for pid in procs {
+                    let _ = kill(Pid::from_raw(pid), Signal::SIGKILL);
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn remove(&self) {
+        self.kill_all();
+        if let Err(e) = std::fs::remove_dir(&self.path) {
+            warn!("Failed to remove cgroup {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Remove the cgroups of `box_id` left over by a worker that died mid-run.
+/// The box's lease is only released once its runs are over, so none of them
+/// belongs to a live run.
+fn remove_stale_cgroups(box_id: u32) {
+    let prefix = format!("box{}-", box_id);
+    let Ok(entries) = std::fs::read_dir(&get_config().cgroup_root) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            drop(Cgroup { path: entry.path() });
+        }
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Setup steps of the children, reported when one fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    JoinCgroup,
+    Unshare,
+    IdMapping,
+    Fork,
+    Mount,
+    PivotRoot,
+    Stdio,
+    Rlimits,
+    Affinity,
+    Credentials,
+    Seccomp,
+    Exec,
+}
+
+const STEPS: [Step; 12] = [
+    Step::JoinCgroup,
+    Step::Unshare,
+    Step::IdMapping,
+    Step::Fork,
+    Step::Mount,
+    Step::PivotRoot,
+    Step::Stdio,
+    Step::Rlimits,
+    Step::Affinity,
+    Step::Credentials,
+    Step::Seccomp,
+    Step::Exec,
+];
+
+/// Message from a child to the judge over the report pipe
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Report {
+    /// The program exited with this code
+    Exited(i32),
+    /// The program was killed by this signal
+    Signaled(i32),
+    /// A setup step failed before the program ran
+    Failed(Step, Errno),
+}
+
+impl Report {
+    const SIZE: usize = 8;
+
+    fn encode(self) -> [u8; Self::SIZE] {
+        let (kind, step, value) = match self {
+            Report::Exited(code) => (0, 0, code),
+            Report::Signaled(signal) => (1, 0, signal),
+            Report::Failed(step, errno) => (2, step as u8, errno as i32),
+        };
+        let mut bytes = [kind, step, 0, 0, 0, 0, 0, 0];
+        bytes[4..].copy_from_slice(&value.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let value = i32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?);
+        match bytes[0] {
+            0 => Some(Report::Exited(value)),
+            1 => Some(Report::Signaled(value)),
+            2 => Some(Report::Failed(
+                *STEPS.get(bytes[1] as usize)?,
+                Errno::from_raw(value),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// A host path bound into the box
+struct BindMount {
+    source: CString,
+    target: CString,
+    /// Flags the bind is remounted with
+    flags: MsFlags,
+}
+
+impl BindMount {
+    /// Bind `source` at `inside` under `root`, creating the mount point
+    fn new(source: &Path, root: &Path, inside: &str, flags: MsFlags) -> Result<Self> {
+        let target = root.join(inside.trim_start_matches('/'));
+        if source.is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if !target.exists() {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            File::create(&target)?;
+        }
+
+        // Mounts from the host are locked inside our user namespace, so a
+        // remount has to keep their flags
+        let host_flags = statvfs(source)
+            .with_context(|| format!("Failed to stat {}", source.display()))?
+            .flags();
+        Ok(Self {
+            source: path_cstring(source)?,
+            target: path_cstring(&target)?,
+            flags: flags | locked_flags(host_flags),
+        })
+    }
+}
+
+/// Mount flags matching a host mount's `statvfs` flags
+fn locked_flags(host: FsFlags) -> MsFlags {
+    let mut flags = MsFlags::empty();
+    for (st, ms) in [
+        (FsFlags::ST_RDONLY, MsFlags::MS_RDONLY),
+        (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+        (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+        (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+        (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+        (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+        (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+    ] {
+        if host.contains(st) {
+            flags |= ms;
+        }
+    }
+    // Remounts default to relatime, which a strictatime host mount refuses
+    if !host.intersects(FsFlags::ST_NOATIME | FsFlags::ST_RELATIME) {
+        flags |= MsFlags::MS_STRICTATIME;
+    }
+    flags
+}
+
+fn path_cstring(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .with_context(|| format!("Path contains a NUL byte: {}", path.display()))
+}
+
+/// Everything the children need, prepared before forking
+struct RunPlan {
+    cgroup_name: String,
+    root: CString,
+    binds: Vec<BindMount>,
+    proc_dir: CString,
+    tmp_dir: CString,
+    tmp_options: CString,
+    program: CString,
+    argv: Vec<CString>,
+    env: Vec<CString>,
+    id: u32,
+    /// (resource, soft limit, hard limit)
+    rlimits: Vec<(Resource, u64, u64)>,
+    cpu: Option<CpuSet>,
+    /// Seccomp filters of the profile; they allow `execve` of `program` only
+    filters: Vec<BpfProgram>,
+    limits: Limits,
+}
+
+impl RunPlan {
+    fn new(
+        sandbox: &BoxInfo,
+        command: &[String],
+        limits: &Limits,
+        binds: &[DirBind],
+    ) -> Result<Self> {
+        let Some((program, args)) = command.split_first() else {
+            bail!("No command specified for execution");
+        };
+
+        let root = sandbox.dir.join("root");
+        let mut mounts = Vec::new();
+        for dir in SYSTEM_DIRS {
+            if Path::new(dir).exists() {
+                mounts.push(BindMount::new(
+                    Path::new(dir),
+                    &root,
+                    dir,
+                    MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                )?);
+            }
+        }
+        mounts.push(BindMount::new(
+            Path::new("/etc"),
+            &root,
+            "/etc",
+            MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+        )?);
+        for device in DEVICES {
+            mounts.push(BindMount::new(
+                Path::new(device),
+                &root,
+                device,
+                MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC,
+            )?);
+        }
+        mounts.push(BindMount::new(
+            &sandbox.work_dir(),
+            &root,
+            "/box",
+            MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        )?);
+        for bind in binds {
+            if !bind.inside.starts_with('/') || bind.inside.split('/').any(|part| part == "..") {
+                bail!("Invalid mount point {:?}", bind.inside);
+            }
+            mounts.push(BindMount::new(
+                &bind.host,
+                &root,
+                &bind.inside,
+                MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+            )?);
+        }
+
+        let proc_dir = root.join("proc");
+        let tmp_dir = root.join("tmp");
+        std::fs::create_dir_all(&proc_dir)?;
+        std::fs::create_dir_all(&tmp_dir)?;
+
+        let program = CString::new(program_path(program))?;
+        let mut argv = vec![program.clone()];
+        for arg in args {
+            argv.push(CString::new(arg.as_str())?);
+        }
+        // Built from the CString whose heap buffer `execve` receives later
+        let filters = limits.seccomp.filters(&program)?;
+        let env = SANDBOX_ENV
+            .iter()
+            .map(|(name, value)| CString::new(format!("{}={}", name, value)))
+            .collect::<Result<_, _>>()?;
+
+        let cpu_secs = (limits.time_ms as u64).div_ceil(1000) + 1;
+        let fsize = limits.fsize_kb as u64 * 1024;
+        let rlimits = vec![
+            (Resource::RLIMIT_CPU, cpu_secs, cpu_secs + 1),
+            (Resource::RLIMIT_FSIZE, fsize, fsize),
+            (
+                Resource::RLIMIT_NOFILE,
+                limits.open_files as u64,
+                limits.open_files as u64,
+            ),
+            (Resource::RLIMIT_CORE, 0, 0),
+        ];
+
+        let cpu = match sandbox.cpu {
+            Some(cpu) => {
+                let mut set = CpuSet::new();
+                set.set(cpu)?;
+                Some(set)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            // Unique per run, so a run that outlives its box never shares a
+            // cgroup with the next run of the same box ID
+            cgroup_name: format!(
+                "box{}-{}-{}",
+                sandbox.box_id,
+                std::process::id(),
+                RUN_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ),
+            root: path_cstring(&root)?,
+            binds: mounts,
+            proc_dir: path_cstring(&proc_dir)?,
+            tmp_dir: path_cstring(&tmp_dir)?,
+            tmp_options: CString::new(format!("size={}m,mode=1777", limits.memory_mb))?,
+            program,
+            argv,
+            env,
+            id: FIRST_UID + sandbox.box_id,
+            rlimits,
+            cpu,
+            filters,
+            limits: limits.clone(),
+        })
+    }
+}
+
+/// Pipe ends and files the first child uses
+struct ChildFds {
+    cgroup_procs: File,
+    /// Written once the namespaces exist
+    ready: OwnedFd,
+    /// Readable once the judge has written the id maps
+    go: OwnedFd,
+    report: OwnedFd,
+    /// The program's stdin, stdout and stderr
+    stdio: [OwnedFd; 3],
+}
+
+/// `execve` arguments, pointing into a [`RunPlan`]
+struct ExecArgs {
+    argv: Vec<*const libc::c_char>,
+    envp: Vec<*const libc::c_char>,
+}
+
+impl ExecArgs {
+    fn new(plan: &RunPlan) -> Self {
+        let pointers = |strings: &[CString]| {
+            strings
+                .iter()
+                .map(|s| s.as_ptr())
+                .chain(std::iter::once(std::ptr::null()))
+                .collect()
+        };
+        Self {
+            argv: pointers(&plan.argv),
+            envp: pointers(&plan.env),
+        }
+    }
+}
+
+fn failed(step: Step) -> impl Fn(Errno) -> (Step, Errno) {
+    move |errno| (step, errno)
+}
+
+/// Wait for `child` and describe how it ended
+fn wait_for(child: Pid) -> Result<Report, (Step, Errno)> {
+    loop {
+        match waitpid(child, None) {
+            Ok(WaitStatus::Exited(_, code)) => return Ok(Report::Exited(code)),
+            Ok(WaitStatus::Signaled(_, signal, _)) => return Ok(Report::Signaled(signal as i32)),
+            Ok(_) | Err(Errno::EINTR) => continue,
+            Err(errno) => return Err((Step::Fork, errno)),
+        }
+    }
+}
+
+/// Send a report and exit
+fn report_and_exit(fds: &ChildFds, report: Result<Report, (Step, Errno)>, code: i32) -> ! {
+    let report = report.unwrap_or_else(|(step, errno)| Report::Failed(step, errno));
+    let _ = nix::unistd::write(&fds.report, &report.encode());
+    // SAFETY: _exit is async-signal-safe
+    unsafe { libc::_exit(code) }
+}
+
+/// First child: enter the cgroup and new namespaces, then start the box's
+/// init and report how it ended
+fn first_child(plan: &RunPlan, fds: &ChildFds, exec: &ExecArgs) -> ! {
+    let run = || -> Result<Report, (Step, Errno)> {
+        nix::unistd::write(&fds.cgroup_procs, b"0").map_err(failed(Step::JoinCgroup))?;
+        unshare(
+            CloneFlags::CLONE_NEWUSER
+                | CloneFlags::CLONE_NEWNS
+                | CloneFlags::CLONE_NEWPID
+                | CloneFlags::CLONE_NEWNET
+                | CloneFlags::CLONE_NEWIPC
+                | CloneFlags::CLONE_NEWUTS,
+        )
+        .map_err(failed(Step::Unshare))?;
+
+        // Only the judge can map our ids; wait until it has
+        nix::unistd::write(&fds.ready, &[1]).map_err(failed(Step::IdMapping))?;
+        let mut go = [0u8];
+        match nix::unistd::read(fds.go.as_raw_fd(), &mut go) {
+            Ok(1) => {}
+            Ok(_) => return Err((Step::IdMapping, Errno::EPIPE)),
+            Err(errno) => return Err((Step::IdMapping, errno)),
+        }
+
+        // SAFETY: the box's init only makes async-signal-safe calls
+        match unsafe { fork() }.map_err(failed(Step::Fork))? {
+            ForkResult::Child => box_init(plan, fds, exec),
+            ForkResult::Parent { child } => wait_for(child),
+        }
+    };
+    report_and_exit(fds, run(), 0)
+}
+
+/// pid 1 of the box's pid namespace: mount the box filesystem, then run the
+/// program as its child and report how it ended. The program can't be pid 1
+/// itself, since the kernel drops signals that pid 1 sends itself.
+fn box_init(plan: &RunPlan, fds: &ChildFds, exec: &ExecArgs) -> ! {
+    let run = || -> Result<Report, (Step, Errno)> {
+        // Nothing may outlive the first child
+        prctl::set_pdeathsig(Signal::SIGKILL).map_err(failed(Step::Fork))?;
+        mount_box(plan)?;
+
+        // SAFETY: the program only makes async-signal-safe calls until execve
+        match unsafe { fork() }.map_err(failed(Step::Fork))? {
+            ForkResult::Child => {
+                let error = match enter_program(plan, fds) {
+                    // SAFETY: the pointers are NUL-terminated arrays of
+                    // NUL-terminated strings
+                    Ok(()) => unsafe {
+                        libc::execve(
+                            plan.program.as_ptr(),
+                            exec.argv.as_ptr(),
+                            exec.envp.as_ptr(),
+                        );
+                        (Step::Exec, Errno::last())
+                    },
+                    Err(error) => error,
+                };
+                report_and_exit(fds, Err(error), 127)
+            }
+            ForkResult::Parent { child } => wait_for(child),
+        }
+    };
+    report_and_exit(fds, run(), 0)
+}
+
+/// Build the box filesystem and pivot into it
+fn mount_box(plan: &RunPlan) -> Result<(), (Step, Errno)> {
+    let none = None::<&str>;
+    // Keep our mounts from propagating back to the host
+    mount(none, "/", none, MsFlags::MS_REC | MsFlags::MS_PRIVATE, none)
+        .map_err(failed(Step::Mount))?;
+    // pivot_root needs the new root to be a mount point
+    mount(
+        Some(plan.root.as_c_str()),
+        plan.root.as_c_str(),
+        none,
+        MsFlags::MS_BIND,
+        none,
+    )
+    .map_err(failed(Step::Mount))?;
+    for bind in &plan.binds {
+        mount(
+            Some(bind.source.as_c_str()),
+            bind.target.as_c_str(),
+            none,
+            MsFlags::MS_BIND,
+            none,
+        )
+        .map_err(failed(Step::Mount))?;
+        mount(
+            none,
+            bind.target.as_c_str(),
+            none,
+            MsFlags::MS_BIND | MsFlags::MS_REMOUNT | bind.flags,
+            none,
+        )
+        .map_err(failed(Step::Mount))?;
+    }
+    // A container may hide parts of the host's /proc, which makes mounting a
+    // new one fail; programs then run with an empty /proc
+    let _ = mount(
+        Some("proc"),
+        plan.proc_dir.as_c_str(),
+        Some("proc"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+        none,
+    );
+    mount(
+        Some("tmpfs"),
+        plan.tmp_dir.as_c_str(),
+        Some("tmpfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some(plan.tmp_options.as_c_str()),
+    )
+    .map_err(failed(Step::Mount))?;
+
+    chdir(plan.root.as_c_str()).map_err(failed(Step::PivotRoot))?;
+    pivot_root(".", ".").map_err(failed(Step::PivotRoot))?;
+    umount2(".", MntFlags::MNT_DETACH).map_err(failed(Step::PivotRoot))?;
+    chdir("/box").map_err(failed(Step::PivotRoot))?;
+    Ok(())
+}
+
+/// Attach the program's stdio and apply its limits, credentials and seccomp filter
+fn enter_program(plan: &RunPlan, fds: &ChildFds) -> Result<(), (Step, Errno)> {
+    for (target, fd) in fds.stdio.iter().enumerate() {
+        dup2(fd.as_raw_fd(), target as i32).map_err(failed(Step::Stdio))?;
+    }
+
+    for &(resource, soft, hard) in &plan.rlimits {
+        setrlimit(resource, soft, hard).map_err(failed(Step::Rlimits))?;
+    }
+    if let Some(cpu) = &plan.cpu {
+        sched_setaffinity(Pid::from_raw(0), cpu).map_err(failed(Step::Affinity))?;
+    }
+
+    let (uid, gid) = (Uid::from_raw(plan.id), Gid::from_raw(plan.id));
+    setgroups(&[gid]).map_err(failed(Step::Credentials))?;
+    setresgid(gid, gid, gid).map_err(failed(Step::Credentials))?;
+    setresuid(uid, uid, uid).map_err(failed(Step::Credentials))?;
+
+    // Rust ignores SIGPIPE, which programs would inherit
+    // SAFETY: restores the default disposition, no handler is involved
+    unsafe { signal(Signal::SIGPIPE, SigHandler::SigDfl) }.map_err(failed(Step::Seccomp))?;
+    for filter in &plan.filters {
+        seccompiler::apply_filter(filter).map_err(|_| (Step::Seccomp, Errno::last()))?;
+    }
+    Ok(())
+}
+
+/// Resources a finished run used, read from its cgroup
+#[derive(Debug, Default)]
+struct Usage {
+    cpu_ms: u32,
+    wall_ms: u32,
+    memory_kb: u32,
+    oom_killed: bool,
+    /// Killed for running past the time or wall time limit
+    timed_out: bool,
+}
+
+/// Sets its flag when dropped, i.e. when the future waiting for a run is
+/// dropped or finishes
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Run a prepared plan to completion, killing it early once `cancelled` is
+/// set. Returns the meta and, if the program could not be executed, why.
+fn execute(
+    plan: RunPlan,
+    stdio: [OwnedFd; 3],
+    cancelled: &AtomicBool,
+) -> Result<(IsolateMeta, Option<Errno>)> {
+    let limits = plan.limits.clone();
+    let cgroup = Cgroup::create(&plan.cgroup_name)?;
+    cgroup.write(
+        "memory.max",
+        (limits.memory_mb as u64 * 1024 * 1024).to_string(),
+    )?;
+    // Without swap accounting there is nothing to disable
+    let _ = cgroup.write("memory.swap.max", "0");
+    cgroup.write("pids.max", limits.processes.to_string())?;
+
+    let (ready_read, ready_write) = pipe2(OFlag::O_CLOEXEC)?;
+    let (go_read, go_write) = pipe2(OFlag::O_CLOEXEC)?;
+    let (report_read, report_write) = pipe2(OFlag::O_CLOEXEC)?;
+    let fds = ChildFds {
+        cgroup_procs: OpenOptions::new()
+            .write(true)
+            .open(cgroup.path.join("cgroup.procs"))?,
+        ready: ready_write,
+        go: go_read,
+        report: report_write,
+        stdio,
+    };
+    let exec = ExecArgs::new(&plan);
+
+    // SAFETY: the children only make async-signal-safe calls until execve
+    let child = match unsafe { fork() }.context("Failed to fork")? {
+        ForkResult::Child => {
+            // Our copy would keep the first child waiting forever if the judge gave up
+            drop(go_write);
+            first_child(&plan, &fds, &exec)
+        }
+        ForkResult::Parent { child } => child,
+    };
+    // Release our copies so pipe peers see EOF once the program exits
+    drop(fds);
+
+    let mut ready = [0u8];
+    let mapped = match File::from(ready_read).read(&mut ready) {
+        Ok(1) => write_id_maps(child, plan.id),
+        _ => Err(anyhow::anyhow!("Sandbox child exited during setup")),
+    };
+    if mapped.is_ok() {
+        let _ = File::from(go_write).write_all(&[1]);
+    } else {
+        drop(go_write);
+    }
+
+    let started = Instant::now();
+    let wall_limit = Duration::from_millis(limits.time_ms as u64 * 2 + 1000);
+    let mut usage = Usage::default();
+    loop {
+        match waitpid(child, Some(WaitPidFlag::WNOHANG)) {
+            Ok(WaitStatus::StillAlive) | Err(Errno::EINTR) => {}
+            Ok(_) => break,
+            Err(errno) => return Err(errno).context("Failed to wait for the sandbox"),
+        }
+        if !usage.timed_out
+            && (started.elapsed() > wall_limit || cgroup.cpu_usage_ms() > limits.time_ms)
+        {
+            usage.timed_out = true;
+            cgroup.kill_all();
+        }
+        if cancelled.load(Ordering::Relaxed) {
+            cgroup.kill_all();
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    usage.wall_ms = started.elapsed().as_millis() as u32;
+    // The program is gone, but a child that escaped its pid namespace isn't
+    cgroup.kill_all();
+    if cancelled.load(Ordering::Relaxed) {
+        bail!("Native sandbox run was cancelled");
+    }
+    mapped?;
+
+    let mut bytes = Vec::new();
+    File::from(report_read).read_to_end(&mut bytes)?;
+    let reports: Vec<Report> = bytes
+        .chunks_exact(Report::SIZE)
+        .filter_map(Report::decode)
+        .collect();
+    // The innermost report comes first: the program's exit or a failed step
+    let report = reports.first().copied();
+    if let Some(Report::Failed(step, errno)) = report {
+        if step != Step::Exec {
+            bail!("Native sandbox setup failed ({:?}): {}", step, errno);
+        }
+    }
+
+    usage.cpu_ms = cgroup.cpu_usage_ms();
+    usage.memory_kb = (cgroup.value("memory.peak").unwrap_or(0) / 1024) as u32;
+    usage.oom_killed = cgroup.stat("memory.events", "oom_kill").unwrap_or(0) > 0;
+
+    let exec_error = match report {
+        Some(Report::Failed(Step::Exec, errno)) => Some(errno),
+        _ => None,
+    };
+    Ok((into_meta(report, &usage, &limits), exec_error))
+}
+
+/// Map the box's id to itself in the first child's user namespace
+fn write_id_maps(child: Pid, id: u32) -> Result<()> {
+    let map = format!("{0} {0} 1\n", id);
+    for file in ["uid_map", "gid_map"] {
+        let path = format!("/proc/{}/{}", child, file);
+        std::fs::write(&path, &map).with_context(|| format!("Failed to write {}", path))?;
+    }
+    Ok(())
+}
+
+/// Meta of a finished run, as isolate would report it
+fn into_meta(report: Option<Report>, usage: &Usage, limits: &Limits) -> IsolateMeta {
+    let memory_limit_kb = limits.memory_mb * 1024;
+    let mut meta = IsolateMeta {
+        time_ms: usage.cpu_ms,
+        wall_time_ms: usage.wall_ms,
+        // Usage never passes memory.max, so an OOM kill is reported just
+        // above the limit for it to read as exceeding it
+        memory_kb: if usage.oom_killed {
+            usage.memory_kb.max(memory_limit_kb + 1)
+        } else {
+            usage.memory_kb
+        },
+        ..IsolateMeta::default()
+    };
+
+    meta.status = if usage.timed_out || usage.cpu_ms > limits.time_ms {
+        IsolateStatus::TimeOut
+    } else {
+        match report {
+            Some(Report::Exited(0)) => IsolateStatus::Ok,
+            Some(Report::Exited(code)) => {
+                meta.exit_code = code;
+                IsolateStatus::RuntimeError
+            }
+            Some(Report::Signaled(signal)) if signal == Signal::SIGXCPU as i32 => {
+                IsolateStatus::TimeOut
+            }
+            Some(Report::Signaled(signal)) if signal == Signal::SIGXFSZ as i32 => {
+                IsolateStatus::OutputLimitExceeded
+            }
+            Some(Report::Signaled(signal)) => IsolateStatus::Signal(signal),
+            Some(Report::Failed(..)) => {
+                meta.exit_code = 127;
+                IsolateStatus::RuntimeError
+            }
+            None => IsolateStatus::InternalError,
+        }
+    };
+    meta
+}
+
+/// What a run needs to know about its box
+#[derive(Clone)]
+struct BoxInfo {
+    box_id: u32,
+    dir: PathBuf,
+    cpu: Option<usize>,
+}
+
+impl BoxInfo {
+    fn work_dir(&self) -> PathBuf {
+        self.dir.join("box")
+    }
+}
+
+/// Box of the native backend
+pub struct NativeBox {
+    /// Keeps the box ID (and with it the uid) reserved; runs hold a clone
+    /// until they are over, even if the box is dropped first
+    lease: Arc<BoxLease>,
+    info: BoxInfo,
+}
+
+impl NativeBox {
+    /// Lease a box ID and create the box's directories
+    pub async fn new() -> Result<Self> {
+        let lease = BoxLease::acquire()?;
+        let box_id = lease.box_id();
+        let dir = std::env::temp_dir()
+            .join("judge-native")
+            .join(format!("box{}", box_id));
+
+        // Left over by a worker that did not clean up
+        let _ = fs::remove_dir_all(&dir).await;
+        tokio::task::spawn_blocking(move || remove_stale_cgroups(box_id)).await?;
+        fs::create_dir_all(dir.join("box")).await?;
+        fs::create_dir_all(dir.join("root")).await?;
+        let id = FIRST_UID + box_id;
+        chown(
+            &dir.join("box"),
+            Some(Uid::from_raw(id)),
+            Some(Gid::from_raw(id)),
+        )
+        .with_context(|| format!("Failed to hand {} to uid {}", dir.display(), id))?;
+
+        info!("Initialized native box {} at {}", box_id, dir.display());
+        Ok(Self {
+            lease: Arc::new(lease),
+            info: BoxInfo {
+                box_id,
+                dir,
+                cpu: None,
+            },
+        })
+    }
+
+    /// Get the box ID
+    pub fn box_id(&self) -> u32 {
+        self.info.box_id
+    }
+
+    /// Run `command` with the given stdio and collect its outcome
+    async fn execute(
+        &self,
+        command: &[String],
+        limits: &Limits,
+        binds: &[DirBind],
+        stdio: [OwnedFd; 3],
+        stderr_file: &str,
+        stdout_path: Option<PathBuf>,
+    ) -> Result<SandboxOutcome> {
+        debug!("Running {:?} in native box {}", command, self.info.box_id);
+        let (info, lease) = (self.info.clone(), self.lease.clone());
+        let (task_command, task_limits, binds) = (command.to_vec(), limits.clone(), binds.to_vec());
+        // Blocking tasks can't be aborted, so the run is killed through a flag
+        // when this future is dropped (cancellation or shutdown)
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _cancel = CancelOnDrop(cancelled.clone());
+        let (meta, exec_error) = tokio::task::spawn_blocking(move || {
+            let _lease = lease;
+            let plan = RunPlan::new(&info, &task_command, &task_limits, &binds)?;
+            execute(plan, stdio, &cancelled)
+        })
+        .await??;
+
+        let stdout_bytes = match stdout_path {
+            Some(path) => read_stdout(&path, limits).await,
+            None => Vec::new(),
+        };
+        let mut stderr = fs::read_to_string(self.info.work_dir().join(stderr_file))
+            .await
+            .unwrap_or_default();
+        if let Some(errno) = exec_error {
+            stderr.push_str(&format!("Failed to execute {}: {}\n", command[0], errno));
+        }
+
+        let mut outcome = SandboxOutcome {
+            meta,
+            stdout: String::from_utf8_lossy(&stdout_bytes).to_string(),
+            stdout_bytes,
+            stderr,
+        };
+        outcome.check_output_limit(limits);
+        Ok(outcome)
+    }
+
+    /// Create (or truncate) a file in the box for the program's output
+    async fn output_file(&self, name: &str) -> Result<OwnedFd> {
+        let path = self.info.work_dir().join(name);
+        let file = fs::File::create(&path)
+            .await
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(file.into_std().await.into())
+    }
+}
+
+#[async_trait]
+impl SandboxBackend for NativeBox {
+    fn work_dir(&self) -> PathBuf {
+        self.info.work_dir()
+    }
+
+    fn pin_to_cpu(&mut self, cpu: usize) {
+        self.info.cpu = Some(cpu);
+    }
+
+    async fn run(
+        &self,
+        command: &[String],
+        limits: &Limits,
+        io: &IoSpec,
+    ) -> Result<SandboxOutcome> {
+        let stdin_path = io
+            .stdin_path
+            .as_deref()
+            .unwrap_or_else(|| Path::new("/dev/null"));
+        let stdin = fs::File::open(stdin_path)
+            .await
+            .with_context(|| format!("Failed to open {}", stdin_path.display()))?
+            .into_std()
+            .await
+            .into();
+        let stdio = [
+            stdin,
+            self.output_file(&io.stdout_file).await?,
+            self.output_file(&io.stderr_file).await?,
+        ];
+
+        let stdout_path = self.info.work_dir().join(&io.stdout_file);
+        self.execute(
+            command,
+            limits,
+            &io.binds,
+            stdio,
+            &io.stderr_file,
+            Some(stdout_path),
+        )
+        .await
+    }
+
+    async fn run_with_stdio(
+        &self,
+        command: &[String],
+        limits: &Limits,
+        stderr_file: &str,
+        binds: &[DirBind],
+        stdin: OwnedFd,
+        stdout: OwnedFd,
+    ) -> Result<SandboxOutcome> {
+        let stdio = [stdin, stdout, self.output_file(stderr_file).await?];
+        self.execute(command, limits, binds, stdio, stderr_file, None)
+            .await
+    }
+
+    async fn cleanup(self: Box<Self>) -> Result<()> {
+        fs::remove_dir_all(&self.info.dir)
+            .await
+            .with_context(|| format!("Failed to remove {}", self.info.dir.display()))?;
+        info!("Cleaned up native box {}", self.info.box_id);
+        Ok(())
+    }
+}
+
+impl Drop for NativeBox {
+    fn drop(&mut self) {
+        // Boxes that were never cleaned up, e.g. of a cancelled job
+        let _ = std::fs::remove_dir_all(&self.info.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_roundtrip() {
+        for report in [
+            Report::Exited(3),
+            Report::Signaled(11),
+            Report::Failed(Step::Mount, Errno::EPERM),
+        ] {
+            assert_eq!(Report::decode(&report.encode()), Some(report));
+        }
+        assert_eq!(Report::decode(&[9, 0, 0, 0, 0, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_into_meta_statuses() {
+        let limits = Limits::default();
+        let usage = Usage {
+            cpu_ms: 10,
+            ..Usage::default()
+        };
+        let status = |report| into_meta(Some(report), &usage, &limits).status;
+
+        assert_eq!(status(Report::Exited(0)), IsolateStatus::Ok);
+        assert_eq!(status(Report::Exited(1)), IsolateStatus::RuntimeError);
+        assert_eq!(status(Report::Signaled(11)), IsolateStatus::Signal(11));
+        assert_eq!(
+            status(Report::Signaled(Signal::SIGXFSZ as i32)),
+            IsolateStatus::OutputLimitExceeded
+        );
+        assert_eq!(
+            status(Report::Signaled(Signal::SIGXCPU as i32)),
+            IsolateStatus::TimeOut
+        );
+        assert_eq!(
+            into_meta(None, &usage, &limits).status,
+            IsolateStatus::InternalError
+        );
+    }
+
+    #[test]
+    fn test_into_meta_limits() {
+        let limits = Limits::default();
+        let slow = Usage {
+            cpu_ms: limits.time_ms + 1,
+            ..Usage::default()
+        };
+        assert_eq!(
+            into_meta(Some(Report::Exited(0)), &slow, &limits).status,
+            IsolateStatus::TimeOut
+        );
+
+        let oom = Usage {
+            memory_kb: 1000,
+            oom_killed: true,
+            ..Usage::default()
+        };
+        let meta = into_meta(Some(Report::Signaled(9)), &oom, &limits);
+        assert!(meta.memory_kb > limits.memory_mb * 1024);
+    }
+}
@@ -0,0 +1,221 @@
+//! Seccomp profiles
+//!
+//! Every program in a native box runs at least under the base profile, which
+//! kills it on syscalls that administer the kernel or escape the box.
+//! Submissions run under the profile of their language (`seccomp` in
+//! languages.toml):
+//!
+//! - `runtime` (Java, Python, JavaScript) also forbids starting processes:
+//!   `fork`, `vfork`, `clone` without `CLONE_THREAD`, and every `execve` but
+//!   the one that starts the program. Threads stay allowed for the VMs.
+//! - `strict` (C, C++, Rust, Go) additionally forbids sockets, io_uring and
+//!   `personality`, which compiled solutions never need.
+//!
+//! `clone3` passes its flags in memory the filter can't read, so the
+//! language profiles make it fail with ENOSYS; libc then falls back to
+//! `clone`. A program killed by a filter dies of SIGSYS, which the judge
+//! reports as `restricted_function` for language profile runs only.
+//!
+//! isolate has no syscall filter, so isolate boxes start language runs
+//! through the `judge-seccomp` helper; base profile runs are not filtered
+//! there.
+
+use anyhow::{bail, Result};
+use seccompiler::{
+    BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+    SeccompRule, TargetArch,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+
+/// Syscalls that kill the program under every profile: kernel and namespace
+/// administration, tracing, and other ways out of the box
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_acct,
+    libc::SYS_add_key,
+    libc::SYS_bpf,
+    libc::SYS_chroot,
+    libc::SYS_clock_settime,
+    libc::SYS_delete_module,
+    libc::SYS_finit_module,
+    libc::SYS_fsconfig,
+    libc::SYS_fsmount,
+    libc::SYS_fsopen,
+    libc::SYS_init_module,
+    libc::SYS_kexec_load,
+    libc::SYS_keyctl,
+    libc::SYS_mount,
+    libc::SYS_move_mount,
+    libc::SYS_name_to_handle_at,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_open_tree,
+    libc::SYS_perf_event_open,
+    libc::SYS_pivot_root,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_ptrace,
+    libc::SYS_quotactl,
+    libc::SYS_reboot,
+    libc::SYS_request_key,
+    libc::SYS_setdomainname,
+    libc::SYS_sethostname,
+    libc::SYS_setns,
+    libc::SYS_settimeofday,
+    libc::SYS_swapoff,
+    libc::SYS_swapon,
+    libc::SYS_umount2,
+    libc::SYS_unshare,
+    libc::SYS_userfaultfd,
+];
+
+/// Syscalls that only start processes (`clone` and `execve` are checked by
+/// their arguments)
+const PROCESS_SYSCALLS: &[libc::c_long] = &[
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_fork,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_vfork,
+    libc::SYS_execveat,
+];
+
+/// Syscalls that additionally kill programs under the strict profile
+const STRICT_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_io_uring_enter,
+    libc::SYS_io_uring_register,
+    libc::SYS_io_uring_setup,
+    libc::SYS_personality,
+    libc::SYS_socket,
+    libc::SYS_socketpair,
+];
+
+/// Syscall filter a program runs under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeccompProfile {
+    /// Kernel administration and escapes only; for compilers and tools
+    #[default]
+    Base,
+    /// No new processes; for language runtimes that need threads
+    Runtime,
+    /// No new processes, sockets or io_uring; for compiled languages
+    Strict,
+}
+
+impl std::fmt::Display for SeccompProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SeccompProfile::Base => "base",
+            SeccompProfile::Runtime => "runtime",
+            SeccompProfile::Strict => "strict",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for SeccompProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "base" => Ok(Self::Base),
+            "runtime" => Ok(Self::Runtime),
+            "strict" => Ok(Self::Strict),
+            other => bail!("Unknown seccomp profile {:?}", other),
+        }
+    }
+}
+
+impl SeccompProfile {
+    /// Build the filters to install right before `execve(program, ..)`.
+    ///
+    /// The language profiles allow exactly that `execve`: it must be passed
+    /// the same `program` pointer, which the filters compare against.
+    pub fn filters(self, program: &CStr) -> Result<Vec<BpfProgram>> {
+        let arch: TargetArch = std::env::consts::ARCH.try_into()?;
+        let mut killed = BTreeMap::new();
+        kill_always(&mut killed, DENIED_SYSCALLS);
+        let mut filters = Vec::new();
+
+        if self != SeccompProfile::Base {
+            kill_always(&mut killed, PROCESS_SYSCALLS);
+            killed.insert(
+                libc::SYS_clone,
+                vec![arg_rule(
+                    0,
+                    SeccompCmpOp::MaskedEq(libc::CLONE_THREAD as u64),
+                    0,
+                )?],
+            );
+            killed.insert(
+                libc::SYS_execve,
+                vec![arg_rule(0, SeccompCmpOp::Ne, program.as_ptr() as u64)?],
+            );
+
+            let clone3 = SeccompFilter::new(
+                BTreeMap::from([(libc::SYS_clone3, Vec::new())]),
+                SeccompAction::Allow,
+                SeccompAction::Errno(libc::ENOSYS as u32),
+                arch,
+            )?;
+            filters.push(clone3.try_into()?);
+        }
+        if self == SeccompProfile::Strict {
+            kill_always(&mut killed, STRICT_SYSCALLS);
+        }
+
+        let filter = SeccompFilter::new(
+            killed,
+            SeccompAction::Allow,
+            SeccompAction::KillProcess,
+            arch,
+        )?;
+        filters.push(filter.try_into()?);
+        Ok(filters)
+    }
+}
+
+/// Match `syscalls` whatever their arguments
+fn kill_always(rules: &mut BTreeMap<i64, Vec<SeccompRule>>, syscalls: &[libc::c_long]) {
+    rules.extend(syscalls.iter().map(|&syscall| (syscall, Vec::new())));
+}
+
+/// Rule matching a syscall by one of its arguments
+fn arg_rule(index: u8, op: SeccompCmpOp, value: u64) -> Result<SeccompRule> {
+    let condition = SeccompCondition::new(index, SeccompCmpArgLen::Qword, op, value)?;
+    Ok(SeccompRule::new(vec![condition])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_names_roundtrip() {
+        for profile in [
+            SeccompProfile::Base,
+            SeccompProfile::Runtime,
+            SeccompProfile::Strict,
+        ] {
+            assert_eq!(
+                profile.to_string().parse::<SeccompProfile>().unwrap(),
+                profile
+            );
+        }
+        assert!("none".parse::<SeccompProfile>().is_err());
+    }
+
+    #[test]
+    fn test_language_profiles_add_filters() {
+        let program = c"/box/Main";
+        let base = SeccompProfile::Base.filters(program).unwrap();
+        let runtime = SeccompProfile::Runtime.filters(program).unwrap();
+        let strict = SeccompProfile::Strict.filters(program).unwrap();
+
+        assert_eq!(base.len(), 1);
+        assert_eq!(runtime.len(), 2);
+        assert_eq!(strict.len(), 2);
+        assert!(strict[1].len() > runtime[1].len());
+        assert!(runtime[1].len() > base[0].len());
+    }
+}
@@ -0,0 +1,86 @@
+//! Graceful shutdown on SIGTERM/SIGINT
+//!
+//! The first signal asks the worker to stop taking new jobs and to finish the
+//! current one within a grace period. A second signal gives up on the current
+//! job right away so it can be requeued.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Default time the current job gets to finish after a shutdown request
+const DEFAULT_GRACE_SECS: u64 = 20;
+
+/// Shutdown state shared by the worker loop
+#[derive(Clone)]
+pub struct Shutdown {
+    /// Number of shutdown signals received so far
+    signals: watch::Receiver<u32>,
+    grace: Duration,
+}
+
+impl Shutdown {
+    /// Start listening for SIGTERM and SIGINT.
+    ///
+    /// The grace period is read from `JUDGE_SHUTDOWN_GRACE_SECS`.
+    pub fn listen() -> Result<Self> {
+        let mut sigterm =
+            signal(SignalKind::terminate()).context("Failed to listen for SIGTERM")?;
+        let mut sigint = signal(SignalKind::interrupt()).context("Failed to listen for SIGINT")?;
+        let (tx, signals) = watch::channel(0u32);
+
+        tokio::spawn(async move {
+            loop {
+                let name = tokio::select! {
+                    _ = sigterm.recv() => "SIGTERM",
+                    _ = sigint.recv() => "SIGINT",
+                };
+                tx.send_modify(|count| *count += 1);
+                if *tx.borrow() == 1 {
+                    info!(
+                        "Received {}, finishing the current job before exiting",
+                        name
+                    );
+                } else {
+                    warn!("Received {} again, abandoning the current job", name);
+                }
+            }
+        });
+
+        let grace_secs = std::env::var("JUDGE_SHUTDOWN_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_GRACE_SECS);
+
+        Ok(Self {
+            signals,
+            grace: Duration::from_secs(grace_secs),
+        })
+    }
+
+    /// Whether a shutdown was requested
+    pub fn is_requested(&self) -> bool {
+        *self.signals.borrow() > 0
+    }
+
+    /// Wait until a shutdown is requested
+    pub async fn requested(&mut self) {
+        let _ = self.signals.wait_for(|count| *count > 0).await;
+    }
+
+    /// Wait until the current job should be abandoned: the grace period after
+    /// the first signal ran out, or a second signal arrived
+    pub async fn abandon_job(&mut self) {
+        self.requested().await;
+        let mut signals = self.signals.clone();
+        tokio::select! {
+            _ = tokio::time::sleep(self.grace) => {
+                warn!("Shutdown grace period of {:?} expired", self.grace);
+            }
+            _ = signals.wait_for(|count| *count > 1) => {}
+        }
+    }
+}
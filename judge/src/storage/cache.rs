@@ -0,0 +1,270 @@
+//! On-disk testcase cache
+//!
+//! Objects are stored per version so that an updated testcase never collides
+//! with the copy cached before the update.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::info;
+
+/// Bytes of the SHA-256 digest kept in cache file names
+const HASH_BYTES: usize = 16;
+
+/// Size-bounded, least-recently-used on-disk cache of storage objects.
+///
+/// Each cached object is one file named `<hash(key)>-<hash(version)>`, so
+/// a new version of an object never collides with the old one. The hash is
+/// SHA-256 so names stay the same across builds and restarts. The LRU index
+/// lives in memory and is rebuilt from file modification times on startup.
+pub struct TestcaseCache {
+    dir: PathBuf,
+    index: Mutex<CacheIndex>,
+}
+
+impl TestcaseCache {
+    /// Open (or create) a cache directory, indexing the files already in it
+    pub async fn open(dir: PathBuf, max_bytes: u64) -> Result<Self> {
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let mut files = Vec::new();
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Leftovers from interrupted writes or an older naming scheme
+            if !is_cache_file_name(&name) {
+                let _ = tokio::fs::remove_file(entry.path()).await;
+                continue;
+            }
+            files.push((metadata.modified().ok(), name, metadata.len()));
+        }
+        files.sort();
+
+        let mut index = CacheIndex::new(max_bytes);
+        let mut evicted = Vec::new();
+        for (_, name, size) in files {
+            evicted.extend(index.insert(name, size));
+        }
+        for name in evicted {
+            let _ = tokio::fs::remove_file(dir.join(name)).await;
+        }
+
+        info!(
+            "Testcase cache at {:?}: {} files, {} bytes (max {} bytes)",
+            dir,
+            index.entries.len(),
+            index.total_bytes,
+            max_bytes
+        );
+
+        Ok(Self {
+            dir,
+            index: Mutex::new(index),
+        })
+    }
+
+    /// Read a cached object, if this version is cached
+    pub async fn get(&self, key: &str, version: &str) -> Option<Vec<u8>> {
+        let name = cache_file_name(key, version);
+        if !self.index.lock().unwrap().touch(&name) {
+            return None;
+        }
+        // The file may have been evicted in the meantime; that's just a miss
+        tokio::fs::read(self.dir.join(&name)).await.ok()
+    }
+
+    /// Store an object, replacing older versions and evicting the least
+    /// recently used files to stay within the size limit
+    pub async fn insert(&self, key: &str, version: &str, data: &[u8]) -> Result<()> {
+        let name = cache_file_name(key, version);
+        let path = self.dir.join(&name);
+
+        // Write to a unique temporary file first so readers never see partial
+        // data, even while other slots store the same testcase
+        let dir = self.dir.clone();
+        let contents = data.to_vec();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let mut tmp = tempfile::NamedTempFile::new_in(&dir)?;
+            tmp.write_all(&contents)?;
+            tmp.persist(&path)?;
+            Ok(())
+        })
+        .await??;
+
+        let key_prefix = format!("{}-", hash_hex(key));
+        let removed = {
+            let mut index = self.index.lock().unwrap();
+            let mut removed = index.remove_prefix(&key_prefix, &name);
+            removed.extend(index.insert(name, data.len() as u64));
+            removed
+        };
+        for name in removed {
+            let _ = tokio::fs::remove_file(self.dir.join(name)).await;
+        }
+
+        Ok(())
+    }
+}
+
+/// In-memory LRU bookkeeping for [`TestcaseCache`]
+struct CacheIndex {
+    max_bytes: u64,
+    total_bytes: u64,
+    /// File name -> (size, last use)
+    entries: HashMap<String, (u64, u64)>,
+    /// Monotonic use counter
+    clock: u64,
+}
+
+impl CacheIndex {
+    fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            total_bytes: 0,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Mark a file as used; returns whether it is cached
+    fn touch(&mut self, name: &str) -> bool {
+        self.clock += 1;
+        match self.entries.get_mut(name) {
+            Some((_, last_used)) => {
+                *last_used = self.clock;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add a file and return the names of the files evicted to make room.
+    ///
+    /// A file larger than the whole cache is evicted right away.
+    fn insert(&mut self, name: String, size: u64) -> Vec<String> {
+        self.clock += 1;
+        if let Some((old_size, _)) = self.entries.insert(name, (size, self.clock)) {
+            self.total_bytes -= old_size;
+        }
+        self.total_bytes += size;
+
+        let mut evicted = Vec::new();
+        while self.total_bytes > self.max_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(name, _)| name.clone())
+            else {
+                break;
+            };
+            if let Some((size, _)) = self.entries.remove(&oldest) {
+                self.total_bytes -= size;
+            }
+            evicted.push(oldest);
+        }
+        evicted
+    }
+
+    /// Remove every file starting with `prefix` except `keep`
+    fn remove_prefix(&mut self, prefix: &str, keep: &str) -> Vec<String> {
+        let names: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|name| name.starts_with(prefix) && *name != keep)
+            .cloned()
+            .collect();
+        for name in &names {
+            if let Some((size, _)) = self.entries.remove(name) {
+                self.total_bytes -= size;
+            }
+        }
+        names
+    }
+}
+
+/// Cache file name for an object version
+fn cache_file_name(key: &str, version: &str) -> String {
+    format!("{}-{}", hash_hex(key), hash_hex(version))
+}
+
+/// First 128 bits of the SHA-256 of `value`, in hex
+fn hash_hex(value: &str) -> String {
+    Sha256::digest(value.as_bytes())[..HASH_BYTES]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Whether a file in the cache directory was named by [`cache_file_name`]
+fn is_cache_file_name(name: &str) -> bool {
+    let is_hash =
+        |part: &str| part.len() == HASH_BYTES * 2 && part.bytes().all(|b| b.is_ascii_hexdigit());
+    name.split_once('-')
+        .is_some_and(|(key, version)| is_hash(key) && is_hash(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_index_evicts_least_recently_used() {
+        let mut index = CacheIndex::new(100);
+        assert!(index.insert("a".into(), 40).is_empty());
+        assert!(index.insert("b".into(), 40).is_empty());
+        assert!(index.touch("a"));
+
+        assert_eq!(index.insert("c".into(), 40), vec!["b".to_string()]);
+        assert!(!index.touch("b"));
+        assert_eq!(index.total_bytes, 80);
+    }
+
+    #[test]
+    fn test_cache_index_replaces_old_versions() {
+        let mut index = CacheIndex::new(100);
+        index.insert(cache_file_name("tc/1.in", "v1"), 10);
+        index.insert(cache_file_name("tc/2.in", "v1"), 10);
+
+        let current = cache_file_name("tc/1.in", "v2");
+        let prefix = format!("{}-", hash_hex("tc/1.in"));
+        let removed = index.remove_prefix(&prefix, &current);
+
+        assert_eq!(removed, vec![cache_file_name("tc/1.in", "v1")]);
+        assert_eq!(index.total_bytes, 10);
+    }
+
+    #[test]
+    fn test_cache_file_names_are_stable() {
+        let name = cache_file_name("tc/1.in", "v1");
+        assert!(name.starts_with("8c899b16b9c826302c26a0c175814746-"));
+        assert!(is_cache_file_name(&name));
+        assert!(!is_cache_file_name("0123456789abcdef-0123456789abcdef"));
+        assert!(!is_cache_file_name(".tmpA1b2C3"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_inserts_of_same_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TestcaseCache::open(dir.path().to_path_buf(), 1024)
+            .await
+            .unwrap();
+
+        let (a, b) = tokio::join!(
+            cache.insert("tc/1.in", "v1", b"1 2\n"),
+            cache.insert("tc/1.in", "v1", b"1 2\n"),
+        );
+        a.unwrap();
+        b.unwrap();
+
+        assert_eq!(cache.get("tc/1.in", "v1").await.unwrap(), b"1 2\n");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
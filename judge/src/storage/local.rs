@@ -0,0 +1,87 @@
+//! Local-directory storage backend
+//!
+//! Serves objects from a directory laid out like the bucket, e.g. the key
+//! `problems/1/testcases/1.in` maps to `<root>/problems/1/testcases/1.in`.
+//! Meant for development, offline contests and integration tests.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::{Component, Path, PathBuf};
+
+use super::StorageBackend;
+use crate::error::Transient;
+
+/// Objects stored as files under a root directory
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    /// Serve objects from `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Path of an object, refusing keys that would escape the root
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            anyhow::bail!("Invalid storage key: {}", key);
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn download(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.path(key)?;
+        let message = || format!("Failed to read {}", path.display());
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(data),
+            // A missing object won't appear on retry
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(e).with_context(message),
+            Err(e) => Err(e).with_context(|| Transient(message())),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> bool {
+        match self.path(key) {
+            Ok(path) => tokio::fs::try_exists(path).await.unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    /// Files are already on disk, so they are never cached
+    async fn version(&self, _key: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_storage_serves_files_under_root() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("problems/1")).unwrap();
+        std::fs::write(dir.path().join("problems/1/1.in"), "1 2\n").unwrap();
+
+        let storage = LocalStorage::new(dir.path());
+        assert_eq!(storage.download("problems/1/1.in").await.unwrap(), b"1 2\n");
+        assert!(storage.exists("problems/1/1.in").await);
+        assert!(!storage.exists("problems/1/2.in").await);
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_rejects_escaping_keys() {
+        let storage = LocalStorage::new("/srv/storage");
+        assert!(storage.download("../etc/passwd").await.is_err());
+        assert!(storage.download("/etc/passwd").await.is_err());
+        assert!(!storage.exists("problems/../../etc/passwd").await);
+    }
+}
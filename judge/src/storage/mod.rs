@@ -0,0 +1,169 @@
+//! Storage for testcases, problem tools and submissions
+//!
+//! Objects are fetched through a [`StorageBackend`]: MinIO/S3 by default, or
+//! a local directory when `STORAGE_BACKEND=local`. Testcase files from MinIO
+//! are kept in a size-bounded on-disk cache, keyed by object path and version
+//! (ETag, or Last-Modified when there is no ETag), so each worker pulls a
+//! testcase only once per version.
+
+pub mod cache;
+pub mod local;
+pub mod s3;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::metrics::{metrics, Timing};
+
+pub use cache::TestcaseCache;
+pub use local::LocalStorage;
+pub use s3::S3Storage;
+
+/// Default testcase cache location
+const DEFAULT_CACHE_DIR: &str = "/tmp/testcase_cache";
+/// Default testcase cache size (1GB)
+const DEFAULT_CACHE_MAX_MB: u64 = 1024;
+
+/// Where objects are read from
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Read a whole object
+    async fn download(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Read an object as text, normalizing CRLF line endings
+    async fn download_string(&self, key: &str) -> Result<String> {
+        bytes_to_string(self.download(key).await?)
+    }
+
+    /// Check if an object exists
+    async fn exists(&self, key: &str) -> bool;
+
+    /// Version of an object for the testcase cache; `None` bypasses the cache
+    async fn version(&self, key: &str) -> Result<Option<String>>;
+}
+
+/// Storage client shared by the job modules
+#[derive(Clone)]
+pub struct StorageClient {
+    backend: Arc<dyn StorageBackend>,
+    /// On-disk testcase cache (None when disabled)
+    cache: Option<Arc<TestcaseCache>>,
+}
+
+impl StorageClient {
+    /// Create a storage client without a testcase cache
+    pub fn new(backend: impl StorageBackend + 'static) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            cache: None,
+        }
+    }
+
+    /// Create a storage client from environment variables
+    pub async fn from_env() -> Result<Self> {
+        let backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "minio".into());
+        match backend.as_str() {
+            "minio" | "s3" => {
+                let mut client = Self::new(S3Storage::from_env());
+                client.cache = open_cache_from_env().await?.map(Arc::new);
+                Ok(client)
+            }
+            "local" => {
+                let root = std::env::var("STORAGE_LOCAL_DIR")
+                    .context("STORAGE_LOCAL_DIR is required for the local storage backend")?;
+                info!("Serving storage from local directory {}", root);
+                Ok(Self::new(LocalStorage::new(root)))
+            }
+            other => anyhow::bail!("Unknown STORAGE_BACKEND: {}", other),
+        }
+    }
+
+    /// Download a file
+    pub async fn download(&self, key: &str) -> Result<Vec<u8>> {
+        let started = Instant::now();
+        let data = self.backend.download(key).await?;
+        metrics().observe(Timing::StorageDownload, started.elapsed());
+        Ok(data)
+    }
+
+    /// Download a file as string
+    pub async fn download_string(&self, key: &str) -> Result<String> {
+        let bytes = self.download(key).await?;
+        bytes_to_string(bytes)
+    }
+
+    /// Download a testcase file, going through the on-disk cache.
+    ///
+    /// The object version is checked on every call, so an updated testcase is
+    /// never served stale.
+    pub async fn download_testcase(&self, key: &str) -> Result<Vec<u8>> {
+        let Some(cache) = &self.cache else {
+            return self.download(key).await;
+        };
+
+        let version = match self.backend.version(key).await {
+            Ok(Some(version)) => version,
+            Ok(None) => return self.download(key).await,
+            Err(e) => {
+                warn!(
+                    "Failed to check version of {}, bypassing cache: {:#}",
+                    key, e
+                );
+                return self.download(key).await;
+            }
+        };
+
+        if let Some(data) = cache.get(key, &version).await {
+            debug!("Testcase cache hit: {}", key);
+            return Ok(data);
+        }
+
+        debug!("Testcase cache miss: {}", key);
+        let data = self.download(key).await?;
+        if let Err(e) = cache.insert(key, &version, &data).await {
+            warn!("Failed to cache {}: {:#}", key, e);
+        }
+        Ok(data)
+    }
+
+    /// Download a testcase file as string, going through the on-disk cache
+    pub async fn download_testcase_string(&self, key: &str) -> Result<String> {
+        let bytes = self.download_testcase(key).await?;
+        bytes_to_string(bytes)
+    }
+
+    /// Check if a file exists
+    pub async fn exists(&self, key: &str) -> bool {
+        self.backend.exists(key).await
+    }
+}
+
+/// Open the testcase cache (`TESTCASE_CACHE_MAX_MB=0` disables it)
+async fn open_cache_from_env() -> Result<Option<TestcaseCache>> {
+    let cache_dir = std::env::var("TESTCASE_CACHE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_CACHE_DIR));
+    let cache_max_mb = std::env::var("TESTCASE_CACHE_MAX_MB")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_CACHE_MAX_MB);
+    if cache_max_mb == 0 {
+        info!("Testcase cache disabled");
+        return Ok(None);
+    }
+    let cache = TestcaseCache::open(cache_dir, cache_max_mb * 1024 * 1024)
+        .await
+        .context("Failed to open testcase cache")?;
+    Ok(Some(cache))
+}
+
+/// Decode downloaded text, normalizing CRLF line endings
+fn bytes_to_string(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes)
+        .context("Invalid UTF-8 content")
+        .map(|s| s.replace("\r\n", "\n"))
+}
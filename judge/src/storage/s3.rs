@@ -1,21 +1,24 @@
-//! MinIO/S3 storage client for fetching testcases
+//! MinIO/S3 storage backend
 
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use aws_config::BehaviorVersion;
 use aws_sdk_s3::config::{Credentials, Region};
 use aws_sdk_s3::Client;
 use tracing::info;
 
-/// S3/MinIO storage client
-#[derive(Clone)]
-pub struct StorageClient {
+use super::StorageBackend;
+use crate::error::Transient;
+
+/// Objects in a MinIO/S3 bucket
+pub struct S3Storage {
     client: Client,
     bucket: String,
 }
 
-impl StorageClient {
-    /// Create a new storage client from environment variables
-    pub async fn from_env() -> Result<Self> {
+impl S3Storage {
+    /// Connect to MinIO using the `MINIO_*` environment variables
+    pub fn from_env() -> Self {
         let endpoint = std::env::var("MINIO_ENDPOINT").unwrap_or_else(|_| "localhost".into());
         let port = std::env::var("MINIO_PORT").unwrap_or_else(|_| "9000".into());
         let access_key = std::env::var("MINIO_ACCESS_KEY").unwrap_or_else(|_| "minioadmin".into());
@@ -40,37 +43,42 @@ impl StorageClient {
             .force_path_style(true)
             .build();
 
-        let client = Client::from_conf(config);
-
-        Ok(Self { client, bucket })
+        Self {
+            client: Client::from_conf(config),
+            bucket,
+        }
     }
+}
 
-    /// Download a file from S3/MinIO
-    pub async fn download(&self, key: &str) -> Result<Vec<u8>> {
-        let response = self
+#[async_trait]
+impl StorageBackend for S3Storage {
+    async fn download(&self, key: &str) -> Result<Vec<u8>> {
+        let message = || format!("Failed to download {}", key);
+        let response = match self
             .client
             .get_object()
             .bucket(&self.bucket)
             .key(key)
             .send()
             .await
-            .with_context(|| format!("Failed to download {}", key))?;
+        {
+            Ok(response) => response,
+            // A missing object won't appear on retry
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                return Err(e).with_context(message);
+            }
+            Err(e) => return Err(e).with_context(|| Transient(message())),
+        };
 
-        let data = response.body.collect().await?;
+        let data = response
+            .body
+            .collect()
+            .await
+            .with_context(|| Transient(message()))?;
         Ok(data.into_bytes().to_vec())
     }
 
-    /// Download a file as string
-    pub async fn download_string(&self, key: &str) -> Result<String> {
-        let bytes = self.download(key).await?;
-        String::from_utf8(bytes)
-            .context("Invalid UTF-8 content")
-            .map(|s| s.replace("\r\n", "\n"))
-    }
-
-    /// Check if a file exists
-    #[allow(dead_code)]
-    pub async fn exists(&self, key: &str) -> bool {
+    async fn exists(&self, key: &str) -> bool {
         self.client
             .head_object()
             .bucket(&self.bucket)
@@ -79,4 +87,21 @@ impl StorageClient {
             .await
             .is_ok()
     }
+
+    /// The object's ETag, or Last-Modified if it has none
+    async fn version(&self, key: &str) -> Result<Option<String>> {
+        let response = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .with_context(|| format!("Failed to head {}", key))?;
+
+        Ok(response
+            .e_tag()
+            .map(str::to_string)
+            .or_else(|| response.last_modified().map(|t| t.to_string())))
+    }
 }
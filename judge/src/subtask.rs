@@ -0,0 +1,315 @@
+//! Subtask (group) scoring for IOI-style problems
+//!
+//! Testcases can be grouped into subtasks that each carry their own points.
+//! A subtask's score is aggregated from the scores of its testcases according
+//! to its policy, and a subtask only scores when all of its dependencies are
+//! fully solved.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::checker::Verdict;
+
+/// How testcase results within a subtask are combined
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoringPolicy {
+    /// Lowest testcase score wins (all-or-nothing for binary verdicts)
+    #[default]
+    Min,
+    /// Points are split evenly across the subtask's testcases
+    Sum,
+}
+
+impl ScoringPolicy {
+    /// Combine testcase score ratios into one ratio (0.0 without testcases)
+    pub fn aggregate(self, ratios: &[f64]) -> f64 {
+        if ratios.is_empty() {
+            return 0.0;
+        }
+        match self {
+            ScoringPolicy::Min => ratios.iter().copied().fold(1.0, f64::min),
+            ScoringPolicy::Sum => ratios.iter().sum::<f64>() / ratios.len() as f64,
+        }
+    }
+}
+
+/// Subtask definition received with a judge job
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubtaskInfo {
+    pub id: i64,
+    /// Points awarded when the subtask is fully solved
+    pub score: i64,
+    #[serde(default)]
+    pub policy: ScoringPolicy,
+    /// Subtasks that must be fully solved for this one to score
+    #[serde(default)]
+    pub dependencies: Vec<i64>,
+}
+
+/// Per-subtask scoring result
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubtaskResult {
+    pub subtask_id: i64,
+    pub verdict: String,
+    pub score: i64,
+    pub max_score: i64,
+}
+
+/// Outcome of a single testcase, as seen by subtask aggregation
+#[derive(Debug, Clone)]
+pub struct TestcaseOutcome {
+    pub subtask_id: Option<i64>,
+    pub verdict: Verdict,
+    /// Fraction of the testcase's points earned (0.0 ~ 1.0)
+    pub ratio: f64,
+}
+
+/// Tracks failed subtasks while testcases are running, so that testcases
+/// which can no longer change the score are skipped.
+#[derive(Debug, Default)]
+pub struct SubtaskTracker {
+    /// Subtasks that can no longer be fully solved
+    failed: HashSet<i64>,
+    /// Subtasks that already have a zero-score testcase
+    zeroed: HashSet<i64>,
+}
+
+impl SubtaskTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a testcase of the given subtask can be skipped.
+    ///
+    /// Min-policy subtasks stop at their first zero-score testcase (partial
+    /// scores keep running since a later testcase may lower the minimum), and
+    /// any subtask whose dependency already failed cannot score anymore.
+    pub fn should_skip(&mut self, subtask: &SubtaskInfo) -> bool {
+        if subtask.dependencies.iter().any(|d| self.failed.contains(d)) {
+            // Propagate so that subtasks depending on this one are skipped too
+            self.failed.insert(subtask.id);
+            return true;
+        }
+        subtask.policy == ScoringPolicy::Min && self.zeroed.contains(&subtask.id)
+    }
+
+    /// Record the outcome of a testcase of the given subtask
+    pub fn record(&mut self, subtask: &SubtaskInfo, ratio: f64) {
+        if ratio < 1.0 {
+            self.failed.insert(subtask.id);
+        }
+        if ratio <= 0.0 {
+            self.zeroed.insert(subtask.id);
+        }
+    }
+}
+
+/// Aggregate testcase outcomes into per-subtask results.
+///
+/// Results are returned in the order the subtasks were given. Testcases that
+/// do not belong to any known subtask are ignored.
+pub fn score_subtasks(
+    subtasks: &[SubtaskInfo],
+    outcomes: &[TestcaseOutcome],
+) -> Vec<SubtaskResult> {
+    // Raw ratio and first failing verdict of each subtask, ignoring dependencies
+    let mut raw: HashMap<i64, (f64, Verdict)> = HashMap::new();
+    for subtask in subtasks {
+        let cases: Vec<&TestcaseOutcome> = outcomes
+            .iter()
+            .filter(|o| o.subtask_id == Some(subtask.id))
+            .collect();
+
+        let ratios: Vec<f64> = cases.iter().map(|o| o.ratio).collect();
+        let ratio = subtask.policy.aggregate(&ratios);
+        let verdict = cases
+            .iter()
+            .map(|o| o.verdict.clone())
+            .find(|v| *v != Verdict::Accepted)
+            .unwrap_or(if cases.is_empty() {
+                Verdict::Skipped
+            } else {
+                Verdict::Accepted
+            });
+
+        raw.insert(subtask.id, (ratio.clamp(0.0, 1.0), verdict));
+    }
+
+    let deps: HashMap<i64, &[i64]> = subtasks
+        .iter()
+        .map(|s| (s.id, s.dependencies.as_slice()))
+        .collect();
+
+    subtasks
+        .iter()
+        .map(|subtask| {
+            let (ratio, verdict) = raw[&subtask.id].clone();
+            let deps_solved = subtask
+                .dependencies
+                .iter()
+                .all(|d| is_fully_solved(*d, &raw, &deps, &mut HashSet::new()));
+            let score = if deps_solved {
+                (subtask.score as f64 * ratio).floor() as i64
+            } else {
+                0
+            };
+
+            SubtaskResult {
+                subtask_id: subtask.id,
+                verdict: verdict.to_string(),
+                score,
+                max_score: subtask.score,
+            }
+        })
+        .collect()
+}
+
+/// A subtask is fully solved when it earned all of its points and every
+/// subtask it depends on (transitively) is fully solved as well.
+fn is_fully_solved(
+    id: i64,
+    raw: &HashMap<i64, (f64, Verdict)>,
+    deps: &HashMap<i64, &[i64]>,
+    visiting: &mut HashSet<i64>,
+) -> bool {
+    // Unknown subtasks and dependency cycles never count as solved
+    if !visiting.insert(id) {
+        return false;
+    }
+    let solved = match raw.get(&id) {
+        Some((ratio, _)) if *ratio >= 1.0 => deps
+            .get(&id)
+            .map(|ds| ds.iter().all(|d| is_fully_solved(*d, raw, deps, visiting)))
+            .unwrap_or(true),
+        _ => false,
+    };
+    visiting.remove(&id);
+    solved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subtask(id: i64, score: i64, policy: ScoringPolicy, dependencies: Vec<i64>) -> SubtaskInfo {
+        SubtaskInfo {
+            id,
+            score,
+            policy,
+            dependencies,
+        }
+    }
+
+    fn outcome(subtask_id: i64, verdict: Verdict) -> TestcaseOutcome {
+        let ratio = if verdict == Verdict::Accepted {
+            1.0
+        } else {
+            0.0
+        };
+        TestcaseOutcome {
+            subtask_id: Some(subtask_id),
+            verdict,
+            ratio,
+        }
+    }
+
+    #[test]
+    fn test_min_policy_is_all_or_nothing() {
+        let subtasks = vec![
+            subtask(1, 30, ScoringPolicy::Min, vec![]),
+            subtask(2, 70, ScoringPolicy::Min, vec![]),
+        ];
+        let outcomes = vec![
+            outcome(1, Verdict::Accepted),
+            outcome(1, Verdict::Accepted),
+            outcome(2, Verdict::Accepted),
+            outcome(2, Verdict::TimeLimitExceeded),
+        ];
+
+        let results = score_subtasks(&subtasks, &outcomes);
+        assert_eq!(results[0].score, 30);
+        assert_eq!(results[0].verdict, "accepted");
+        assert_eq!(results[1].score, 0);
+        assert_eq!(results[1].verdict, "time_limit_exceeded");
+    }
+
+    #[test]
+    fn test_sum_policy_is_proportional() {
+        let subtasks = vec![subtask(1, 40, ScoringPolicy::Sum, vec![])];
+        let outcomes = vec![
+            outcome(1, Verdict::Accepted),
+            outcome(1, Verdict::WrongAnswer),
+            outcome(1, Verdict::Accepted),
+            outcome(1, Verdict::Accepted),
+        ];
+
+        let results = score_subtasks(&subtasks, &outcomes);
+        assert_eq!(results[0].score, 30);
+        assert_eq!(results[0].verdict, "wrong_answer");
+    }
+
+    #[test]
+    fn test_partial_scores() {
+        let subtasks = vec![
+            subtask(1, 50, ScoringPolicy::Min, vec![]),
+            subtask(2, 50, ScoringPolicy::Sum, vec![]),
+        ];
+        let partial = |subtask_id, ratio| TestcaseOutcome {
+            subtask_id: Some(subtask_id),
+            verdict: Verdict::Partial,
+            ratio,
+        };
+        let outcomes = vec![
+            outcome(1, Verdict::Accepted),
+            partial(1, 0.5),
+            partial(1, 0.8),
+            partial(2, 0.5),
+            outcome(2, Verdict::Accepted),
+        ];
+
+        let results = score_subtasks(&subtasks, &outcomes);
+        assert_eq!(results[0].score, 25);
+        assert_eq!(results[0].verdict, "partial");
+        assert_eq!(results[1].score, 37);
+    }
+
+    #[test]
+    fn test_dependency_failure_zeroes_score() {
+        let subtasks = vec![
+            subtask(1, 30, ScoringPolicy::Min, vec![]),
+            subtask(2, 30, ScoringPolicy::Min, vec![1]),
+            subtask(3, 40, ScoringPolicy::Min, vec![2]),
+        ];
+        let outcomes = vec![
+            outcome(1, Verdict::WrongAnswer),
+            outcome(2, Verdict::Accepted),
+            outcome(3, Verdict::Accepted),
+        ];
+
+        let results = score_subtasks(&subtasks, &outcomes);
+        assert_eq!(results[0].score, 0);
+        assert_eq!(results[1].score, 0);
+        assert_eq!(results[2].score, 0);
+    }
+
+    #[test]
+    fn test_tracker_skips_failed_min_subtask_and_dependents() {
+        let first = subtask(1, 30, ScoringPolicy::Min, vec![]);
+        let partial = subtask(2, 30, ScoringPolicy::Sum, vec![]);
+        let dependent = subtask(3, 40, ScoringPolicy::Sum, vec![1]);
+
+        let mut tracker = SubtaskTracker::new();
+        assert!(!tracker.should_skip(&first));
+        tracker.record(&first, 0.5);
+        assert!(!tracker.should_skip(&first));
+        tracker.record(&first, 0.0);
+        assert!(tracker.should_skip(&first));
+
+        tracker.record(&partial, 0.0);
+        assert!(!tracker.should_skip(&partial));
+
+        assert!(tracker.should_skip(&dependent));
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::Context;
 use std::io::{Read, Seek};
 use std::path::Path;
 use zip::ZipArchive;
@@ -30,3 +31,10 @@ pub fn extract_zip<R: Read + Seek>(data: R, dest: &Path) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Get the file name component of a path as `&str`
+pub fn file_name(path: &Path) -> anyhow::Result<&str> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid file path: {:?}", path))
+}
@@ -9,9 +9,12 @@ use std::path::Path;
 use tokio::fs;
 use tracing::{debug, info, warn};
 
-use crate::compiler::ValidatorCompiler;
-use crate::executer::{execute_trusted, ExecutionLimits, ExecutionSpec};
+use crate::compiler::{TestlibCompiler, TestlibTool};
+use crate::executer::{execute_sandboxed, execute_trusted, ExecutionLimits, ExecutionSpec};
+use crate::progress::{ProgressEvent, ProgressSink, ProgressSubject};
+use crate::sandbox::get_config;
 use crate::storage::StorageClient;
+use crate::utils::file_name;
 
 /// Validation job received from Redis queue
 #[derive(Debug, Serialize, Deserialize)]
@@ -86,16 +89,20 @@ pub async fn run_validator(
 
     // Build execution spec for validator (no args, input via stdin)
     let spec = ExecutionSpec::new(validator_path.parent().unwrap_or(Path::new(".")))
-        .with_command([validator_path.to_str().unwrap_or("")])
         .with_limits(ExecutionLimits {
             time_ms: (timeout_secs * 1000) as u32,
             memory_mb: 512,
         })
         .with_stdin(&input_content);
 
-    let result = execute_trusted(&spec)
-        .await
-        .context("Failed to run validator")?;
+    let result = if get_config().trusted_tools {
+        execute_trusted(&spec.with_command([validator_path.to_str().unwrap_or("")])).await
+    } else {
+        // The validator binary is copied into the box with the rest of its directory
+        let command = format!("./{}", file_name(validator_path)?);
+        execute_sandboxed(&spec.with_command([command])).await
+    }
+    .context("Failed to run validator")?;
 
     debug!(
         "Validator result: exit_code={}, stderr={}",
@@ -118,14 +125,20 @@ pub async fn run_validator(
 /// Validator manager for handling validator compilation and caching
 pub struct ValidatorManager {
     /// Compiler for validators
-    compiler: ValidatorCompiler,
+    compiler: TestlibCompiler,
+}
+
+impl Default for ValidatorManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ValidatorManager {
     /// Create a new validator manager
     pub fn new() -> Self {
         Self {
-            compiler: ValidatorCompiler::new(),
+            compiler: TestlibCompiler::new(TestlibTool::Validator),
         }
     }
 
@@ -160,14 +173,25 @@ pub async fn process_validate_job(
     job: &ValidateJob,
     storage: &StorageClient,
     validator_manager: &ValidatorManager,
+    progress: &mut dyn ProgressSink,
 ) -> Result<ValidateResult> {
     info!("Processing validation job for problem {}", job.problem_id);
+    let subject = ProgressSubject::ProblemId(job.problem_id);
 
     // Get compiled validator
-    let validator_path = match validator_manager
+    progress.publish(subject, ProgressEvent::CompileStart).await;
+    let validator = validator_manager
         .get_validator(storage, &job.validator_path, job.problem_id)
-        .await
-    {
+        .await;
+    progress
+        .publish(
+            subject,
+            ProgressEvent::CompileDone {
+                success: validator.is_ok(),
+            },
+        )
+        .await;
+    let validator_path = match validator {
         Ok(path) => path,
         Err(e) => {
             return Ok(ValidateResult {
@@ -185,48 +209,22 @@ pub async fn process_validate_job(
     // Create temp directory for input files
     let temp_dir = tempfile::tempdir()?;
 
-    for tc in &job.testcase_inputs {
-        // Download testcase input
-        let input_content = match storage.download_string(&tc.input_path).await {
-            Ok(content) => content,
-            Err(e) => {
-                warn!("Failed to download testcase input {}: {}", tc.id, e);
-                testcase_results.push(TestcaseValidationResult {
-                    testcase_id: tc.id,
-                    valid: false,
-                    message: Some(format!("Failed to download input: {}", e)),
-                });
-                all_valid = false;
-                continue;
-            }
-        };
+    let total = job.testcase_inputs.len();
+    for (idx, tc) in job.testcase_inputs.iter().enumerate() {
+        let result = validate_testcase(storage, &validator_path, temp_dir.path(), tc).await?;
+        if !result.valid {
+            all_valid = false;
+        }
 
-        // Write input to temp file
-        let input_path = temp_dir.path().join(format!("input_{}.txt", tc.id));
-        tokio::fs::write(&input_path, &input_content).await?;
+        let verdict = if result.valid { "valid" } else { "invalid" };
+        progress
+            .publish(
+                subject,
+                ProgressEvent::testcase(tc.id, idx + 1, total, verdict, None, None),
+            )
+            .await;
 
-        // Run validator
-        match run_validator(&validator_path, &input_path, DEFAULT_VALIDATOR_TIMEOUT_SECS).await {
-            Ok((valid, message)) => {
-                if !valid {
-                    all_valid = false;
-                }
-                testcase_results.push(TestcaseValidationResult {
-                    testcase_id: tc.id,
-                    valid,
-                    message,
-                });
-            }
-            Err(e) => {
-                warn!("Validator error for testcase {}: {}", tc.id, e);
-                testcase_results.push(TestcaseValidationResult {
-                    testcase_id: tc.id,
-                    valid: false,
-                    message: Some(format!("Validator error: {}", e)),
-                });
-                all_valid = false;
-            }
-        }
+        testcase_results.push(result);
     }
 
     Ok(ValidateResult {
@@ -237,6 +235,50 @@ pub async fn process_validate_job(
     })
 }
 
+/// Download one testcase input and run the validator on it
+async fn validate_testcase(
+    storage: &StorageClient,
+    validator_path: &Path,
+    temp_dir: &Path,
+    tc: &TestcaseInput,
+) -> Result<TestcaseValidationResult> {
+    // Download testcase input
+    let input_content = match storage.download_testcase_string(&tc.input_path).await {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("Failed to download testcase input {}: {}", tc.id, e);
+            return Ok(TestcaseValidationResult {
+                testcase_id: tc.id,
+                valid: false,
+                message: Some(format!("Failed to download input: {}", e)),
+            });
+        }
+    };
+
+    // Write input to temp file
+    let input_path = temp_dir.join(format!("input_{}.txt", tc.id));
+    tokio::fs::write(&input_path, &input_content).await?;
+
+    // Run validator
+    let result =
+        match run_validator(validator_path, &input_path, DEFAULT_VALIDATOR_TIMEOUT_SECS).await {
+            Ok((valid, message)) => TestcaseValidationResult {
+                testcase_id: tc.id,
+                valid,
+                message,
+            },
+            Err(e) => {
+                warn!("Validator error for testcase {}: {}", tc.id, e);
+                TestcaseValidationResult {
+                    testcase_id: tc.id,
+                    valid: false,
+                    message: Some(format!("Validator error: {}", e)),
+                }
+            }
+        };
+    Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
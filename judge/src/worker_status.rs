@@ -0,0 +1,129 @@
+//! Worker status reporting
+//!
+//! Each worker keeps a `judge:worker:status:{id}` hash up to date so admins
+//! can see what it is doing. The hash is written when a job starts or ends
+//! and refreshed by the lease heartbeat; it expires with the lease, so the
+//! hashes of dead workers disappear on their own.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What a worker is doing and how it has been doing
+#[derive(Debug, Clone)]
+pub struct WorkerStatus {
+    pub host: String,
+    pub worker_id: u32,
+    /// Job type of the current job (`judge`, `validate`, ...)
+    pub job_type: Option<&'static str>,
+    /// Subject of the current job, e.g. `submission:42`
+    pub current_job: Option<String>,
+    /// When the current job started
+    pub job_started_at: Option<SystemTime>,
+    pub jobs_done: u64,
+    /// Sum of the judge times of all finished jobs
+    pub total_job_time: Duration,
+    /// Whether isolate's cgroup support was usable at startup
+    pub cgroups_available: Option<bool>,
+    pub started_at: SystemTime,
+}
+
+impl WorkerStatus {
+    pub fn new(host: String, worker_id: u32) -> Self {
+        Self {
+            host,
+            worker_id,
+            job_type: None,
+            current_job: None,
+            job_started_at: None,
+            jobs_done: 0,
+            total_job_time: Duration::ZERO,
+            cgroups_available: None,
+            started_at: SystemTime::now(),
+        }
+    }
+
+    /// Record the start of a job
+    pub fn begin_job(&mut self, job_type: &'static str, current_job: String) {
+        self.job_type = Some(job_type);
+        self.current_job = Some(current_job);
+        self.job_started_at = Some(SystemTime::now());
+    }
+
+    /// Record the end of the current job
+    pub fn finish_job(&mut self) {
+        if let Some(started) = self.job_started_at.take() {
+            self.jobs_done += 1;
+            self.total_job_time += started.elapsed().unwrap_or_default();
+        }
+        self.job_type = None;
+        self.current_job = None;
+    }
+
+    /// Average judge time of the finished jobs
+    pub fn average_job_time(&self) -> Duration {
+        match u32::try_from(self.jobs_done) {
+            Ok(0) => Duration::ZERO,
+            Ok(jobs) => self.total_job_time / jobs,
+            Err(_) => Duration::ZERO,
+        }
+    }
+
+    /// Fields of the status hash. Times are Unix timestamps in seconds and
+    /// fields without a value are written as empty strings.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let busy = self.current_job.is_some();
+        vec![
+            ("host", self.host.clone()),
+            ("worker_id", self.worker_id.to_string()),
+            ("state", if busy { "busy" } else { "idle" }.to_string()),
+            ("job_type", self.job_type.unwrap_or_default().to_string()),
+            ("current_job", self.current_job.clone().unwrap_or_default()),
+            (
+                "job_started_at",
+                self.job_started_at.map(unix_secs).unwrap_or_default(),
+            ),
+            ("jobs_done", self.jobs_done.to_string()),
+            (
+                "avg_job_ms",
+                self.average_job_time().as_millis().to_string(),
+            ),
+            (
+                "cgroups",
+                self.cgroups_available
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("version", env!("CARGO_PKG_VERSION").to_string()),
+            ("started_at", unix_secs(self.started_at)),
+            ("updated_at", unix_secs(SystemTime::now())),
+        ]
+    }
+}
+
+fn unix_secs(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_bookkeeping() {
+        let mut status = WorkerStatus::new("judge-1".into(), 3);
+        assert_eq!(status.average_job_time(), Duration::ZERO);
+
+        status.begin_job("judge", "submission:42".into());
+        let fields = status.fields();
+        assert!(fields.contains(&("state", "busy".into())));
+        assert!(fields.contains(&("current_job", "submission:42".into())));
+
+        status.finish_job();
+        status.finish_job();
+        assert_eq!(status.jobs_done, 1);
+        assert!(status.fields().contains(&("state", "idle".into())));
+        assert!(status.fields().contains(&("job_type", String::new())));
+    }
+}
@@ -0,0 +1,2 @@
+-- Add output_limit_exceeded verdict
+ALTER TYPE "public"."verdict" ADD VALUE IF NOT EXISTS 'output_limit_exceeded' AFTER 'memory_limit_exceeded';
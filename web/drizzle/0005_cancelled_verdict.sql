@@ -0,0 +1,2 @@
+-- Add cancelled verdict
+ALTER TYPE "public"."verdict" ADD VALUE IF NOT EXISTS 'cancelled';
@@ -0,0 +1,2 @@
+-- Add restricted_function verdict
+ALTER TYPE "public"."verdict" ADD VALUE IF NOT EXISTS 'restricted_function' AFTER 'runtime_error';
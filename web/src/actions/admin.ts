@@ -6,6 +6,7 @@ import { db } from "@/db";
 import { contestProblems, type ProblemType, problems, testcases, users } from "@/db/schema";
 import { recalculateContestBonus } from "@/lib/anigma-bonus";
 import { requireAdmin } from "@/lib/auth-utils";
+import { JUDGE_QUEUES } from "@/lib/judge-queue";
 import { getRedisClient } from "@/lib/redis";
 import {
 	deleteAllProblemFiles,
@@ -442,6 +443,7 @@ export async function validateTestcases(problemId: number) {
 	// Create validation job with job_type for unified queue
 	const validateJob = {
 		job_type: "validate",
+		queued_at: Date.now(),
 		problem_id: problemId,
 		validator_path: problem.validatorPath,
 		testcase_inputs: testcaseList.map((tc) => ({
@@ -450,9 +452,9 @@ export async function validateTestcases(problemId: number) {
 		})),
 	};
 
-	// Push to unified judge queue
+	// Push to validation queue
 	const redis = await getRedisClient();
-	await redis.lpush("judge:queue", JSON.stringify(validateJob));
+	await redis.rpush(JUDGE_QUEUES.validation, JSON.stringify(validateJob));
 
 	return {
 		success: true,
@@ -5,6 +5,7 @@ import JSZip from "jszip";
 import { db } from "@/db";
 import { problems, submissions, testcases } from "@/db/schema";
 import { ANIGMA_TASK2_BASE_SCORE, ANIGMA_TASK2_BONUS } from "@/lib/anigma-bonus";
+import { enqueueSubmissionJob } from "@/lib/judge-queue";
 import { getRedisClient } from "@/lib/redis";
 import { uploadFile } from "@/lib/storage";
 
@@ -120,10 +121,13 @@ export async function submitAnigmaTask1(data: {
 
 		// 5. Judge Job 큐에 추가
 		const redis = await getRedisClient();
-		await redis.rpush(
-			"judge:queue",
+		await enqueueSubmissionJob(
+			redis,
+			submission.id,
+			data.contestId,
 			JSON.stringify({
 				job_type: "anigma_task1",
+				queued_at: Date.now(),
 				submission_id: submission.id,
 				problem_id: data.problemId,
 				input_path: inputPath,
@@ -250,10 +254,13 @@ export async function submitAnigmaCode(data: {
 
 		// 5. Judge Job 큐에 추가
 		const redis = await getRedisClient();
-		await redis.rpush(
-			"judge:queue",
+		await enqueueSubmissionJob(
+			redis,
+			submission.id,
+			data.contestId,
 			JSON.stringify({
 				job_type: "anigma",
+				queued_at: Date.now(),
 				submission_id: submission.id,
 				problem_id: data.problemId,
 				zip_path: zipPath,
@@ -337,6 +337,7 @@ export async function submitCode(data: {
 			})),
 			problemType: problem[0].problemType,
 			checkerPath: problem[0].checkerPath,
+			contestId: data.contestId,
 		});
 
 		revalidatePath("/submissions");
@@ -361,13 +362,16 @@ async function pushJudgeJob(job: {
 	testcases: { id: number; inputPath: string; outputPath: string }[];
 	problemType: string;
 	checkerPath: string | null;
+	contestId?: number;
 }) {
 	// Import redis client dynamically to avoid client-side import
 	const { getRedisClient } = await import("@/lib/redis");
+	const { enqueueSubmissionJob } = await import("@/lib/judge-queue");
 	const redis = await getRedisClient();
 
 	const jobData = JSON.stringify({
 		job_type: "judge",
+		queued_at: Date.now(),
 		submission_id: job.submissionId,
 		problem_id: job.problemId,
 		code: job.code,
@@ -387,7 +391,7 @@ async function pushJudgeJob(job: {
 		checker_path: job.checkerPath,
 	});
 
-	await redis.rpush("judge:queue", jobData);
+	await enqueueSubmissionJob(redis, job.submissionId, job.contestId, jobData);
 
 	// Update submission status to judging
 	await db
@@ -396,6 +400,36 @@ async function pushJudgeJob(job: {
 		.where(eq(submissions.id, job.submissionId));
 }
 
+// Ask the judge to stop a pending or judging submission
+export async function cancelSubmission(submissionId: number) {
+	const session = await auth();
+	if (!session?.user?.id) {
+		return { error: "로그인이 필요합니다." };
+	}
+	const isAdmin = session.user.role === "admin";
+	const currentUserId = parseInt(session.user.id, 10);
+
+	const [submission] = await db
+		.select({ userId: submissions.userId, verdict: submissions.verdict })
+		.from(submissions)
+		.where(eq(submissions.id, submissionId))
+		.limit(1);
+
+	if (!submission || (!isAdmin && submission.userId !== currentUserId)) {
+		return { error: "제출을 찾을 수 없습니다." };
+	}
+	if (submission.verdict !== "pending" && submission.verdict !== "judging") {
+		return { error: "이미 채점이 끝난 제출입니다." };
+	}
+
+	const { getRedisClient } = await import("@/lib/redis");
+	const { JUDGE_CANCEL_PREFIX, JUDGE_CANCEL_TTL_SECONDS } = await import("@/lib/judge-queue");
+	const redis = await getRedisClient();
+	await redis.set(`${JUDGE_CANCEL_PREFIX}${submissionId}`, "1", "EX", JUDGE_CANCEL_TTL_SECONDS);
+
+	return { success: true };
+}
+
 export type GetSubmissionsReturn = Awaited<ReturnType<typeof getSubmissions>>;
 
 // Get user's best submission status for multiple problems
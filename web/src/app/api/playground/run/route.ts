@@ -1,6 +1,7 @@
 import { type NextRequest, NextResponse } from "next/server";
 import { getPlaygroundSession, requirePlaygroundAccess } from "@/actions/playground";
 import { auth } from "@/auth";
+import { JUDGE_QUEUES } from "@/lib/judge-queue";
 import { getRedisClient } from "@/lib/redis";
 
 export async function POST(request: NextRequest) {
@@ -101,6 +102,7 @@ export async function POST(request: NextRequest) {
 	// Job 생성
 	const job = {
 		job_type: "playground",
+		queued_at: Date.now(),
 		session_id: sessionId,
 		target_path: targetPath,
 		files: filesWithContent,
@@ -115,7 +117,7 @@ export async function POST(request: NextRequest) {
 	};
 
 	// Job 큐에 추가
-	await redis.rpush("judge:queue", JSON.stringify(job));
+	await redis.rpush(JUDGE_QUEUES.playground, JSON.stringify(job));
 
 	// 결과 대기 (최대 30초)
 	// BLPOP returns [key, value]
@@ -4,6 +4,9 @@ import { Loader2 } from "lucide-react";
 import { useRouter } from "next/navigation";
 import { useEffect, useRef, useState } from "react";
 import { Badge } from "@/components/ui/badge";
+import type { JudgeProgressEvent } from "@/lib/judge-progress";
+
+type TestcaseProgress = Extract<JudgeProgressEvent, { event: "testcase" }>;
 
 interface SubmissionStatusProps {
 	submissionId: number;
@@ -19,15 +22,30 @@ const VERDICT_LABELS: Record<string, { label: string; color: string }> = {
 	wrong_answer: { label: "오답", color: "bg-rose-500" },
 	time_limit_exceeded: { label: "시간 초과", color: "bg-amber-500" },
 	memory_limit_exceeded: { label: "메모리 초과", color: "bg-orange-500" },
+	output_limit_exceeded: { label: "출력 초과", color: "bg-orange-600" },
 	runtime_error: { label: "런타임 에러", color: "bg-purple-500" },
+	restricted_function: { label: "제한된 함수", color: "bg-purple-700" },
 	compile_error: { label: "컴파일 에러", color: "bg-pink-500" },
 	system_error: { label: "시스템 에러", color: "bg-red-500" },
 	partial: { label: "부분 점수", color: "bg-yellow-500" },
 	skipped: { label: "건너뜀", color: "bg-gray-400" },
 	presentation_error: { label: "출력 형식 에러", color: "bg-orange-400" },
 	fail: { label: "실패", color: "bg-red-600" },
+	cancelled: { label: "채점 취소", color: "bg-gray-600" },
 };
 
+// 예: "테스트 7: 정답, 120 ms, 14 MB"
+function formatTestcaseProgress(testcase: TestcaseProgress) {
+	const parts = [VERDICT_LABELS[testcase.verdict]?.label ?? testcase.verdict];
+	if (testcase.time_ms !== undefined) {
+		parts.push(`${testcase.time_ms} ms`);
+	}
+	if (testcase.memory_kb !== undefined) {
+		parts.push(`${Math.round(testcase.memory_kb / 1024)} MB`);
+	}
+	return `테스트 ${testcase.index}: ${parts.join(", ")}`;
+}
+
 export function SubmissionStatus({
 	submissionId,
 	initialVerdict,
@@ -42,6 +60,8 @@ export function SubmissionStatus({
 	);
 	const [displayProgress, setDisplayProgress] = useState(0);
 	const [targetProgress, setTargetProgress] = useState(0);
+	const [isCompiling, setIsCompiling] = useState(false);
+	const [lastTestcase, setLastTestcase] = useState<TestcaseProgress | null>(null);
 	const animationRef = useRef<number | null>(null);
 
 	// 부드러운 진행률 애니메이션
@@ -89,8 +109,15 @@ export function SubmissionStatus({
 
 			eventSource.addEventListener("progress", (event) => {
 				if (!isCancelled) {
-					const data = JSON.parse(event.data);
-					setTargetProgress(data.percentage);
+					const data: JudgeProgressEvent = JSON.parse(event.data);
+					if (data.event === "compile_start") {
+						setIsCompiling(true);
+					} else if (data.event === "compile_done") {
+						setIsCompiling(false);
+					} else if (data.event === "testcase") {
+						setLastTestcase(data);
+						setTargetProgress(data.percentage);
+					}
 				}
 			});
 
@@ -148,7 +175,11 @@ export function SubmissionStatus({
 
 	// 채점 중일 때 진행률 표시
 	if (isJudging) {
-		const statusText = displayProgress === 0 ? "채점 준비 중" : `채점 중 (${displayProgress}%)`;
+		const statusText = isCompiling
+			? "컴파일 중"
+			: displayProgress === 0
+				? "채점 준비 중"
+				: `채점 중 (${displayProgress}%)`;
 
 		return (
 			<div className="flex flex-col gap-2">
@@ -164,6 +195,11 @@ export function SubmissionStatus({
 						/>
 					</div>
 				)}
+				{lastTestcase && (
+					<span className="text-xs text-muted-foreground">
+						{formatTestcaseProgress(lastTestcase)}
+					</span>
+				)}
 			</div>
 		);
 	}
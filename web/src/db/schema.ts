@@ -19,13 +19,16 @@ export const verdictEnum = pgEnum("verdict", [
 	"wrong_answer",
 	"time_limit_exceeded",
 	"memory_limit_exceeded",
+	"output_limit_exceeded",
 	"runtime_error",
+	"restricted_function",
 	"compile_error",
 	"system_error",
 	"skipped",
 	"presentation_error",
 	"fail",
 	"partial", // Anigma partial score
+	"cancelled",
 ]);
 export const languageEnum = pgEnum("language", ["c", "cpp", "python", "java"]);
 export const problemTypeEnum = pgEnum("problem_type", ["icpc", "special_judge", "anigma"]);
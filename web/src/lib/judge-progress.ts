@@ -0,0 +1,23 @@
+/**
+ * Progress events the judge publishes on `judge:progress` while a job runs.
+ * Submission events carry `submission_id`; validation events carry `problem_id`.
+ */
+export type JudgeProgressEvent =
+	| { event: "compile_start" }
+	| { event: "compile_done"; success: boolean }
+	| {
+			event: "testcase";
+			testcase_id: number;
+			index: number;
+			total: number;
+			percentage: number;
+			verdict: string;
+			time_ms?: number;
+			memory_kb?: number;
+	  }
+	| { event: "judge_done"; verdict: string; score?: number };
+
+export type JudgeProgressMessage = JudgeProgressEvent & {
+	submission_id?: number;
+	problem_id?: number;
+};
@@ -0,0 +1,53 @@
+import type { Redis } from "ioredis";
+
+/**
+ * Judge job queues. Workers take jobs from the highest-priority non-empty
+ * queue first: contest > practice > validation > playground.
+ * Every job carries `queued_at` (Date.now() when pushed) so the judge can
+ * report how long jobs wait.
+ */
+export const JUDGE_QUEUES = {
+	contest: "judge:queue:contest",
+	practice: "judge:queue",
+	validation: "judge:queue:validation",
+	playground: "judge:queue:playground",
+} as const;
+
+/**
+ * Setting `${JUDGE_CANCEL_PREFIX}${submissionId}` makes the judge stop that
+ * submission and store a "cancelled" result. The key expires in case the
+ * submission was already judged.
+ */
+export const JUDGE_CANCEL_PREFIX = "judge:cancel:";
+export const JUDGE_CANCEL_TTL_SECONDS = 3600;
+
+/**
+ * Jobs the judge could not parse or that kept failing with a system error.
+ * Each entry is JSON: { payload, queue, error, retry_count, failed_at }.
+ */
+export const JUDGE_DEAD_LETTER_QUEUE = "judge:dead_letter";
+
+/**
+ * Queue for a submission, depending on whether it belongs to a contest
+ */
+export function submissionQueue(contestId?: number | null): string {
+	return contestId ? JUDGE_QUEUES.contest : JUDGE_QUEUES.practice;
+}
+
+/**
+ * Push a submission's job to its queue. A cancel request left over from an
+ * earlier run of the submission (e.g. before a rejudge) is removed first,
+ * since it would otherwise stop the new run until it expires.
+ */
+export async function enqueueSubmissionJob(
+	redis: Redis,
+	submissionId: number,
+	contestId: number | null | undefined,
+	jobData: string
+): Promise<void> {
+	await redis
+		.multi()
+		.del(`${JUDGE_CANCEL_PREFIX}${submissionId}`)
+		.rpush(submissionQueue(contestId), jobData)
+		.exec();
+}
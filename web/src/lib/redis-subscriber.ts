@@ -5,6 +5,7 @@ import { Redis } from "ioredis";
 import { db } from "@/db";
 import { submissionResults, submissions, type Verdict } from "@/db/schema";
 import { serverEnv } from "@/lib/env";
+import type { JudgeProgressMessage } from "@/lib/judge-progress";
 import { notifySubmissionUpdate } from "./sse-manager";
 
 interface JudgeResult {
@@ -100,15 +101,13 @@ class RedisSubscriber {
 
 	private async handleProgressMessage(message: string) {
 		try {
-			const progress = JSON.parse(message);
-			const submissionId = progress.submission_id;
-			const percentage = progress.percentage;
-
-			console.log(`Progress for submission ${submissionId}: ${percentage}%`);
+			const { submission_id: submissionId, ...event }: JudgeProgressMessage = JSON.parse(message);
+			// Validation progress has no submission to notify
+			if (submissionId === undefined) return;
 
 			// Notify SSE clients about progress
 			const { notifySubmissionProgress } = await import("./sse-manager");
-			notifySubmissionProgress(submissionId, percentage);
+			notifySubmissionProgress(submissionId, event);
 		} catch (error) {
 			console.error("Error processing progress message:", error);
 		}
@@ -1,5 +1,7 @@
 import "server-only";
 
+import type { JudgeProgressEvent } from "@/lib/judge-progress";
+
 type SSEClient = {
 	controller: ReadableStreamDefaultController;
 	encoder: TextEncoder;
@@ -83,7 +85,7 @@ export function sendHeartbeat(client: SSEClient) {
 /**
  * Notify all clients watching a submission about progress
  */
-export function notifySubmissionProgress(submissionId: number, percentage: number) {
+export function notifySubmissionProgress(submissionId: number, event: JudgeProgressEvent) {
 	const sseClients = getClientsMap();
 	const clients = sseClients.get(submissionId);
 
@@ -95,7 +97,7 @@ export function notifySubmissionProgress(submissionId: number, percentage: numbe
 
 	for (const client of clientsArray) {
 		try {
-			sendEvent(client, "progress", JSON.stringify({ percentage }));
+			sendEvent(client, "progress", JSON.stringify(event));
 		} catch (error) {
 			console.error("Error sending progress to SSE client:", error);
 			clients.delete(client);